@@ -0,0 +1,49 @@
+//! Measures the cost, in time and allocations, of serving `GET /` from a minimal app.
+//!
+//! Run with `cargo bench`; the allocations per request are printed along with the timing.
+
+#![feature(test, futures_api, async_await)]
+
+extern crate test;
+
+use http_service::Body;
+use http_service_mock::make_server;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use test::Bencher;
+
+/// The system allocator, counting allocations.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[bench]
+fn get_root(b: &mut Bencher) {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "Hello, world!");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let mut requests = 0;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    b.iter(|| {
+        requests += 1;
+        let req = http::Request::get("/").body(Body::empty()).unwrap();
+        server.simulate(req).unwrap()
+    });
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    eprintln!("GET /: {} allocations per request", allocations / requests.max(1));
+}
@@ -52,5 +52,5 @@ fn main() {
     app.at("/echo/json").post(echo_json);
     app.at("/echo/form").post(echo_form);
 
-    app.serve();
+    app.serve().unwrap();
 }
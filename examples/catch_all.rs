@@ -10,5 +10,5 @@ fn main() {
         router.at("*").get(echo_path);
     });
 
-    app.serve();
+    app.serve().unwrap();
 }
@@ -44,5 +44,5 @@ fn main() {
     app.config(app_config);
 
     app.at("/").get(reply);
-    app.serve();
+    app.serve().unwrap();
 }
@@ -34,5 +34,5 @@ fn main() {
     let mut app = tide::App::new(());
     app.at("/").get(hello_cookies);
 
-    app.serve();
+    app.serve().unwrap();
 }
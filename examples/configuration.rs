@@ -30,5 +30,5 @@ fn main() {
     app.at("add_one/{}").get(add); // `IncreaseBy` is set to 1
     app.at("add_two/{}").get(add).config(IncreaseBy(2)); // `IncreaseBy` is overridden to 2
 
-    app.serve();
+    app.serve().unwrap();
 }
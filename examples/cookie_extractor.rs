@@ -14,5 +14,5 @@ fn main() {
 
     let address = "127.0.0.1:8000".to_owned();
     println!("Server is listening on http://{}", address);
-    app.serve();
+    app.serve().unwrap();
 }
@@ -9,5 +9,5 @@ fn main() {
 
     app.default_handler(async || "¯\\_(ツ)_/¯".with_status(StatusCode::NOT_FOUND));
 
-    app.serve()
+    app.serve().unwrap()
 }
@@ -13,5 +13,5 @@ fn main() {
 
     app.at("/").get(async || "Hello, world!");
 
-    app.serve();
+    app.serve().unwrap();
 }
@@ -63,5 +63,5 @@ fn main() {
 
     app.at("/graphql").post(handle_graphql);
 
-    app.serve();
+    app.serve().unwrap();
 }
@@ -4,5 +4,5 @@ fn main() {
     let mut app = tide::App::new(());
     app.at("/").get(async || "Hello, world!");
 
-    app.serve();
+    app.serve().unwrap();
 }
@@ -60,20 +60,15 @@ async fn set_message(
 async fn get_message(
     mut db: AppData<Database>,
     id: head::Path<usize>,
-) -> Result<body::Json<Message>, StatusCode> {
-    if let Some(msg) = db.get(*id) {
-        Ok(body::Json(msg))
-    } else {
-        Err(StatusCode::NOT_FOUND)
-    }
+) -> Option<body::Json<Message>> {
+    db.get(*id).map(body::Json)
 }
 
 fn main() {
     let mut app = App::new(Database::default());
 
     app.at("/message").post(new_message);
-    app.at("/message/{}").get(get_message);
-    app.at("/message/{}").post(set_message);
+    app.at("/message/{}").get(get_message).post(set_message);
 
-    app.serve();
+    app.serve().unwrap();
 }
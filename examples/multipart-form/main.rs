@@ -65,7 +65,7 @@ fn main() {
 
     app.at("/upload_file").post(upload_file);
 
-    app.serve();
+    app.serve().unwrap();
 }
 
 // Test with:
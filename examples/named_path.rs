@@ -25,5 +25,5 @@ fn main() {
     let mut app = tide::App::new(());
     app.at("add_two/{num}").get(add_two);
 
-    app.serve();
+    app.serve().unwrap();
 }
@@ -34,5 +34,5 @@ fn main() {
     let mut app = tide::App::new(());
     app.at("add_two").nest(build_add_two);
 
-    app.serve();
+    app.serve().unwrap();
 }
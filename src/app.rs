@@ -1,21 +1,38 @@
-use futures::future::{self, FutureObj};
-use http_service::HttpService;
+#[cfg(feature = "hyper")]
+use futures::compat::{Compat, Compat01As03};
+use futures::future::{self, Future, FutureObj};
+use futures::Stream;
+use futures_timer::Delay;
+use http_service::{Body, HttpService};
 use std::{
     any::Any,
-    fmt::Debug,
+    collections::HashMap,
+    fmt::{self, Debug},
+    net::SocketAddr,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{LocalWaker, Poll},
+    time::{Duration, Instant},
 };
 
 use crate::{
-    configuration::{Configuration, Store},
+    configuration::{Configuration, RequestTimeout, Store, TrailingSlash},
     endpoint::BoxedEndpoint,
     endpoint::Endpoint,
     extract::Extract,
-    middleware::{logger::RootLogger, RequestContext},
-    router::{EndpointData, Resource, RouteResult, Router},
-    Middleware, Request, Response, RouteMatch,
+    forwarded::{self, IpRange},
+    head::RoutePattern,
+    health::{self, Health},
+    middleware::{logger::RootLogger, timing::RouteTiming, RequestContext},
+    request::ReceivedAt,
+    router::{insert_name, EndpointData, Resource, RouteMeta, RouteResult, Router, Urls},
+    Error, IntoResponse, Middleware, Request, Response, RouteMatch,
 };
+#[cfg(feature = "hyper")]
+use crate::serve::Security;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use crate::tls::TlsConfig;
 
 /// The top-level type for setting up a Tide application.
 ///
@@ -33,7 +50,7 @@ use crate::{
 ///
 /// let mut app = tide::App::new(());
 /// app.at("/hello").get(async || "Hello, world!");
-/// app.serve()
+/// app.serve().unwrap()
 /// ```
 ///
 /// `App` state can be modeled with an underlying `Data` handle for a cloneable type `T`. Endpoints
@@ -64,7 +81,7 @@ use crate::{
 /// fn main() {
 ///     let mut app = tide::App::new(Database::default());
 ///     app.at("/messages/insert").post(insert);
-///     app.serve()
+///     app.serve().unwrap()
 /// }
 /// ```
 ///
@@ -75,8 +92,24 @@ pub struct App<Data> {
     data: Data,
     router: Router<Data>,
     default_handler: EndpointData<Data>,
+    response_hooks: Vec<ResponseHook>,
+    trusted_proxies: Vec<IpRange>,
+    hosts: Vec<(String, Router<Data>)>,
+    spa_fallback: Option<String>,
+    start_hooks: Vec<LifecycleHook<Data>>,
+    stop_hooks: Vec<LifecycleHook<Data>>,
+    health: Option<(String, String)>,
+    readiness_checks: Vec<health::Check<Data>>,
+    readiness_timeout: Duration,
+    logger: slog::Logger,
 }
 
+/// A function applied to every response after the middleware chain has run.
+type ResponseHook = Box<dyn Fn(Response) -> Response + Send + Sync>;
+
+/// A function run with the app data when the server starts or stops.
+type LifecycleHook<Data> = Box<dyn Fn(Data) -> FutureObj<'static, ()> + Send + Sync>;
+
 impl<Data: Clone + Send + Sync + 'static> App<Data> {
     /// Set up a new app with some initial `data`.
     pub fn new(data: Data) -> App<Data> {
@@ -87,7 +120,19 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
             default_handler: EndpointData {
                 endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
                 store: Store::new(),
+                meta: RouteMeta::default(),
+                middleware: None,
             },
+            response_hooks: Vec::new(),
+            trusted_proxies: Vec::new(),
+            hosts: Vec::new(),
+            spa_fallback: None,
+            start_hooks: Vec::new(),
+            stop_hooks: Vec::new(),
+            health: None,
+            readiness_checks: Vec::new(),
+            readiness_timeout: health::DEFAULT_CHECK_TIMEOUT,
+            logger: logger.logger(),
         };
 
         // Add RootLogger as a default middleware
@@ -114,6 +159,13 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
         self.router.at(path)
     }
 
+    /// Group routes under `prefix`.
+    /// See [Router.scope](struct.Router.html#method.scope) for details.
+    pub fn scope(&mut self, prefix: &str, builder: impl FnOnce(&mut Router<Data>)) -> &mut Self {
+        self.router.scope(prefix, builder);
+        self
+    }
+
     /// Set the default handler for the app, a fallback function when there is no match to the route requested
     pub fn default_handler<T: Endpoint<Data, U>, U>(
         &mut self,
@@ -122,6 +174,8 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
         let endpoint = EndpointData {
             endpoint: BoxedEndpoint::new(handler),
             store: self.router.store_base.clone(),
+            meta: RouteMeta::default(),
+            middleware: None,
         };
         self.default_handler = endpoint;
         &mut self.default_handler
@@ -130,7 +184,225 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
     /// Apply `middleware` to the whole app. Note that the order of nesting subrouters and applying
     /// middleware matters; see `Router` for details.
     pub fn middleware(&mut self, middleware: impl Middleware<Data> + 'static) -> &mut Self {
-        self.router.middleware(middleware);
+        let middleware: Arc<dyn Middleware<Data> + Send + Sync> = Arc::new(middleware);
+        self.router.apply_middleware(middleware.clone());
+        for (_, router) in &mut self.hosts {
+            router.apply_middleware(middleware.clone());
+        }
+        self
+    }
+
+    /// Match the concrete segments of request paths regardless of their ASCII case, so that
+    /// `/Users/42` reaches the resource at `/users/{id}`.
+    ///
+    /// Wildcard segments are still passed to endpoints as sent. This covers host routers as well,
+    /// and must be set up before adding resources:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.case_insensitive(true);
+    /// app.at("/Users/{id}").get(async || "user");
+    /// ```
+    ///
+    /// Panics if resources were already added.
+    pub fn case_insensitive(&mut self, enabled: bool) -> &mut Self {
+        self.router.set_case_insensitive(enabled);
+        for (_, router) in &mut self.hosts {
+            router.set_case_insensitive(enabled);
+        }
+        self
+    }
+
+    /// Get the router for requests addressed to `host`, as given by the `Host` header.
+    ///
+    /// Requests for hosts without a router of their own are routed by the top-level router. A
+    /// leading `*.` matches any subdomain, with exact names taking precedence, and then the
+    /// longest matching wildcard, whatever the order the hosts were added in:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.host("api.example.com").at("/").get(async || "api");
+    /// app.host("*.example.com").at("/").get(async || "tenant site");
+    /// app.at("/").get(async || "main site");
+    /// ```
+    ///
+    /// Host routers start out with the middleware applied to the app so far, and middleware
+    /// applied to the app later covers them too. They inherit the app's configuration, and share
+    /// its default handler.
+    ///
+    /// Route names are shared by all hosts: the server panics on startup if two routers use the
+    /// same name.
+    pub fn host(&mut self, host: &str) -> &mut Router<Data> {
+        let host = host.to_ascii_lowercase();
+        let index = match self.hosts.iter().position(|(name, _)| *name == host) {
+            Some(index) => index,
+            None => {
+                let router = self.router.sibling();
+                self.hosts.push((host, router));
+                self.hosts.len() - 1
+            }
+        };
+        &mut self.hosts[index].1
+    }
+
+    /// Add a hook post-processing every response, after the whole middleware chain has run.
+    ///
+    /// Hooks also see the responses generated by the framework itself, like the ones of the
+    /// default handler, which makes them suitable for last-mile concerns such as scrubbing
+    /// headers. They run in the order they were added.
+    ///
+    /// ```rust, no_run
+    /// let mut app = tide::App::new(());
+    /// app.map_response(|mut res| {
+    ///     res.headers_mut().remove("X-Powered-By");
+    ///     res
+    /// });
+    /// ```
+    pub fn map_response(
+        &mut self,
+        hook: impl Fn(Response) -> Response + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.response_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` with the app data once the server listens, before it handles any connection.
+    ///
+    /// This is the place to warm up connection pools or prime caches; connections wait in the
+    /// listen backlog meanwhile. Hooks run one after the other, in the order they were added:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await, futures_api)]
+    /// let mut app = tide::App::new(());
+    /// app.on_start(async move |_| println!("warming up"));
+    /// ```
+    pub fn on_start<F, Fut>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(Data) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.start_hooks
+            .push(Box::new(move |data| FutureObj::new(Box::new(hook(data)))));
+        self
+    }
+
+    /// Run `hook` with the app data once the server has shut down gracefully, e.g. to flush
+    /// metrics.
+    ///
+    /// Stop hooks run after in-flight requests have completed or the `shutdown_timeout` has
+    /// elapsed, in the order they were added. They do not run when the server fails.
+    pub fn on_stop<F, Fut>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(Data) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.stop_hooks
+            .push(Box::new(move |data| FutureObj::new(Box::new(hook(data)))));
+        self
+    }
+
+    /// Serve a liveness probe at `live` and a readiness probe at `ready`, for orchestrators
+    /// such as Kubernetes.
+    ///
+    /// The liveness probe always answers `200 OK`. The readiness probe runs the checks
+    /// registered with `readiness_check`, answering `200 OK` if all of them pass and
+    /// `503 Service Unavailable` otherwise, with a JSON report of every check.
+    ///
+    /// The probes are answered for every host, ahead of routing and middleware, so that neither
+    /// host routing nor authentication can hide them, and they take precedence over routes at
+    /// the same paths:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await, futures_api)]
+    /// let mut app = tide::App::new(());
+    /// app.enable_health("/healthz", "/readyz")
+    ///     .readiness_check("db", async move |_| Ok(()));
+    /// ```
+    pub fn enable_health(&mut self, live: &str, ready: &str) -> &mut Self {
+        self.health = Some((live.to_owned(), ready.to_owned()));
+        self
+    }
+
+    /// Add a check to the readiness probe of `enable_health`, failing with a message describing
+    /// what is not ready.
+    ///
+    /// Checks run one after the other with a clone of the app data, in the order they were
+    /// added, on every probe. A check taking longer than the `readiness_timeout` fails.
+    ///
+    /// Panics if a check with the same name was already added.
+    pub fn readiness_check<F, Fut>(&mut self, name: &str, check: F) -> &mut Self
+    where
+        F: Fn(Data) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        if self.readiness_checks.iter().any(|added| added.name == name) {
+            panic!("Duplicate readiness check `{}`", name);
+        }
+        self.readiness_checks.push(health::Check {
+            name: name.to_owned(),
+            check: Box::new(move |data| FutureObj::new(Box::new(check(data)))),
+        });
+        self
+    }
+
+    /// Fail each readiness check that takes longer than `timeout`, one second by default.
+    pub fn readiness_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.readiness_timeout = timeout;
+        self
+    }
+
+    /// Trust the reverse proxies connecting from the given IP ranges, in CIDR notation.
+    ///
+    /// For requests arriving from a trusted proxy, the `Forwarded` header (or, in its absence, the
+    /// `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers) determines the
+    /// client address reported by `ConnectionInfo`, as well as the scheme and host of the request
+    /// URI and `Host` header. This happens before any middleware runs, so everything from the
+    /// `client_ip` of the access log to redirects sees the same request. The client port is 0 if
+    /// the proxy does not report it.
+    ///
+    /// ```rust, no_run
+    /// let mut app = tide::App::new(());
+    /// app.trust_proxies(&["10.0.0.0/8", "::1"]);
+    /// ```
+    ///
+    /// Panics if a range is not a valid IP address or CIDR range.
+    pub fn trust_proxies<I>(&mut self, ranges: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for range in ranges {
+            let range = range.as_ref();
+            match IpRange::parse(range) {
+                Some(parsed) => self.trusted_proxies.push(parsed),
+                None => panic!("Invalid trusted proxy range `{}`", range),
+            }
+        }
+        self
+    }
+
+    /// Route unmatched `GET` requests from browsers to the resource at `path`, as single-page
+    /// apps doing their own client-side routing need.
+    ///
+    /// This applies to requests accepting `text/html` that would otherwise reach the app's
+    /// default handler. They are handled as if they were for `path`, with their URI left as sent,
+    /// so that the page can pick it up. Other requests are unaffected, and so are paths covered
+    /// by a subrouter fallback, so an API can keep answering with its own errors:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await)]
+    /// let mut app = tide::App::new(());
+    /// app.at("/index.html").get(async || "<!DOCTYPE html>...");
+    /// app.at("/api").nest(|router| {
+    ///     router.default_handler(async || http::StatusCode::NOT_FOUND);
+    ///     router.at("/users").get(async || "[]");
+    /// });
+    /// app.spa_fallback("/index.html");
+    /// ```
+    pub fn spa_fallback(&mut self, path: &str) -> &mut Self {
+        self.spa_fallback = Some(path.to_owned());
         self
     }
 
@@ -145,81 +417,733 @@ impl<Data: Clone + Send + Sync + 'static> App<Data> {
     }
 
     /// Make this app into an `HttpService`.
-    pub fn into_http_service(mut self) -> Server<Data> {
-        self.router.apply_default_config();
+    pub fn into_http_service(self) -> Server<Data> {
+        let (data, state) = self.into_state();
         Server {
-            data: self.data,
+            inner: Arc::new(Inner {
+                data: Arc::new(data),
+                state: RwLock::new(Arc::new(state)),
+            }),
+        }
+    }
+
+    fn into_state(mut self) -> (Data, ServerState<Data>) {
+        let checks = std::mem::replace(&mut self.readiness_checks, Vec::new());
+        let timeout = self.readiness_timeout;
+        let health = self
+            .health
+            .take()
+            .map(|(live, ready)| Health::new(live, ready, checks, timeout));
+        self.router.apply_default_config();
+        self.default_handler.store.merge(&self.router.store_base);
+        let mut names = std::mem::replace(&mut self.router.names, HashMap::new());
+        for (_, router) in &mut self.hosts {
+            router.store_base.merge(&self.router.store_base);
+            router.apply_default_config();
+            for (name, path) in router.names.drain() {
+                insert_name(&mut names, name, path);
+            }
+        }
+        let state = ServerState {
             router: Arc::new(self.router),
+            hosts: self.hosts,
             default_handler: Arc::new(self.default_handler),
-        }
+            response_hooks: self.response_hooks,
+            trusted_proxies: self.trusted_proxies,
+            urls: Urls::new(names),
+            spa_fallback: self.spa_fallback,
+            health,
+            start_hooks: self.start_hooks,
+            stop_hooks: self.stop_hooks,
+            logger: self.logger,
+        };
+        (self.data, state)
     }
 
     /// Start serving the app at the given address.
     ///
-    /// Blocks the calling thread indefinitely.
+    /// Blocks the calling thread indefinitely, unless the server fails to start (e.g. because
+    /// the address is invalid or in use) or stops with an error.
+    #[cfg(feature = "hyper")]
+    pub fn serve(self) -> Result<(), Error> {
+        self.serve_until(None, Security::None, None)
+    }
+
+    /// Start serving the app on a listener bound by the caller, like `serve`.
+    ///
+    /// The address of the `Configuration` is ignored. This is how to receive the socket of a
+    /// service manager, e.g. with systemd socket activation through `tide::systemd_listener`, or
+    /// to inherit it from a restart manager, so connections are never refused while the server
+    /// is started or upgraded.
+    #[cfg(feature = "hyper")]
+    pub fn serve_listener(self, listener: std::net::TcpListener) -> Result<(), Error> {
+        self.serve_until(Some(listener), Security::None, None)
+    }
+
+    /// Start serving the app over HTTPS at the given address, terminating TLS with `tls`.
+    ///
+    /// This behaves like `serve`, without needing a separate reverse proxy for HTTPS. With the
+    /// PROXY protocol enabled, its header is expected before the TLS handshake, as load balancers
+    /// passing TLS through send it. Requires the `tls` (rustls) or `native-tls` feature.
+    ///
+    /// ```rust, ignore
+    /// use tide::tls::TlsConfig;
+    ///
+    /// let tls = TlsConfig::from_pem_files("cert.pem", "key.pem").unwrap();
+    /// let mut app = tide::App::new(());
+    /// app.serve_tls(tls).unwrap();
+    /// ```
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    pub fn serve_tls(self, tls: TlsConfig) -> Result<(), Error> {
+        self.serve_until(None, Security::Tls(tls), None)
+    }
+
+    /// Start serving the app at the given address, until `signal` resolves.
+    ///
+    /// The server then stops accepting connections, and waits for in-flight requests to complete
+    /// for at most the `shutdown_timeout` of the `Configuration`, before dropping the remaining
+    /// connections and returning. With the `signals` feature, `tide::shutdown_signal` resolves
+    /// when the process is asked to stop. Errors are reported as for `serve`:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await, await_macro, futures_api)]
+    /// let (_stop, stopped) = futures::channel::oneshot::channel::<()>();
+    /// let mut app = tide::App::new(());
+    /// app.at("/").get(async || "Hello, world!");
+    /// // ... hand `stop` to whatever decides when to shut down.
+    /// app.serve_with_shutdown(async move {
+    ///     let _ = await!(stopped);
+    /// })
+    /// .unwrap();
+    /// ```
     #[cfg(feature = "hyper")]
-    pub fn serve(self) {
-        let configuration = self.get_item::<Configuration>().unwrap();
-        let addr = format!("{}:{}", configuration.address, configuration.port)
-            .parse::<std::net::SocketAddr>()
-            .unwrap();
+    pub fn serve_with_shutdown(
+        self,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<(), Error> {
+        self.serve_until(None, Security::None, Some(FutureObj::new(Box::new(signal))))
+    }
 
-        println!("Server is listening on: http://{}", addr);
+    #[cfg(feature = "hyper")]
+    fn serve_until(
+        self,
+        listener: Option<std::net::TcpListener>,
+        security: Security,
+        signal: Option<FutureObj<'static, ()>>,
+    ) -> Result<(), Error> {
+        use std::net::ToSocketAddrs;
+
+        let configuration = self.get_item::<Configuration>().unwrap().clone();
+        let bound = match listener {
+            Some(listener) => self.into_http_service().bind_listener(listener)?,
+            None => {
+                let address = format!("{}:{}", configuration.address, configuration.port);
+                let addr = address
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                    .ok_or_else(|| Error::Address(address))?;
+                self.into_http_service().bind(addr)?
+            }
+        };
+
+        let scheme = match security {
+            Security::None => "http",
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            Security::Tls(_) => "https",
+        };
+        println!("Server is listening on: {}://{}", scheme, bound.local_addr());
 
-        crate::serve::serve(self.into_http_service(), addr);
+        let serving = Compat::new(bound.run_until(security, signal));
+        crate::serve::block_on(Box::new(serving), &configuration)
     }
 }
 
-#[derive(Clone)]
+/// A built Tide application, ready to serve requests as an `HttpService`.
+///
+/// Created by `App::into_http_service`. Clones of a server share everything behind one `Arc`, so
+/// cloning it never clones the app data.
 pub struct Server<Data> {
-    data: Data,
-    router: Arc<Router<Data>>,
-    default_handler: Arc<EndpointData<Data>>,
+    inner: Arc<Inner<Data>>,
 }
 
-impl<Data> HttpService for Server<Data>
-where
-    Data: Clone + Send + Sync + 'static,
-{
-    type Connection = ();
-    type ConnectionFuture = future::Ready<Result<(), std::io::Error>>;
-    type Fut = FutureObj<'static, Result<http_service::Response, std::io::Error>>;
+/// What the clones of a server share.
+struct Inner<Data> {
+    /// The app data, handed out as is by `SharedData` and cloned once per request for
+    /// `RequestContext::app_data`, from which `AppData` is taken.
+    data: Arc<Data>,
+    state: RwLock<Arc<ServerState<Data>>>,
+}
 
-    fn connect(&self) -> Self::ConnectionFuture {
-        future::ok(())
+impl<Data> Clone for Server<Data> {
+    fn clone(&self) -> Server<Data> {
+        Server {
+            inner: self.inner.clone(),
+        }
     }
+}
 
-    fn respond(&self, _conn: &mut (), req: http_service::Request) -> Self::Fut {
-        let data = self.data.clone();
-        let router = self.router.clone();
-        let default_handler = self.default_handler.clone();
-        let path = req.uri().path().to_owned();
-        let method = req.method().to_owned();
+/// Everything a server got from its app except the data, replaced as a whole by `Server::reload`.
+struct ServerState<Data> {
+    router: Arc<Router<Data>>,
+    hosts: Vec<(String, Router<Data>)>,
+    default_handler: Arc<EndpointData<Data>>,
+    response_hooks: Vec<ResponseHook>,
+    trusted_proxies: Vec<IpRange>,
+    urls: Urls,
+    spa_fallback: Option<String>,
+    health: Option<Health<Data>>,
+    start_hooks: Vec<LifecycleHook<Data>>,
+    stop_hooks: Vec<LifecycleHook<Data>>,
+    logger: slog::Logger,
+}
 
+impl<Data: Clone + Send + Sync + 'static> Server<Data> {
+    /// Route `req` through the router and middleware in-process, and return the response.
+    ///
+    /// This runs the same routing and middleware as a request received over HTTP, except for the
+    /// `App::trust_proxies` rewriting and `App::map_response` hooks, which apply to the outermost
+    /// request only. Endpoints reach the server through the `Dispatcher` extractor.
+    pub fn dispatch(&self, req: Request) -> FutureObj<'static, Response> {
+        self.dispatch_with(self.state(), req)
+    }
+
+    /// Dispatch `req` with `state`, loaded once for the whole request.
+    fn dispatch_with(
+        &self,
+        state: Arc<ServerState<Data>>,
+        mut req: Request,
+    ) -> FutureObj<'static, Response> {
+        let server = self.clone();
+        if let Some(health) = &state.health {
+            if let Some(res) = health.probe(&req, &*self.inner.data) {
+                return res;
+            }
+        }
         FutureObj::new(Box::new(
             async move {
+                let start = Instant::now();
+                req.extensions_mut().insert(ReceivedAt(start));
+                req.extensions_mut().insert(Dispatcher(server.clone()));
+                req.extensions_mut()
+                    .insert(SharedAppData(server.inner.data.clone()));
+                req.extensions_mut().insert(state.urls.clone());
+                let path = req.uri().path().to_owned();
+                let method = req.method().to_owned();
+                let router = state.router_for(&req);
+                let routing_path = router.routing_path(&path);
+                let fallback_path;
+                let mut routed = match &routing_path {
+                    Some(routing_path) => router
+                        .route(routing_path, &method, &req, &state.default_handler)
+                        .restore_case(routing_path, &path),
+                    None => router.route(&path, &method, &req, &state.default_handler),
+                };
+                if let Some(spa_fallback) = &state.spa_fallback {
+                    if std::ptr::eq(routed.endpoint, &*state.default_handler)
+                        && method == http::Method::GET
+                        && accepts_html(&req)
+                    {
+                        // Routed like any request path, e.g. lowercased when case-insensitive.
+                        fallback_path = router.routing_path(spa_fallback);
+                        let spa_fallback = fallback_path.as_ref().unwrap_or(spa_fallback);
+                        routed = router.route(spa_fallback, &method, &req, &state.default_handler);
+                    }
+                }
                 let RouteResult {
                     endpoint,
                     params,
                     middleware,
-                } = router.route(&path, &method, &default_handler);
+                    pattern,
+                } = routed;
+                let max_buffered = endpoint
+                    .store
+                    .read::<Configuration>()
+                    .and_then(|configuration| configuration.max_buffered_body_size);
+                if let Some(max) = max_buffered {
+                    crate::body::limit_buffered(&mut req, max);
+                }
+                req.extensions_mut().insert(RouteTiming(start.elapsed()));
+                req.extensions_mut()
+                    .insert(RoutePattern(pattern.map(str::to_owned)));
+                let response = crate::body::failure(&req).or_else(|| {
+                    match endpoint.store.read::<TrailingSlash>() {
+                        Some(TrailingSlash::Redirect) => redirect_trailing_slash(&req),
+                        _ => None,
+                    }
+                });
 
+                let deadline = endpoint
+                    .store
+                    .read::<RequestTimeout>()
+                    .and_then(|timeout| start.checked_add(timeout.0));
                 let ctx = RequestContext {
-                    app_data: data,
+                    app_data: (*server.inner.data).clone(),
                     req,
                     params,
                     endpoint,
                     next_middleware: middleware,
+                    route_pattern: pattern,
+                    response,
+                    deadline,
                 };
-                Ok(await!(ctx.next()))
+                await!(ctx.next())
+            },
+        ))
+    }
+
+    /// Replace the routes of this server, and of all its clones, by those of `app`.
+    ///
+    /// This lets the routing table change while the server is running, e.g. when plugins are
+    /// installed or an administrator edits pages, by rebuilding the app from scratch:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(futures_api)]
+    /// fn build(pages: &[&'static str]) -> tide::App<()> {
+    ///     let mut app = tide::App::new(());
+    ///     for &page in pages {
+    ///         app.at(page).get(move || futures::future::ready(page));
+    ///     }
+    ///     app
+    /// }
+    ///
+    /// let server = build(&["/about"]).into_http_service();
+    /// // ... later, from an admin endpoint or a background task:
+    /// server.reload(build(&["/about", "/contact"]));
+    /// ```
+    ///
+    /// Everything set up on `app`, from middleware and configuration to response hooks and
+    /// trusted proxies, replaces what the server had, except for the data: the server keeps
+    /// handing out its own, and the data of `app` is dropped. Requests already being handled
+    /// finish with the routes they started with.
+    pub fn reload(&self, app: App<Data>) {
+        let (_, state) = app.into_state();
+        *self.inner.state.write().unwrap() = Arc::new(state);
+    }
+}
+
+impl<Data> Server<Data> {
+    /// The current state, which stays valid for a request even if the server is reloaded.
+    fn state(&self) -> Arc<ServerState<Data>> {
+        self.inner.state.read().unwrap().clone()
+    }
+
+    /// Get the app data this server hands out to its endpoints.
+    pub fn data(&self) -> &Data {
+        &*self.inner.data
+    }
+
+    /// Get the top-level router this server dispatches requests with.
+    pub fn router(&self) -> Arc<Router<Data>> {
+        self.state().router.clone()
+    }
+
+    /// Serve at `addr`, as a future to run on a tokio runtime set up by the caller.
+    ///
+    /// Unlike `App::serve`, this leaves the runtime to the application, so the server can run
+    /// alongside other tasks such as database pools or queue consumers. The address of the
+    /// `Configuration` is ignored, the rest of it applies:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await, futures_api)]
+    /// use futures::TryFutureExt;
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/").get(async || "Hello, world!");
+    /// let server = app.into_http_service().run(([127, 0, 0, 1], 8181).into());
+    ///
+    /// tokio::run(server.map_err(|e| eprintln!("{}", e)).compat());
+    /// ```
+    ///
+    /// The future fails if the server cannot listen at `addr`, and otherwise only resolves on
+    /// errors. The listener is set up on first poll, on the runtime's reactor.
+    #[cfg(feature = "hyper")]
+    pub fn run(self, addr: SocketAddr) -> FutureObj<'static, Result<(), Error>>
+    where
+        Data: Clone + Send + Sync + 'static,
+    {
+        self.run_until(addr, None)
+    }
+
+    /// Serve at `addr` until `signal` resolves, as a future to run on a tokio runtime set up by
+    /// the caller.
+    ///
+    /// This shuts down gracefully like `App::serve_with_shutdown`: the requests still in flight
+    /// after the `shutdown_timeout` are aborted, closing their connections, even though the
+    /// runtime keeps running.
+    #[cfg(feature = "hyper")]
+    pub fn run_with_shutdown(
+        self,
+        addr: SocketAddr,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> FutureObj<'static, Result<(), Error>>
+    where
+        Data: Clone + Send + Sync + 'static,
+    {
+        self.run_until(addr, Some(FutureObj::new(Box::new(signal))))
+    }
+
+    #[cfg(feature = "hyper")]
+    fn run_until(
+        self,
+        addr: SocketAddr,
+        signal: Option<FutureObj<'static, ()>>,
+    ) -> FutureObj<'static, Result<(), Error>>
+    where
+        Data: Clone + Send + Sync + 'static,
+    {
+        match self.bind(addr) {
+            Ok(bound) => bound.run_until(Security::None, signal),
+            Err(e) => FutureObj::new(Box::new(future::err(e))),
+        }
+    }
+
+    /// Serve the connections of `incoming`, as a future to run on a tokio runtime set up by the
+    /// caller.
+    ///
+    /// This serves transports other than TCP, such as in-memory pipes for tests, UNIX or vsock
+    /// sockets, or connections already decrypted by a TLS terminator. `ConnectionInfo` reports
+    /// no addresses for them, and the `Configuration` settings about accepting connections,
+    /// their sockets and the PROXY protocol do not apply. The future resolves once `incoming`
+    /// ends and the last connection is closed, or when the server fails.
+    #[cfg(feature = "hyper")]
+    pub fn run_incoming<I, IO>(self, incoming: I) -> FutureObj<'static, Result<(), Error>>
+    where
+        Data: Clone + Send + Sync + 'static,
+        I: futures::Stream<Item = Result<IO, std::io::Error>> + Send + Unpin + 'static,
+        IO: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    {
+        let server = self.clone();
+        let configuration = self.configuration();
+        let logger = self.state().logger.clone();
+        let serving = crate::serve::run_incoming(
+            self,
+            Compat::new(incoming),
+            &configuration,
+            logger,
+        );
+        with_lifecycle_hooks(server, serving)
+    }
+
+    /// Turn the server into a hyper `Service`, to be served by hyper directly.
+    #[cfg(feature = "hyper")]
+    pub fn into_hyper_service(self) -> crate::HyperService<Server<Data>>
+    where
+        Data: Clone + Send + Sync + 'static,
+    {
+        crate::serve::HyperService::new(self)
+    }
+
+    /// Start listening at `addr`, without serving yet.
+    ///
+    /// This makes the address known before the server runs, e.g. the port picked by the system
+    /// when binding to port 0, for tests and orchestration:
+    ///
+    /// ```rust, no_run
+    /// # #![feature(async_await, futures_api)]
+    /// use futures::TryFutureExt;
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.at("/").get(async || "Hello, world!");
+    /// let bound = app.into_http_service().bind(([127, 0, 0, 1], 0).into()).unwrap();
+    /// println!("listening on port {}", bound.local_addr().port());
+    ///
+    /// tokio::run(bound.run().map_err(|e| eprintln!("{}", e)).compat());
+    /// ```
+    ///
+    /// With both `reuse_port` and `workers` set in the `Configuration`, this binds one
+    /// `SO_REUSEPORT` socket per worker thread, each served by an accept loop of its own, so that
+    /// accepting connections is spread over the threads. The system then balances connections
+    /// among the sockets.
+    #[cfg(feature = "hyper")]
+    pub fn bind(self, addr: SocketAddr) -> Result<BoundServer<Data>, Error> {
+        let listeners = crate::serve::bind_all(addr, &self.configuration())?;
+        let local_addr = listeners[0]
+            .local_addr()
+            .map_err(|e| Error::Bind(Box::new(e)))?;
+        Ok(BoundServer {
+            server: self,
+            listeners,
+            local_addr,
+        })
+    }
+
+    /// Get ready to serve on a listener bound by the caller, like `App::serve_listener`.
+    #[cfg(feature = "hyper")]
+    pub fn bind_listener(
+        self,
+        listener: std::net::TcpListener,
+    ) -> Result<BoundServer<Data>, Error> {
+        let local_addr = listener.local_addr().map_err(|e| Error::Bind(Box::new(e)))?;
+        Ok(BoundServer {
+            server: self,
+            listeners: vec![listener],
+            local_addr,
+        })
+    }
+
+    /// The configuration of the top-level router.
+    #[cfg(feature = "hyper")]
+    fn configuration(&self) -> Configuration {
+        self.state()
+            .router
+            .get_item::<Configuration>()
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// A server listening at an address, ready to run.
+///
+/// Created by `Server::bind`. Connections are queued by the system until the server runs.
+#[cfg(feature = "hyper")]
+pub struct BoundServer<Data> {
+    server: Server<Data>,
+    listeners: Vec<std::net::TcpListener>,
+    local_addr: SocketAddr,
+}
+
+#[cfg(feature = "hyper")]
+impl<Data: Clone + Send + Sync + 'static> BoundServer<Data> {
+    /// The address the server listens at.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Serve, as a future to run on a tokio runtime, like `Server::run`.
+    pub fn run(self) -> FutureObj<'static, Result<(), Error>> {
+        self.run_until(Security::None, None)
+    }
+
+    /// Serve until `signal` resolves, as a future to run on a tokio runtime, like
+    /// `Server::run_with_shutdown`.
+    pub fn run_with_shutdown(
+        self,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> FutureObj<'static, Result<(), Error>> {
+        self.run_until(Security::None, Some(FutureObj::new(Box::new(signal))))
+    }
+
+    /// Serve with `security` until `signal` resolves, running the lifecycle hooks of the app
+    /// around it.
+    fn run_until(
+        self,
+        security: Security,
+        signal: Option<FutureObj<'static, ()>>,
+    ) -> FutureObj<'static, Result<(), Error>> {
+        let server = self.server.clone();
+        let configuration = self.server.configuration();
+        let logger = self.server.state().logger.clone();
+        let serving = crate::serve::run_all(
+            self.server,
+            self.listeners,
+            &configuration,
+            logger,
+            security,
+            signal,
+        );
+        with_lifecycle_hooks(server, serving)
+    }
+}
+
+/// Run `serving` between the start and stop hooks of the app of `server`.
+#[cfg(feature = "hyper")]
+fn with_lifecycle_hooks<Data>(
+    server: Server<Data>,
+    serving: crate::serve::Serving,
+) -> FutureObj<'static, Result<(), Error>>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    FutureObj::new(Box::new(
+        async move {
+            let state = server.state();
+            for hook in &state.start_hooks {
+                await!(hook(server.inner.data.clone()));
+            }
+            await!(Compat01As03::new(serving))?;
+            // The app may have been reloaded in the meantime.
+            let state = server.state();
+            for hook in &state.stop_hooks {
+                await!(hook(server.inner.data.clone()));
+            }
+            Ok(())
+        },
+    ))
+}
+
+impl<Data> ServerState<Data> {
+    /// The router for the host `req` is addressed to.
+    fn router_for(&self, req: &Request) -> &Router<Data> {
+        if self.hosts.is_empty() {
+            return &self.router;
+        }
+        let host = req
+            .uri()
+            .host()
+            .or_else(|| req.headers().get(http::header::HOST)?.to_str().ok())
+            .unwrap_or("");
+        let host = if host.starts_with('[') {
+            host.split(']').next().map_or(host, |ip| &host[..=ip.len()])
+        } else {
+            host.split(':').next().unwrap_or(host)
+        };
+        let host = host.to_ascii_lowercase();
+        let exact = self.hosts.iter().find(|(name, _)| *name == host);
+        let wildcard = || {
+            self.hosts
+                .iter()
+                .filter(|(name, _)| name.starts_with("*.") && host.ends_with(&name[1..]))
+                .max_by_key(|(name, _)| name.len())
+        };
+        exact.or_else(wildcard).map_or(&self.router, |(_, router)| router)
+    }
+}
+
+impl<Data> HttpService for Server<Data>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    type Connection = ();
+    type ConnectionFuture = future::Ready<Result<(), std::io::Error>>;
+    type Fut = FutureObj<'static, Result<http_service::Response, std::io::Error>>;
+
+    fn connect(&self) -> Self::ConnectionFuture {
+        future::ok(())
+    }
+
+    fn respond(&self, _conn: &mut (), mut req: http_service::Request) -> Self::Fut {
+        let state = self.state();
+        forwarded::apply(&mut req, &state.trusted_proxies);
+        let is_head = req.method() == http::Method::HEAD;
+        let max_body_size = state
+            .router
+            .get_item::<Configuration>()
+            .and_then(|configuration| configuration.max_body_size);
+        if let Some(max) = max_body_size {
+            crate::body::limit(&mut req, max);
+        }
+        let res = self.dispatch_with(state.clone(), req);
+        FutureObj::new(Box::new(
+            async move {
+                let res = await!(res);
+                let res = state.response_hooks.iter().fold(res, |res, hook| hook(res));
+                if is_head {
+                    Ok(await!(strip_body(res)))
+                } else {
+                    Ok(res)
+                }
             },
         ))
     }
 }
 
+/// Redirect a request whose path ends with a slash to the path without it.
+///
+/// Leading slashes are collapsed, as routing ignores empty segments anyway, so that a path such
+/// as `//example.com/` cannot redirect to another host.
+fn redirect_trailing_slash(req: &Request) -> Option<Response> {
+    let path = req.uri().path();
+    if !path.ends_with('/') {
+        return None;
+    }
+    let trimmed = path
+        .trim_end_matches('/')
+        .trim_start_matches(|c| c == '/' || c == '\\');
+    if trimmed.is_empty() {
+        return None;
+    }
+    let location = match req.uri().query() {
+        Some(query) => format!("/{}?{}", trimmed, query),
+        None => format!("/{}", trimmed),
+    };
+    let status = if req.method() == http::Method::GET || req.method() == http::Method::HEAD {
+        http::StatusCode::MOVED_PERMANENTLY
+    } else {
+        http::StatusCode::PERMANENT_REDIRECT
+    };
+    let res = http::Response::builder()
+        .status(status)
+        .header(http::header::LOCATION, location)
+        .body(Body::empty())
+        .ok()?;
+    Some(res)
+}
+
+/// How much of the body of a response to a `HEAD` request is read to report its length.
+const MAX_HEAD_BODY_SIZE: usize = 64 * 1024;
+
+/// Drop the body of a response to a `HEAD` request, reporting its `Content-Length`.
+///
+/// `HEAD` requests are served by the `GET` endpoint unless there is a `HEAD` endpoint. Unless
+/// the endpoint set the `Content-Length`, the body is read for as long as its chunks are ready,
+/// up to `MAX_HEAD_BODY_SIZE` bytes, so that a body held in memory has its length reported. A
+/// body that is longer, still pending or endless, like that of an event stream, is dropped
+/// without it.
+async fn strip_body(res: Response) -> Response {
+    let (mut parts, mut body) = res.into_parts();
+    let status = parts.status;
+    let has_body = !(status.is_informational()
+        || status == http::StatusCode::NO_CONTENT
+        || status == http::StatusCode::NOT_MODIFIED);
+    if has_body && !parts.headers.contains_key(http::header::CONTENT_LENGTH) {
+        if let Some(len) = await!(future::poll_fn(|waker| Poll::Ready(ready_len(&mut body, waker))))
+        {
+            parts.headers.insert(http::header::CONTENT_LENGTH, len.into());
+        }
+    }
+    http::Response::from_parts(parts, Body::empty())
+}
+
+/// The length of `body` if all of it is ready and within `MAX_HEAD_BODY_SIZE` bytes.
+fn ready_len(body: &mut Body, waker: &LocalWaker) -> Option<usize> {
+    let mut len = 0;
+    loop {
+        match Pin::new(&mut *body).poll_next(waker) {
+            Poll::Ready(Some(Ok(chunk))) => len += chunk.len(),
+            Poll::Ready(None) => return Some(len),
+            Poll::Ready(Some(Err(_))) | Poll::Pending => return None,
+        }
+        if len > MAX_HEAD_BODY_SIZE {
+            return None;
+        }
+    }
+}
+
+/// Resolve to the response of `res`, unless `timeout` elapses first: `res` is then dropped,
+/// cancelling the endpoint, and the response is `503 Service Unavailable`.
+pub(crate) fn within(
+    mut res: FutureObj<'_, Response>,
+    timeout: Duration,
+) -> impl Future<Output = Response> + '_ {
+    let mut delay = Delay::new(timeout);
+    future::poll_fn(move |waker| {
+        if let Poll::Ready(res) = Pin::new(&mut res).poll(waker) {
+            return Poll::Ready(res);
+        }
+        match Pin::new(&mut delay).poll(waker) {
+            Poll::Ready(_) => Poll::Ready(http::StatusCode::SERVICE_UNAVAILABLE.into_response()),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+}
+
+/// Whether the `Accept` header of `req` lists HTML.
+fn accepts_html(req: &Request) -> bool {
+    req.headers()
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .any(|accept| accept.contains("text/html"))
+}
+
 /// An extractor for accessing app data.
 ///
 /// Endpoints can use `AppData<T>` to gain a handle to the data (of type `T`) originally injected into their app.
+/// Within a subrouter given its own data with `Router::data`, they receive that data instead.
+///
+/// Every `AppData<T>` argument receives its own clone of the data. To borrow heavyweight data
+/// instead, extract `SharedData<T>`.
 pub struct AppData<T>(pub T);
 
 impl<T> Deref for AppData<T> {
@@ -234,7 +1158,50 @@ impl<T> DerefMut for AppData<T> {
     }
 }
 
-impl<T: Clone + Send + 'static> Extract<T> for AppData<T> {
+impl<T: Clone + Send + Sync + 'static> Extract<T> for AppData<T> {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut T,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match store.read::<ScopedData<T>>() {
+            Some(scoped) => future::ok(AppData((*scoped.0).clone())),
+            None => future::ok(AppData(data.clone())),
+        }
+    }
+}
+
+/// Endpoints can use `SharedData<T>` to borrow the data (of type `T`) injected into their app,
+/// or into their subrouter with `Router::data`, without cloning it.
+///
+/// The data is shared between all requests behind an `Arc`, so extraction only bumps a reference
+/// count. Since it is shared, it can only be mutated through interior mutability. Extraction
+/// fails with `500 Internal Server Error` for requests not dispatched by a `Server`.
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::SharedData;
+///
+/// struct Catalog {
+///     items: Vec<String>,
+/// }
+///
+/// async fn count(catalog: SharedData<Catalog>) -> String {
+///     catalog.items.len().to_string()
+/// }
+/// ```
+pub struct SharedData<T>(pub Arc<T>);
+
+impl<T> Deref for SharedData<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Extract<T> for SharedData<T> {
     type Fut = future::Ready<Result<Self, Response>>;
     fn extract(
         data: &mut T,
@@ -242,6 +1209,68 @@ impl<T: Clone + Send + 'static> Extract<T> for AppData<T> {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        future::ok(AppData(data.clone()))
+        if let Some(scoped) = store.read::<ScopedData<T>>() {
+            return future::ok(SharedData(scoped.0.clone()));
+        }
+        match req.extensions().get::<SharedAppData<T>>() {
+            Some(shared) => future::ok(SharedData(shared.0.clone())),
+            None => future::err(http::StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        }
+    }
+}
+
+/// The app data of the server dispatching a request, set as a request extension for
+/// `SharedData`.
+struct SharedAppData<T>(Arc<T>);
+
+/// The data of a subrouter, set with `Router::data`, taking precedence over the app data.
+#[derive(Clone)]
+pub(crate) struct ScopedData<T>(pub(crate) Arc<T>);
+
+impl<T> Debug for ScopedData<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ScopedData")
+    }
+}
+
+/// An extractor for dispatching requests through the app from within an endpoint.
+///
+/// This allows forwarding a request to another route, composing the responses of several routes,
+/// or rendering error pages with existing routes, without an HTTP round trip:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api, await_macro)]
+/// use http_service::Body;
+/// use tide::{Dispatcher, Response};
+///
+/// async fn legacy(dispatcher: Dispatcher<()>) -> Response {
+///     let req = http::Request::get("/v2/items").body(Body::empty()).unwrap();
+///     await!(dispatcher.dispatch(req))
+/// }
+///
+/// let mut app = tide::App::new(());
+/// app.at("/v2/items").get(async || "items");
+/// app.at("/items").get(legacy);
+/// ```
+#[derive(Clone)]
+pub struct Dispatcher<Data>(Server<Data>);
+
+impl<Data: Clone + Send + Sync + 'static> Dispatcher<Data> {
+    /// Route `req` through the app; see `Server::dispatch`.
+    pub fn dispatch(&self, req: Request) -> FutureObj<'static, Response> {
+        self.0.dispatch(req)
+    }
+}
+
+impl<Data: Clone + Send + Sync + 'static> Extract<Data> for Dispatcher<Data> {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        // Every request reaching an endpoint went through `Server::dispatch`.
+        future::ok(req.extensions().get::<Dispatcher<Data>>().unwrap().clone())
     }
 }
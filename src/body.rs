@@ -33,7 +33,7 @@
 //! app.at("/echo/string_lossy").post(echo_string_lossy);
 //! app.at("/echo/bytes").post(echo_bytes);
 //!
-//! #    app.serve();
+//! #    app.serve().unwrap();
 //! # }
 //!
 //! ```
@@ -68,16 +68,17 @@
 //! app.at("/echo/json").post(echo_json);
 //! app.at("/echo/form").post(echo_form);
 //! #
-//! #    app.serve();
+//! #    app.serve().unwrap();
 //! # }
 //!
 //! ```
 //!
-use futures::future::FutureObj;
+use futures::{future::FutureObj, StreamExt};
 use http::status::StatusCode;
 use http_service::Body;
 use multipart::server::Multipart;
-use std::io::Cursor;
+use std::fmt;
+use std::io::{self, Cursor};
 use std::ops::{Deref, DerefMut};
 
 use crate::{configuration::Store, Extract, IntoResponse, Request, Response, RouteMatch};
@@ -87,6 +88,195 @@ fn mk_err<T>(_: T) -> Response {
     StatusCode::BAD_REQUEST.into_response()
 }
 
+/// The error reading a request body longer than the `max_body_size` of the `Configuration`, or
+/// than its `max_buffered_body_size` when reading it into memory.
+#[derive(Debug)]
+struct BodyTooLarge;
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("request body too large")
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+fn is_too_large(e: &io::Error) -> bool {
+    e.get_ref()
+        .map_or(false, |inner| inner.is::<BodyTooLarge>())
+}
+
+// The response for a request body that could not be read: `413 Payload Too Large` when it
+// exceeds the configured limit, and `400 Bad Request` otherwise.
+pub(crate) fn read_err(e: io::Error) -> Response {
+    if is_too_large(&e) {
+        StatusCode::PAYLOAD_TOO_LARGE.into_response()
+    } else {
+        StatusCode::BAD_REQUEST.into_response()
+    }
+}
+
+/// A marker in a request's `extensions` recording that its body could not be read, so that
+/// later attempts fail the same way instead of reading an empty body.
+#[derive(Clone, Copy, Debug)]
+enum BodyFailed {
+    TooLarge,
+    Unreadable,
+}
+
+impl BodyFailed {
+    fn to_err(self) -> io::Error {
+        match self {
+            BodyFailed::TooLarge => io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge),
+            BodyFailed::Unreadable => {
+                io::Error::new(io::ErrorKind::Other, "The request body could not be read")
+            }
+        }
+    }
+}
+
+// The response for a request whose body has been refused or could not be read, if any.
+pub(crate) fn failure(req: &Request) -> Option<Response> {
+    let failed = req.extensions().get::<BodyFailed>()?;
+    Some(read_err(failed.to_err()))
+}
+
+// Refuse the body of `req` if it is announced to be longer than `max` bytes, and otherwise make
+// reading it fail once more than `max` bytes arrive.
+pub(crate) fn limit(req: &mut Request, max: u64) {
+    if announced_len(req).map_or(false, |len| len > max) {
+        *req.body_mut() = Body::empty();
+        req.extensions_mut().insert(BodyFailed::TooLarge);
+        return;
+    }
+    let body = std::mem::replace(req.body_mut(), Body::empty());
+    *req.body_mut() = limited(body, max);
+}
+
+// Limit the bodies of `req` read into memory, by `buffer` and the body extractors, to `max`
+// bytes, leaving the body stream itself as it is.
+pub(crate) fn limit_buffered(req: &mut Request, max: u64) {
+    req.extensions_mut().insert(BufferLimit(max));
+}
+
+/// A marker in a request's `extensions` limiting the size of its body read into memory.
+struct BufferLimit(u64);
+
+fn announced_len(req: &Request) -> Option<u64> {
+    req.headers()
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<u64>().ok())
+}
+
+// The body, failing once more than `max` bytes are read.
+fn limited(body: Body, max: u64) -> Body {
+    let mut read = 0u64;
+    let limited = body.map(move |chunk| {
+        let chunk = chunk?;
+        read += chunk.len() as u64;
+        if read > max {
+            Err(io::Error::new(io::ErrorKind::InvalidData, BodyTooLarge))
+        } else {
+            Ok(chunk)
+        }
+    });
+    Body::from_stream(limited)
+}
+
+// Take the body out of `req` to read it into memory, within the limit set by `limit_buffered`.
+fn take_for_buffering(req: &mut Request) -> Result<Body, io::Error> {
+    let max = req.extensions().get::<BufferLimit>().map(|BufferLimit(max)| *max);
+    if max.map_or(false, |max| announced_len(req).map_or(false, |len| len > max)) {
+        *req.body_mut() = Body::empty();
+        req.extensions_mut().insert(BodyFailed::TooLarge);
+        return Err(BodyFailed::TooLarge.to_err());
+    }
+    let body = std::mem::replace(req.body_mut(), Body::empty());
+    Ok(match max {
+        Some(max) => limited(body, max),
+        None => body,
+    })
+}
+
+/// A marker in a request's `extensions` recording that the body has been taken by an extractor.
+struct BodyTaken;
+
+/// The in-memory copy of a request body read by `buffer`, kept in the request's `extensions`.
+struct BufferedBody(bytes::Bytes);
+
+/// The body of `req`, if it has been read into memory by `buffer`.
+pub(crate) fn buffered(req: &Request) -> Option<&bytes::Bytes> {
+    req.extensions().get::<BufferedBody>().map(|BufferedBody(bytes)| bytes)
+}
+
+// Take the body out of the request, failing if an earlier extractor has already consumed it.
+//
+// Without this check, an endpoint with e.g. both `Json<T>` and `Str` arguments would silently
+// hand an empty body to the second extractor. Buffered bodies can be taken any number of times.
+fn take_body(req: &mut Request) -> Result<Body, Response> {
+    if let Some(BufferedBody(bytes)) = req.extensions().get::<BufferedBody>() {
+        return Ok(Body::from(bytes.clone()));
+    }
+    if let Some(res) = failure(req) {
+        return Err(res);
+    }
+    if req.extensions().get::<BodyTaken>().is_some() {
+        return Err("The request body has already been consumed by another extractor"
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+            .into_response());
+    }
+    let body = take_for_buffering(req).map_err(read_err)?;
+    req.extensions_mut().insert(BodyTaken);
+    Ok(body)
+}
+
+/// Read the body of `req` into memory, so that it can be read any number of times.
+///
+/// Subsequent calls, as well as all body extractors (`Json`, `Str`, ...), get a copy of the
+/// buffered bytes instead of competing for the body stream. This lets e.g. signature-verification
+/// middleware inspect the raw body before the endpoint deserializes it. Fails if the body has
+/// already been consumed by an extractor, or if reading it failed before: a body that cannot
+/// be read is not handed out as an empty one.
+///
+/// Bodies are held in memory whole, so reading stops with an error beyond the
+/// `max_buffered_body_size` of the `Configuration`, 2 MiB unless configured otherwise.
+///
+/// See `middleware::BufferBody` for buffering the bodies of all requests.
+pub fn buffer<'a>(req: &'a mut Request) -> FutureObj<'a, std::io::Result<bytes::Bytes>> {
+    FutureObj::new(Box::new(
+        async move {
+            if let Some(BufferedBody(bytes)) = req.extensions().get::<BufferedBody>() {
+                return Ok(bytes.clone());
+            }
+            if let Some(failed) = req.extensions().get::<BodyFailed>() {
+                return Err(failed.to_err());
+            }
+            if req.extensions().get::<BodyTaken>().is_some() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "The request body has already been consumed",
+                ));
+            }
+
+            let body = take_for_buffering(req)?;
+            let bytes = match await!(body.into_vec()) {
+                Ok(bytes) => bytes::Bytes::from(bytes),
+                Err(e) => {
+                    let failed = if is_too_large(&e) {
+                        BodyFailed::TooLarge
+                    } else {
+                        BodyFailed::Unreadable
+                    };
+                    req.extensions_mut().insert(failed);
+                    return Err(e);
+                }
+            };
+            req.extensions_mut().insert(BufferedBody(bytes.clone()));
+            Ok(bytes)
+        },
+    ))
+}
+
 /// A wrapper for multipart form
 ///
 /// This type is useable as an extractor (argument to an endpoint) for getting
@@ -112,11 +302,11 @@ impl<S: 'static> Extract<S> for MultipartForm {
             Some(ct[idx + BOUNDARY.len()..].to_string())
         });
 
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 let boundary = boundary.ok_or(()).map_err(mk_err)?;
                 let mp = Multipart::with_body(Cursor::new(body), boundary);
                 Ok(MultipartForm(mp))
@@ -154,10 +344,10 @@ impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 let json: T = serde_json::from_slice(&body).map_err(mk_err)?;
                 Ok(Json(json))
             },
@@ -206,10 +396,10 @@ impl<T: Send + serde::de::DeserializeOwned + 'static, S: 'static> Extract<S> for
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 let data: T = serde_qs::from_bytes(&body).map_err(mk_err)?;
                 Ok(Form(data))
             },
@@ -254,11 +444,11 @@ impl<S: 'static> Extract<S> for Str {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 let string = String::from_utf8(body).map_err(mk_err)?;
                 Ok(Str(string))
             },
@@ -290,11 +480,11 @@ impl<S: 'static> Extract<S> for StrLossy {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 let string = String::from_utf8_lossy(&body).to_string();
                 Ok(StrLossy(string))
             },
@@ -326,11 +516,11 @@ impl<S: 'static> Extract<S> for Bytes {
         params: &Option<RouteMatch<'_>>,
         store: &Store,
     ) -> Self::Fut {
-        let body = std::mem::replace(req.body_mut(), Body::empty());
+        let body = take_body(req);
 
         FutureObj::new(Box::new(
             async move {
-                let body = await!(body.into_vec()).map_err(mk_err)?;
+                let body = await!(body?.into_vec()).map_err(read_err)?;
                 Ok(Bytes(body))
             },
         ))
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// What environment are we running in?
 #[derive(Debug, Clone)]
 pub enum Environment {
@@ -6,6 +8,36 @@ pub enum Environment {
     Production,
 }
 
+/// The HTTP versions the server speaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpVersions {
+    /// HTTP/1 and HTTP/2. This is the default.
+    ///
+    /// Over TLS, the version is negotiated with ALPN. In cleartext, clients speak HTTP/2 with
+    /// prior knowledge, by starting the connection with its preface; upgrading from HTTP/1 with
+    /// `Upgrade: h2c` is not supported.
+    Any,
+    /// HTTP/1.0 and HTTP/1.1 only.
+    Http1Only,
+    /// HTTP/2 only, e.g. for gRPC or behind a proxy speaking HTTP/2 to its backends.
+    Http2Only,
+}
+
+impl Default for HttpVersions {
+    fn default() -> Self {
+        HttpVersions::Any
+    }
+}
+
+/// How long connections may wait for their next request by default.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The longest request body read into memory by default, 2 MiB.
+const DEFAULT_MAX_BUFFERED_BODY_SIZE: u64 = 2 * 1024 * 1024;
+
+/// The most headers the server parses in a request, and the default limit.
+const MAX_HEADERS: usize = 100;
+
 /// Holds the default configuration for the App.
 ///
 /// Only the one that is applied to the top-level router will be regarded. Overriding this item in
@@ -15,12 +47,72 @@ pub struct Configuration {
     pub env: Environment,
     pub address: String,
     pub port: u16,
+    /// Whether connections start with a PROXY protocol (v1 or v2) header, as sent by load
+    /// balancers such as HAProxy or AWS NLB.
+    pub proxy_protocol: bool,
+    /// How long a graceful shutdown waits for in-flight requests to complete.
+    pub shutdown_timeout: Duration,
+    /// The HTTP versions the server speaks.
+    pub http_versions: HttpVersions,
+    /// Whether HTTP/1 connections are kept open for further requests.
+    pub keep_alive: bool,
+    /// How long the server waits for a client to send more of a request before closing the
+    /// connection, if at all.
+    pub read_timeout: Option<Duration>,
+    /// How long the server waits for a client to accept more data before closing the
+    /// connection, if at all.
+    pub write_timeout: Option<Duration>,
+    /// How many connections the server keeps open at once, if limited.
+    pub max_connections: Option<usize>,
+    /// How many requests the server handles at once, if limited.
+    pub max_requests: Option<usize>,
+    /// How many worker threads `App::serve` runs, by default one per CPU core.
+    pub workers: Option<usize>,
+    /// The prefix of the names of the worker threads of `App::serve`.
+    pub thread_name: String,
+    /// Whether the listening socket is bound with `SO_REUSEPORT`.
+    pub reuse_port: bool,
+    /// The longest request line and headers the server reads, in bytes, if not hyper's default.
+    pub max_head_size: Option<usize>,
+    /// How many headers a request may have, at most 100.
+    pub max_headers: usize,
+    /// The longest request target the server accepts, in bytes, if limited.
+    pub max_uri_length: Option<usize>,
+    /// The longest request body the server accepts, in bytes, if limited.
+    pub max_body_size: Option<u64>,
+    /// The longest request body read into memory, in bytes, if limited; 2 MiB by default.
+    pub max_buffered_body_size: Option<u64>,
+    /// How many TLS handshakes the server performs at once.
+    pub max_handshakes: usize,
+    /// How long a client gets to complete the TLS handshake.
+    pub handshake_timeout: Duration,
+    /// How long the server keeps a connection open without requests, if limited.
+    pub idle_timeout: Option<Duration>,
 }
 
 pub struct ConfigurationBuilder {
     pub env: Environment,
     pub address: String,
     pub port: u16,
+    pub proxy_protocol: bool,
+    pub shutdown_timeout: Duration,
+    pub http_versions: HttpVersions,
+    pub keep_alive: bool,
+    pub read_timeout: Option<Duration>,
+    pub write_timeout: Option<Duration>,
+    pub max_connections: Option<usize>,
+    pub max_requests: Option<usize>,
+    pub workers: Option<usize>,
+    pub thread_name: String,
+    pub reuse_port: bool,
+    pub max_head_size: Option<usize>,
+    pub max_headers: usize,
+    pub max_uri_length: Option<usize>,
+    pub max_body_size: Option<u64>,
+    pub max_buffered_body_size: Option<u64>,
+    pub max_handshakes: usize,
+    pub handshake_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
 }
 
 impl Default for Configuration {
@@ -29,6 +121,25 @@ impl Default for Configuration {
             env: Environment::Development,
             address: "127.0.0.1".to_owned(),
             port: 8181,
+            proxy_protocol: false,
+            shutdown_timeout: Duration::from_secs(30),
+            http_versions: HttpVersions::default(),
+            keep_alive: true,
+            read_timeout: None,
+            write_timeout: None,
+            max_connections: None,
+            max_requests: None,
+            workers: None,
+            thread_name: "tide-worker-".to_owned(),
+            reuse_port: false,
+            max_head_size: None,
+            max_headers: MAX_HEADERS,
+            max_uri_length: None,
+            max_body_size: None,
+            max_buffered_body_size: Some(DEFAULT_MAX_BUFFERED_BODY_SIZE),
+            max_handshakes: 1024,
+            handshake_timeout: Duration::from_secs(10),
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
         }
     }
 }
@@ -47,6 +158,25 @@ impl Default for ConfigurationBuilder {
             env: config.env,
             address: config.address,
             port: config.port,
+            proxy_protocol: config.proxy_protocol,
+            shutdown_timeout: config.shutdown_timeout,
+            http_versions: config.http_versions,
+            keep_alive: config.keep_alive,
+            read_timeout: config.read_timeout,
+            write_timeout: config.write_timeout,
+            max_connections: config.max_connections,
+            max_requests: config.max_requests,
+            workers: config.workers,
+            thread_name: config.thread_name,
+            reuse_port: config.reuse_port,
+            max_head_size: config.max_head_size,
+            max_headers: config.max_headers,
+            max_uri_length: config.max_uri_length,
+            max_body_size: config.max_body_size,
+            max_buffered_body_size: config.max_buffered_body_size,
+            max_handshakes: config.max_handshakes,
+            handshake_timeout: config.handshake_timeout,
+            idle_timeout: config.idle_timeout,
         }
     }
 }
@@ -67,12 +197,223 @@ impl ConfigurationBuilder {
         self
     }
 
+    /// Expect a PROXY protocol header at the start of every connection.
+    ///
+    /// The client and server addresses from the header are then reported by the
+    /// `ConnectionInfo` extractor in place of the load balancer's. Connections without a valid
+    /// header are closed, so only enable this when all traffic passes through such a proxy.
+    pub fn proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+        self
+    }
+
+    /// Wait at most `timeout` for in-flight requests to complete on a graceful shutdown, before
+    /// closing their connections.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Restrict the HTTP versions the server speaks.
+    pub fn http_versions(mut self, versions: HttpVersions) -> Self {
+        self.http_versions = versions;
+        self
+    }
+
+    /// Keep HTTP/1 connections open for further requests, or close them after every response.
+    pub fn keep_alive(mut self, enabled: bool) -> Self {
+        self.keep_alive = enabled;
+        self
+    }
+
+    /// Close connections on which the client sends nothing for `timeout` while sending a request.
+    ///
+    /// This covers clients sending the request line and headers slowly, and those sending the
+    /// body slowly while the endpoint reads it. Connections waiting for their next request are
+    /// subject to the `idle_timeout` instead, and those waiting for a response, however long it
+    /// takes to produce or stream, to neither.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Close connections on which the client accepts nothing for `timeout`, while the server
+    /// has data to send.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep at most `max` connections open at once.
+    ///
+    /// Further clients wait to be accepted until a connection closes, queued by the system,
+    /// which refuses connections once its listen backlog is full. Connections count from the
+    /// moment they are accepted, including during the TLS handshake, and until they are closed,
+    /// which the `idle_timeout` does for those left unused.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Handle at most `max` requests at once, answering further ones with
+    /// `503 Service Unavailable` and a `Retry-After` header.
+    ///
+    /// A request counts until its response body has been sent.
+    pub fn max_requests(mut self, max: usize) -> Self {
+        self.max_requests = Some(max);
+        self
+    }
+
+    /// Run `App::serve` on `count` worker threads.
+    ///
+    /// To run the server on an existing runtime instead, use `Server::run`.
+    pub fn workers(mut self, count: usize) -> Self {
+        self.workers = Some(count);
+        self
+    }
+
+    /// Name the worker threads of `App::serve` with the given prefix, followed by a number.
+    pub fn thread_name<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.thread_name = prefix.into();
+        self
+    }
+
+    /// Bind the listening socket with `SO_REUSEPORT`, so that several servers, in one process or
+    /// several, can listen at the same address and port.
+    ///
+    /// On Linux, connections are then distributed among the servers. All of them need to set
+    /// this option, and run as the same user. Binding fails on platforms without the option.
+    ///
+    /// Together with `workers`, the server binds one such socket per worker thread, each with an
+    /// accept loop of its own. `max_connections` and `max_requests` still apply to the server as
+    /// a whole.
+    pub fn reuse_port(mut self, enabled: bool) -> Self {
+        self.reuse_port = enabled;
+        self
+    }
+
+    /// Read at most `bytes` of request line and headers, answering larger requests with
+    /// `431 Request Header Fields Too Large`.
+    ///
+    /// The limit is at least 8 KiB, and about 400 KiB by default. It also bounds how much of a
+    /// request body is buffered at once.
+    pub fn max_head_size(mut self, bytes: usize) -> Self {
+        self.max_head_size = Some(bytes);
+        self
+    }
+
+    /// Accept at most `count` headers per request, answering requests with more with
+    /// `431 Request Header Fields Too Large`.
+    ///
+    /// The server never parses more than 100 headers, the default.
+    ///
+    /// Panics if `count` is more than 100.
+    pub fn max_headers(mut self, count: usize) -> Self {
+        if count > MAX_HEADERS {
+            panic!(
+                "Cannot accept {} headers per request, the server parses at most {}",
+                count, MAX_HEADERS
+            );
+        }
+        self.max_headers = count;
+        self
+    }
+
+    /// Accept request targets (path and query) of at most `bytes`, answering requests with
+    /// longer ones with `414 URI Too Long`.
+    pub fn max_uri_length(mut self, bytes: usize) -> Self {
+        self.max_uri_length = Some(bytes);
+        self
+    }
+
+    /// Accept request bodies of at most `bytes`, answering requests with longer ones with
+    /// `413 Payload Too Large`.
+    ///
+    /// Requests announcing a longer `Content-Length` are refused before reaching the app. For
+    /// others, reading the body fails once the limit is exceeded, which the body extractors and
+    /// `middleware::BufferBody` turn into the same response.
+    ///
+    /// This applies to every request, including those of endpoints streaming their body and of
+    /// mounted services, so the server sets no such limit by default. Bodies read into memory
+    /// are limited separately, see `max_buffered_body_size`.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Read request bodies of at most `bytes` into memory, with `body::buffer`, the body
+    /// extractors and `middleware::BufferBody`, answering longer ones with
+    /// `413 Payload Too Large`.
+    ///
+    /// Endpoints streaming their body are not affected. Buffered bodies are limited to 2 MiB by
+    /// default. The limit is read after routing, from the configuration the endpoint sees.
+    pub fn max_buffered_body_size(mut self, bytes: u64) -> Self {
+        self.max_buffered_body_size = Some(bytes);
+        self
+    }
+
+    /// Accept request bodies of any size, and read them into memory whole.
+    ///
+    /// Then `body::buffer`, the body extractors and `middleware::BufferBody` keep reading as
+    /// long as the client keeps sending, so only use this if the app limits bodies itself.
+    pub fn unlimited_body_size(mut self) -> Self {
+        self.max_body_size = None;
+        self.max_buffered_body_size = None;
+        self
+    }
+
+    /// Perform at most `count` TLS handshakes at once, 1024 by default.
+    ///
+    /// Every handshake runs as a task of its own, so clients that are slow to complete theirs do
+    /// not hold up the others. While `count` of them are running, no further connections are
+    /// accepted.
+    pub fn max_handshakes(mut self, count: usize) -> Self {
+        self.max_handshakes = count;
+        self
+    }
+
+    /// Close connections whose TLS handshake has not completed after `timeout`, 10 seconds by
+    /// default.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Close connections on which the client sends no request for `timeout`, such as idle
+    /// keep-alive connections. The default is 60 seconds.
+    ///
+    /// Open connections count towards `max_connections`, so without this timeout, clients
+    /// keeping connections open without using them could lock out all others.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
     pub fn finalize(self) -> Configuration {
         let mut config = Configuration::default();
 
         config.port = self.port;
         config.address = self.address;
         config.env = self.env;
+        config.proxy_protocol = self.proxy_protocol;
+        config.shutdown_timeout = self.shutdown_timeout;
+        config.http_versions = self.http_versions;
+        config.keep_alive = self.keep_alive;
+        config.read_timeout = self.read_timeout;
+        config.write_timeout = self.write_timeout;
+        config.max_connections = self.max_connections;
+        config.max_requests = self.max_requests;
+        config.workers = self.workers;
+        config.thread_name = self.thread_name;
+        config.reuse_port = self.reuse_port;
+        config.max_head_size = self.max_head_size;
+        config.max_headers = self.max_headers;
+        config.max_uri_length = self.max_uri_length;
+        config.max_body_size = self.max_body_size;
+        config.max_buffered_body_size = self.max_buffered_body_size;
+        config.max_handshakes = self.max_handshakes;
+        config.handshake_timeout = self.handshake_timeout;
+        config.idle_timeout = self.idle_timeout;
 
         config
     }
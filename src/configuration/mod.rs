@@ -10,7 +10,7 @@ use crate::{Extract, Request, Response, RouteMatch};
 
 mod default_config;
 
-pub use self::default_config::{Configuration, ConfigurationBuilder, Environment};
+pub use self::default_config::{Configuration, ConfigurationBuilder, Environment, HttpVersions};
 
 trait StoreItem: Any + Send + Sync {
     fn clone_any(&self) -> Box<dyn StoreItem>;
@@ -89,6 +89,68 @@ impl Store {
     }
 }
 
+/// The time budget for handling a request, configurable per app, subrouter or endpoint.
+///
+/// Endpoints can learn about their remaining budget through the `head::Deadline` extractor.
+/// Endpoints still running once it is exhausted are cancelled, by dropping their future, and
+/// answered with `503 Service Unavailable`, which passes back through the middleware as usual.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestTimeout(pub std::time::Duration);
+
+/// The status to respond with when a path segment fails to parse in `head::Path` or
+/// `head::Named`.
+///
+/// Defaults to `400 Bad Request`. APIs considering a malformed resource ID as a non-existent
+/// resource can configure `404 Not Found` instead, for the whole app or per subrouter or endpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathErrorStatus(pub http::StatusCode);
+
+impl Default for PathErrorStatus {
+    fn default() -> Self {
+        PathErrorStatus(http::StatusCode::BAD_REQUEST)
+    }
+}
+
+/// How path segments are decoded before `head::Path`, `head::Named` and `head::Params` see them,
+/// configurable per app, subrouter or endpoint.
+///
+/// Segments that are not valid UTF-8 once decoded fail extraction like segments that fail to
+/// parse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathDecoding {
+    /// Decode `%xx` sequences. This is the default.
+    Percent,
+    /// Decode `%xx` sequences, and `+` as a space.
+    PercentAndPlus,
+    /// Hand over segments as they appear in the request.
+    Raw,
+}
+
+impl Default for PathDecoding {
+    fn default() -> Self {
+        PathDecoding::Percent
+    }
+}
+
+/// How to treat request paths with a trailing slash, configurable per app, subrouter or endpoint.
+///
+/// Routing ignores empty path segments, so `/foo` and `/foo/` always reach the same resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrailingSlash {
+    /// Serve both forms. This is the default.
+    Ignore,
+    /// Redirect to the form without trailing slash, with `301 Moved Permanently` for `GET` and
+    /// `HEAD` requests and `308 Permanent Redirect` (which preserves the method and body) for
+    /// others.
+    Redirect,
+}
+
+impl Default for TrailingSlash {
+    fn default() -> Self {
+        TrailingSlash::Ignore
+    }
+}
+
 /// An extractor for reading configuration from endpoints.
 ///
 /// It will try to retrieve the given configuration item. If it is not set, the extracted value
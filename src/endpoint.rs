@@ -1,7 +1,14 @@
-use futures::future::{Future, FutureObj};
+use futures::{
+    future::{Future, FutureObj},
+    prelude::*,
+};
+use http_service::HttpService;
+use std::sync::Arc;
+use std::time::Instant;
 
 use crate::{
-    configuration::Store, extract::Extract, head::Head, IntoResponse, Request, Response, RouteMatch,
+    configuration::Store, extract::Extract, head::Head, middleware::timing, IntoResponse, Request,
+    Response, RouteMatch,
 };
 
 /// The raw representation of an endpoint.
@@ -27,7 +34,7 @@ use crate::{
 /// fn main() {
 ///     let mut app = tide::App::new(());
 ///     app.at("/hello").get(hello);
-///     app.serve()
+///     app.serve().unwrap()
 /// }
 /// ```
 ///
@@ -56,7 +63,7 @@ use crate::{
 /// fn main() {
 ///     let mut app = tide::App::new(Database::default());
 ///     app.at("/messages/insert").post(insert);
-///     app.serve()
+///     app.serve().unwrap()
 /// }
 /// ```
 ///
@@ -106,6 +113,53 @@ impl<Data> BoxedEndpoint<Data> {
     }
 }
 
+/// An endpoint that forwards requests to an external `HttpService`.
+///
+/// A fresh connection is established with the service for every request. Failures to connect
+/// or to respond are turned into `500 Internal Server Error`.
+#[doc(hidden)]
+pub struct ServiceEndpoint<S> {
+    service: Arc<S>,
+}
+
+impl<S> ServiceEndpoint<S> {
+    pub(crate) fn new(service: S) -> ServiceEndpoint<S> {
+        ServiceEndpoint {
+            service: Arc::new(service),
+        }
+    }
+}
+
+/// A marker type used for the (phantom) `Kind` parameter of `ServiceEndpoint`.
+#[doc(hidden)]
+pub struct ServiceKind;
+
+impl<Data, S: HttpService> Endpoint<Data, ServiceKind> for ServiceEndpoint<S> {
+    type Fut = FutureObj<'static, Response>;
+
+    fn call(
+        &self,
+        data: Data,
+        req: Request,
+        params: Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let service = self.service.clone();
+        FutureObj::new(Box::new(
+            async move {
+                let mut conn = match await!(service.connect().into_future()) {
+                    Ok(conn) => conn,
+                    Err(_) => return http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                };
+                match await!(service.respond(&mut conn, req).into_future()) {
+                    Ok(res) => res,
+                    Err(_) => http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                }
+            },
+        ))
+    }
+}
+
 /// A marker type used for the (phantom) `Kind` parameter in endpoints.
 #[doc(hidden)]
 pub struct Ty<T>(T);
@@ -136,17 +190,25 @@ macro_rules! end_point_impl_raw {
             #[allow(unused_mut, non_snake_case)]
             fn call(&self, mut data: Data, mut req: Request, params: Option<RouteMatch<'_>>, store: &Store) -> Self::Fut {
                 let f = self.clone();
+                let timed = timing::enabled(&req);
                 $(let $X = $X::extract(&mut data, &mut req, &params, store);)*
                 FutureObj::new(Box::new(async move {
+                    let start = Instant::now();
                     let (parts, _) = req.into_parts();
                     let head = Head::from(parts);
                     $(let $X = match await!($X) {
                         Ok(x) => x,
                         Err(resp) => return resp,
                     };)*
+                    let extracted = Instant::now();
                     let res = await!(call_f!($($head;)* (f, head); $($X),*));
 
-                    res.into_response()
+                    let mut res = res.into_response();
+                    if timed {
+                        timing::record(&mut res, "extract", extracted - start);
+                        timing::record(&mut res, "endpoint", extracted.elapsed());
+                    }
+                    res
                 }))
             }
         }
@@ -0,0 +1,41 @@
+//! Errors preventing an app from being served.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+/// An error preventing the server from starting, or stopping it while running.
+///
+/// Returned by `App::serve`.
+#[derive(Debug)]
+pub enum Error {
+    /// The configured address and port do not resolve to a socket address.
+    Address(String),
+    /// The server could not listen at the address.
+    Bind(Box<dyn StdError + Send + Sync>),
+    /// The runtime driving the server could not be started.
+    Runtime(io::Error),
+    /// The server failed while running.
+    Server(Box<dyn StdError + Send + Sync>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Address(addr) => write!(f, "invalid address `{}`", addr),
+            Error::Bind(e) => write!(f, "error binding to address: {}", e),
+            Error::Runtime(e) => write!(f, "error starting runtime: {}", e),
+            Error::Server(e) => write!(f, "server error: {}", e),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Address(_) => None,
+            Error::Bind(e) | Error::Server(e) => Some(&**e),
+            Error::Runtime(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Applying the `Forwarded` and `X-Forwarded-*` headers set by trusted reverse proxies.
+
+use http::header::{HeaderMap, HeaderValue};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{ConnectionInfo, Request};
+
+/// A range of IP addresses in CIDR notation, such as `10.0.0.0/8` or `fd00::/8`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Parse a CIDR range, or a single address.
+    pub(crate) fn parse(range: &str) -> Option<IpRange> {
+        let mut parts = range.splitn(2, '/');
+        let addr: IpAddr = parts.next()?.trim().parse().ok()?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match parts.next() {
+            Some(len) => len.trim().parse().ok().filter(|len| *len <= max_len)?,
+            None => max_len,
+        };
+        Some(IpRange { addr, prefix_len })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = (!0u32).checked_shl(32 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = (!0u128).checked_shl(128 - u32::from(self.prefix_len));
+                let mask = mask.unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// One proxy hop, as reported by the proxy that received it.
+#[derive(Debug, Default)]
+struct Hop {
+    addr: Option<SocketAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// Parse a node address, defaulting to port 0 if the proxy did not report one.
+fn parse_node(node: &str) -> Option<SocketAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(addr) = node.parse() {
+        return Some(addr);
+    }
+    let ip = node.trim_start_matches('[').trim_end_matches(']');
+    ip.parse().ok().map(|ip| SocketAddr::new(ip, 0))
+}
+
+fn header_values<'a>(headers: &'a HeaderMap, name: &str) -> impl Iterator<Item = &'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+}
+
+/// The hops recorded in the request headers, from the client to the nearest proxy.
+fn hops(headers: &HeaderMap) -> Vec<Hop> {
+    let forwarded: Vec<Hop> = header_values(headers, "forwarded")
+        .map(|element| {
+            let mut hop = Hop::default();
+            for pair in element.split(';') {
+                let mut pair = pair.splitn(2, '=');
+                let key = pair.next().unwrap_or("").trim().to_ascii_lowercase();
+                let value = pair.next().unwrap_or("").trim().trim_matches('"');
+                match &*key {
+                    "for" => hop.addr = parse_node(value),
+                    "proto" => hop.proto = Some(value.to_owned()),
+                    "host" => hop.host = Some(value.to_owned()),
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    // The `X-Forwarded-*` headers cannot attribute scheme and host to a specific hop, so take the
+    // values reported by the nearest proxy.
+    let proto = header_values(headers, "x-forwarded-proto").last();
+    let host = header_values(headers, "x-forwarded-host").last();
+    header_values(headers, "x-forwarded-for")
+        .map(|node| Hop {
+            addr: parse_node(node),
+            proto: proto.map(str::to_owned),
+            host: host.map(str::to_owned),
+        })
+        .collect()
+}
+
+/// Rewrite the client address, scheme and host of a request coming from a trusted proxy.
+pub(crate) fn apply(req: &mut Request, trusted: &[IpRange]) {
+    let info = match req.extensions().get::<ConnectionInfo>() {
+        Some(info) => info.clone(),
+        None => return,
+    };
+    let is_trusted = |addr: SocketAddr| trusted.iter().any(|range| range.contains(addr.ip()));
+    if !info.peer_addr().map_or(false, is_trusted) {
+        return;
+    }
+
+    // Walk from the nearest proxy towards the client; the first hop not coming from a trusted
+    // proxy is the client.
+    let hops = hops(req.headers());
+    let hop = hops
+        .iter()
+        .rev()
+        .find(|hop| !hop.addr.map_or(false, is_trusted))
+        .or_else(|| hops.first());
+    let hop = match hop {
+        Some(hop) => hop,
+        None => return,
+    };
+
+    if let Some(addr) = hop.addr {
+        let info = ConnectionInfo::new(Some(addr), info.local_addr());
+        req.extensions_mut().insert(info);
+    }
+
+    let proto = hop
+        .proto
+        .as_ref()
+        .map(|proto| proto.to_ascii_lowercase())
+        .filter(|proto| proto == "http" || proto == "https");
+    let host = hop
+        .host
+        .as_ref()
+        .and_then(|host| HeaderValue::from_str(host).ok());
+    if proto.is_none() && host.is_none() {
+        return;
+    }
+
+    let scheme = proto
+        .as_ref()
+        .map(String::as_str)
+        .or_else(|| req.uri().scheme_str())
+        .unwrap_or("http");
+    let authority = match &host {
+        Some(host) => host.to_str().ok(),
+        None => req
+            .uri()
+            .authority_part()
+            .map(|authority| authority.as_str())
+            .or_else(|| req.headers().get(http::header::HOST)?.to_str().ok()),
+    };
+    let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+    let uri = authority.and_then(|authority| {
+        format!("{}://{}{}", scheme, authority, path)
+            .parse::<http::Uri>()
+            .ok()
+    });
+
+    if let Some(uri) = uri {
+        *req.uri_mut() = uri;
+        if let Some(host) = host {
+            req.headers_mut().insert(http::header::HOST, host);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_service::Body;
+
+    fn request(peer: &str, headers: &[(&'static str, &str)]) -> Request {
+        let mut req = http::Request::get("/path?query")
+            .header("Host", "internal:8080")
+            .body(Body::empty())
+            .unwrap();
+        for (name, value) in headers {
+            req.headers_mut()
+                .append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        let info = ConnectionInfo::new(Some(peer.parse().unwrap()), None);
+        req.extensions_mut().insert(info);
+        req
+    }
+
+    fn peer(req: &Request) -> SocketAddr {
+        req.extensions()
+            .get::<ConnectionInfo>()
+            .unwrap()
+            .peer_addr()
+            .unwrap()
+    }
+
+    fn trusted() -> Vec<IpRange> {
+        vec![
+            IpRange::parse("10.0.0.0/8").unwrap(),
+            IpRange::parse("::1").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn ip_ranges() {
+        let range = IpRange::parse("10.0.0.0/8").unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(!range.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(IpRange::parse("0.0.0.0/0")
+            .unwrap()
+            .contains("1.2.3.4".parse().unwrap()));
+        assert!(IpRange::parse("fd00::/8")
+            .unwrap()
+            .contains("fd12::1".parse().unwrap()));
+        assert_eq!(IpRange::parse("10.0.0.0/33"), None);
+        assert_eq!(IpRange::parse("not-an-ip"), None);
+    }
+
+    #[test]
+    fn forwarded_header() {
+        let mut req = request(
+            "10.0.0.1:5000",
+            &[(
+                "Forwarded",
+                r#"for="[2001:db8::17]:4711";proto=https;host=example.com, for=10.0.0.2"#,
+            )],
+        );
+        apply(&mut req, &trusted());
+        assert_eq!(peer(&req), "[2001:db8::17]:4711".parse().unwrap());
+        assert_eq!(req.uri(), "https://example.com/path?query");
+        assert_eq!(req.headers()["Host"], "example.com");
+    }
+
+    #[test]
+    fn x_forwarded_headers() {
+        let mut req = request(
+            "[::1]:5000",
+            &[
+                ("X-Forwarded-For", "203.0.113.7, 198.51.100.1"),
+                ("X-Forwarded-For", "10.0.0.2"),
+                ("X-Forwarded-Proto", "https"),
+            ],
+        );
+        apply(&mut req, &trusted());
+        assert_eq!(peer(&req), "198.51.100.1:0".parse().unwrap());
+        assert_eq!(req.uri(), "https://internal:8080/path?query");
+        assert_eq!(req.headers()["Host"], "internal:8080");
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        let mut req = request(
+            "203.0.113.7:5000",
+            &[("X-Forwarded-For", "192.0.2.1"), ("X-Forwarded-Proto", "https")],
+        );
+        apply(&mut req, &trusted());
+        assert_eq!(peer(&req), "203.0.113.7:5000".parse().unwrap());
+        assert_eq!(req.uri(), "/path?query");
+    }
+}
@@ -3,11 +3,18 @@
 //! This module includes extractors like `Path` that endpoints can use to
 //! automatically parse out information from a request.
 
-use futures::future;
+use futures::future::{self, FutureObj};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::{configuration::Store, Extract, IntoResponse, Request, Response, RouteMatch};
+use crate::{
+    configuration::{PathDecoding, PathErrorStatus, RequestTimeout, Store},
+    request::ReceivedAt,
+    Extract, IntoResponse, Request, Response, RouteMatch,
+};
 
 /// Header and metadata for a request.
 ///
@@ -18,10 +25,15 @@ pub struct Head {
 }
 
 impl From<http::request::Parts> for Head {
-    fn from(parts: http::request::Parts) -> Self {
-        Self {
+    fn from(mut parts: http::request::Parts) -> Self {
+        let slot = parts.extensions.remove::<PartsSlot>();
+        let head = Self {
             inner: Arc::new(parts),
+        };
+        if let Some(slot) = slot {
+            *slot.0.lock().unwrap() = Some(head.clone());
         }
+        head
     }
 }
 
@@ -50,13 +62,90 @@ impl Head {
     pub fn headers(&self) -> &http::header::HeaderMap<http::header::HeaderValue> {
         &self.inner.headers
     }
+
+    /// The HTTP version of this request
+    pub fn version(&self) -> http::Version {
+        self.inner.version
+    }
+
+    /// The extensions of this request, e.g. values inserted by middleware
+    pub fn extensions(&self) -> &http::Extensions {
+        &self.inner.extensions
+    }
+}
+
+/// An extractor for the method, URI, version, headers and extensions of a request.
+///
+/// This is a stable, immutable view of the request metadata in one argument, which custom
+/// extractors and endpoints can build on instead of reaching into `Request` directly:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::head::Parts;
+///
+/// async fn describe(parts: Parts) -> String {
+///     format!("{} {} {:?}", parts.method(), parts.uri(), parts.version())
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/describe").get(describe);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Unlike `Head`, it can be taken in any argument position. It shares the `Head` the endpoint
+/// makes of the request once all arguments are extracted, so nothing is copied, and its future
+/// only resolves once the endpoint runs.
+#[derive(Clone)]
+pub struct Parts(Head);
+
+impl Deref for Parts {
+    type Target = Head;
+    fn deref(&self) -> &Head {
+        &self.0
+    }
+}
+
+/// Where the `Head` of a request is handed to the `Parts` extracted from it, kept in the
+/// request's `extensions` until the head is made.
+#[derive(Clone, Default)]
+struct PartsSlot(Arc<Mutex<Option<Head>>>);
+
+impl<S: 'static> Extract<S> for Parts {
+    type Fut = FutureObj<'static, Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let slot = match req.extensions().get::<PartsSlot>() {
+            Some(slot) => slot.clone(),
+            None => {
+                let slot = PartsSlot::default();
+                req.extensions_mut().insert(slot.clone());
+                slot
+            }
+        };
+        FutureObj::new(Box::new(
+            async move {
+                let head = slot.0.lock().unwrap().clone();
+                head.map(Parts)
+                    .ok_or_else(|| http::StatusCode::INTERNAL_SERVER_ERROR.into_response())
+            },
+        ))
+    }
 }
 
 /// An extractor for path segments.
 ///
 /// Routes can use wildcard path segments (`{}`), which are then extracted by the endpoint using
 /// this `Path` extractor. Each `Path<T>` argument to an extractor parses the next wildcard segment
-/// as type `T`, failing with a `NOT_FOUND` response if the segment fails to parse.
+/// as type `T`, failing with a `BAD_REQUEST` response (or the configured `PathErrorStatus`) if the
+/// segment fails to parse. Segments are percent-decoded first, as configured by
+/// `configuration::PathDecoding`.
 ///
 /// # Examples
 ///
@@ -74,7 +163,7 @@ impl Head {
 /// fn main() {
 ///     let mut app = tide::App::new(());
 ///     app.at("/path/{}").get(path_segment);
-///     app.serve()
+///     app.serve().unwrap()
 /// }
 /// ```
 ///
@@ -93,6 +182,55 @@ impl<T> DerefMut for Path<T> {
     }
 }
 
+// The response for a path segment that fails to parse, as configured by `PathErrorStatus`
+fn parse_error(store: &Store) -> Response {
+    store
+        .read::<PathErrorStatus>()
+        .cloned()
+        .unwrap_or_default()
+        .0
+        .into_response()
+}
+
+/// Decode a path segment as configured by `PathDecoding`, returning `None` if the result is not
+/// valid UTF-8.
+fn decode_segment<'a>(segment: &'a str, store: &Store) -> Option<Cow<'a, str>> {
+    let plus = match store.read::<PathDecoding>().cloned().unwrap_or_default() {
+        PathDecoding::Raw => return Some(Cow::Borrowed(segment)),
+        PathDecoding::Percent => false,
+        PathDecoding::PercentAndPlus => true,
+    };
+    percent_decode(segment, plus)
+}
+
+/// Decode the `%xx` sequences of `segment`, and `+` as a space if `plus` is set, returning `None`
+/// if the result is not valid UTF-8.
+pub(crate) fn percent_decode(segment: &str, plus: bool) -> Option<Cow<'_, str>> {
+    if !segment.contains('%') && !(plus && segment.contains('+')) {
+        return Some(Cow::Borrowed(segment));
+    }
+    let hex = |byte: u8| char::from(byte).to_digit(16);
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3).and_then(|digits| {
+            Some((hex(digits[0])? * 16 + hex(digits[1])?) as u8)
+        });
+        match (bytes[i], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) if plus => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok().map(Cow::Owned)
+}
+
 /// A key for storing the current segment match in a request's `extensions`
 struct PathIdx(usize);
 
@@ -107,9 +245,9 @@ impl<T: Send + 'static + std::str::FromStr, S: 'static> Extract<S> for Path<T> {
         let &PathIdx(i) = req.extensions().get::<PathIdx>().unwrap_or(&PathIdx(0));
         req.extensions_mut().insert(PathIdx(i + 1));
         match params {
-            Some(params) => match params.vec[i].parse() {
-                Ok(t) => future::ok(Path(t)),
-                Err(_) => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
+            Some(params) => match decode_segment(params.vec[i], store).map(|s| s.parse()) {
+                Some(Ok(t)) => future::ok(Path(t)),
+                _ => future::err(parse_error(store)),
             },
             None => future::err(http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response()),
         }
@@ -125,8 +263,8 @@ pub trait NamedSegment: Send + 'static + std::str::FromStr {
 ///
 /// Allows routes to access named path segments (`{foo}`). Each `Named<T>` extracts a single
 /// segment. `T` must implement the `NamedSegment` trait - to provide the segment name - and the
-/// FromStr trait. Fails with a `BAD_REQUEST` response if the segment is not found, fails to
-/// parse or if multiple identically named segments exist.
+/// FromStr trait. Fails with a `BAD_REQUEST` response (or the configured `PathErrorStatus`) if
+/// the segment is not found, fails to parse or if multiple identically named segments exist.
 ///
 /// # Examples
 ///
@@ -159,7 +297,7 @@ pub trait NamedSegment: Send + 'static + std::str::FromStr {
 /// fn main() {
 ///     let mut app = tide::App::new(());
 ///     app.at("/path_named/{num}").get(named_segments);
-///     app.serve()
+///     app.serve().unwrap()
 /// }
 /// ```
 ///
@@ -191,16 +329,296 @@ impl<T: NamedSegment, S: 'static> Extract<S> for Named<T> {
             Some(params) => params
                 .map
                 .get(T::NAME)
-                .and_then(|segment| segment.parse().ok())
-                .map_or(
-                    future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
-                    |t| future::ok(Named(t)),
-                ),
+                .and_then(|segment| decode_segment(segment, store)?.parse().ok())
+                .map_or_else(|| future::err(parse_error(store)), |t| future::ok(Named(t))),
             None => future::err(http::status::StatusCode::BAD_REQUEST.into_response()),
         }
     }
 }
 
+/// An extractor for all path segments matched by the route's wildcards.
+///
+/// Unlike `Path` and `Named`, which each parse a single segment, `Params` hands over every
+/// matched segment, both by position and, for named wildcards, by name. This lets generic
+/// endpoints mounted under several route patterns introspect what matched:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::head::Params;
+///
+/// async fn show(params: Params) -> String {
+///     format!("{:?} (id: {:?})", params.segments(), params.get("id"))
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/users/{id}").get(show);
+///     app.at("/orgs/{}/members/{id}").get(show);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Requests without a matched route (e.g. handled by the default handler) have no parameters.
+#[derive(Clone, Debug, Default)]
+pub struct Params {
+    segments: Vec<String>,
+    named: HashMap<String, String>,
+}
+
+impl Params {
+    /// The matched segments, in the order they appear in the path.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// The segment matched by the named wildcard `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(|s| &**s)
+    }
+
+    /// The named wildcard matches, indexed by name.
+    pub fn named(&self) -> &HashMap<String, String> {
+        &self.named
+    }
+}
+
+impl<'a, 'b> From<&'b RouteMatch<'a>> for Params {
+    fn from(route_match: &'b RouteMatch<'a>) -> Params {
+        Params {
+            segments: route_match.vec.iter().map(|s| s.to_string()).collect(),
+            named: route_match
+                .map
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl<S: 'static> Extract<S> for Params {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let params = match params {
+            Some(params) => params,
+            None => return future::ok(Params::default()),
+        };
+        let segments = params
+            .vec
+            .iter()
+            .map(|segment| decode_segment(segment, store).map(Cow::into_owned))
+            .collect::<Option<_>>();
+        let named = params
+            .map
+            .iter()
+            .map(|(name, segment)| {
+                let segment = decode_segment(segment, store)?.into_owned();
+                Some((name.to_string(), segment))
+            })
+            .collect::<Option<_>>();
+        match (segments, named) {
+            (Some(segments), Some(named)) => future::ok(Params { segments, named }),
+            _ => future::err(parse_error(store)),
+        }
+    }
+}
+
+/// An extractor for the pattern of the matched resource, such as `/message/{id}`.
+///
+/// Unlike the request path, the pattern is shared by every request to the resource, which makes
+/// it a good key for metrics and logs:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::head::RoutePattern;
+///
+/// async fn show(RoutePattern(pattern): RoutePattern) -> String {
+///     pattern.unwrap_or_default()
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/message/{id}").get(show);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Requests handled by a fallback or the default handler have no pattern. Middleware can read it
+/// with `RequestContext::route_pattern`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoutePattern(pub Option<String>);
+
+impl<S: 'static> Extract<S> for RoutePattern {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        future::ok(
+            req.extensions()
+                .get::<RoutePattern>()
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// An extractor for several consecutive path segments at once.
+///
+/// `Segments<(A, B, ...)>` parses the next wildcard segments (as `Path` does, one per element) as
+/// the types of the tuple, which can have up to six `FromStr` elements:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use tide::head::Segments;
+///
+/// async fn repo(Segments((org, repo)): Segments<(String, String)>) -> String {
+///     format!("{}/{}", org, repo)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/orgs/{}/repos/{}").get(repo);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Fails like `Path` if a segment is missing or fails to parse.
+pub struct Segments<T>(pub T);
+
+/// Types that can be parsed from consecutive path segments by the `Segments` extractor.
+pub trait FromSegments: Sized + Send + 'static {
+    /// The number of segments to parse.
+    const LEN: usize;
+
+    /// Parse the value from exactly `LEN` (decoded) segments.
+    fn from_segments(segments: &[&str]) -> Option<Self>;
+}
+
+macro_rules! from_segments_impl {
+    ($len:expr; $($T:ident $i:tt),*) => {
+        impl<$($T: std::str::FromStr + Send + 'static),*> FromSegments for ($($T,)*) {
+            const LEN: usize = $len;
+
+            fn from_segments(segments: &[&str]) -> Option<Self> {
+                Some(($(segments[$i].parse().ok()?,)*))
+            }
+        }
+    };
+}
+
+from_segments_impl!(1; T0 0);
+from_segments_impl!(2; T0 0, T1 1);
+from_segments_impl!(3; T0 0, T1 1, T2 2);
+from_segments_impl!(4; T0 0, T1 1, T2 2, T3 3);
+from_segments_impl!(5; T0 0, T1 1, T2 2, T3 3, T4 4);
+from_segments_impl!(6; T0 0, T1 1, T2 2, T3 3, T4 4, T5 5);
+
+impl<T: FromSegments, S: 'static> Extract<S> for Segments<T> {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let &PathIdx(i) = req.extensions().get::<PathIdx>().unwrap_or(&PathIdx(0));
+        req.extensions_mut().insert(PathIdx(i + T::LEN));
+        let params = match params {
+            Some(params) => params,
+            None => {
+                return future::err(http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response())
+            }
+        };
+        let decoded = params
+            .vec
+            .get(i..i + T::LEN)
+            .unwrap_or(&[])
+            .iter()
+            .map(|segment| decode_segment(segment, store))
+            .collect::<Option<Vec<_>>>()
+            .filter(|decoded| decoded.len() == T::LEN);
+        let parsed = decoded.and_then(|decoded| {
+            let segments: Vec<&str> = decoded.iter().map(|segment| &**segment).collect();
+            T::from_segments(&segments)
+        });
+        match parsed {
+            Some(t) => future::ok(Segments(t)),
+            None => future::err(parse_error(store)),
+        }
+    }
+}
+
+/// An extractor deserializing the named path segments into a struct.
+///
+/// Each field is taken from the wildcard segment of the same name, and parsed as for a query
+/// string, so fields can be numbers, strings or anything `serde` can deserialize from a string:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use serde_derive::Deserialize;
+/// use tide::head::NamedSegments;
+///
+/// #[derive(Deserialize)]
+/// struct Repo {
+///     org: String,
+///     id: u32,
+/// }
+///
+/// async fn repo(NamedSegments(repo): NamedSegments<Repo>) -> String {
+///     format!("{}/{}", repo.org, repo.id)
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.at("/orgs/{org}/repos/{id}").get(repo);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Fails with a `BAD_REQUEST` response (or the configured `PathErrorStatus`) if a field is
+/// missing or fails to parse.
+pub struct NamedSegments<T>(pub T);
+
+impl<T, S> Extract<S> for NamedSegments<T>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+    S: 'static,
+{
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let named = params.as_ref().and_then(|params| {
+            params
+                .map
+                .iter()
+                .map(|(name, segment)| Some((*name, decode_segment(segment, store)?)))
+                .collect::<Option<HashMap<_, _>>>()
+        });
+        let parsed = named
+            .and_then(|named| serde_qs::to_string(&named).ok())
+            .and_then(|query| serde_qs::from_str(&query).ok());
+        match parsed {
+            Some(t) => future::ok(NamedSegments(t)),
+            None => future::err(parse_error(store)),
+        }
+    }
+}
+
 /// An extractor for query string in URL
 ///
 pub struct UrlQuery<T>(pub T);
@@ -223,3 +641,117 @@ where
         )
     }
 }
+
+/// An extractor for the point in time by which the response should be ready.
+///
+/// The deadline is derived from the `RequestTimeout` configured for the endpoint and from the
+/// timeout requested by the client through an `X-Request-Timeout` header (in seconds) or a
+/// gRPC-style `grpc-timeout` header (e.g. `500m`), whichever is earlier. Endpoints can hand the
+/// remaining budget to downstream calls and bail out early once it is exhausted. If neither is
+/// set, there is no deadline.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use std::time::Duration;
+/// use tide::{configuration::RequestTimeout, head::Deadline};
+///
+/// async fn search(deadline: Deadline) -> String {
+///     match deadline.remaining() {
+///         Some(budget) => format!("{}ms left", budget.as_millis()),
+///         None => "no deadline".to_string(),
+///     }
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.config(RequestTimeout(Duration::from_secs(10)));
+///     app.at("/search").get(search);
+///     app.serve().unwrap()
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(pub Option<Instant>);
+
+impl Deadline {
+    /// The time left until the deadline, or `None` if there is no deadline.
+    ///
+    /// Returns a zero duration once the deadline has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.0.map(|deadline| {
+            if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            }
+        })
+    }
+
+    /// Whether the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.0.map_or(false, |deadline| deadline <= Instant::now())
+    }
+}
+
+// Parse a timeout requested by the client, if any.
+//
+// Values that are malformed or too large to represent are ignored.
+fn requested_timeout(headers: &http::HeaderMap) -> Option<Duration> {
+    if let Some(secs) = headers
+        .get("X-Request-Timeout")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+    {
+        if secs.is_finite() && secs >= 0.0 && secs < u64::max_value() as f64 {
+            let nanos = (secs.fract() * 1e9) as u32;
+            return Some(Duration::new(secs.trunc() as u64, nanos));
+        }
+    }
+
+    // gRPC allows at most 8 digits followed by a one-letter unit.
+    let value = headers.get("grpc-timeout")?.to_str().ok()?.trim();
+    if value.len() < 2 || value.len() > 9 || !value.is_ascii() {
+        return None;
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => amount.checked_mul(3600).map(Duration::from_secs),
+        "M" => amount.checked_mul(60).map(Duration::from_secs),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+impl<S: 'static> Extract<S> for Deadline {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let start = req
+            .extensions()
+            .get::<ReceivedAt>()
+            .map_or_else(Instant::now, |received| received.0);
+        let configured = store.read::<RequestTimeout>().map(|timeout| timeout.0);
+        let requested = requested_timeout(req.headers());
+        let timeout = match (configured, requested) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        future::ok(Deadline(
+            timeout.and_then(|timeout| start.checked_add(timeout)),
+        ))
+    }
+}
@@ -0,0 +1,103 @@
+//! Liveness and readiness endpoints for orchestration probes, set up by `App::enable_health`.
+
+use futures::future::{self, FutureObj};
+use http::{Method, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    body::Json,
+    longpoll::{self, LongPoll},
+    response::WithStatus,
+    IntoResponse, Request, Response,
+};
+
+/// How long a readiness check may take by default before it counts as failed, matching the
+/// default probe timeout of Kubernetes.
+pub(crate) const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A named readiness check, registered with `App::readiness_check`.
+pub(crate) struct Check<Data> {
+    pub(crate) name: String,
+    pub(crate) check: Box<dyn Fn(Data) -> FutureObj<'static, Result<(), String>> + Send + Sync>,
+}
+
+/// The probes of an app, answered ahead of routing.
+pub(crate) struct Health<Data> {
+    live: String,
+    ready: String,
+    checks: Arc<Vec<Check<Data>>>,
+    timeout: Duration,
+}
+
+impl<Data: Clone + Send + Sync + 'static> Health<Data> {
+    pub(crate) fn new(
+        live: String,
+        ready: String,
+        checks: Vec<Check<Data>>,
+        timeout: Duration,
+    ) -> Health<Data> {
+        Health {
+            live,
+            ready,
+            checks: Arc::new(checks),
+            timeout,
+        }
+    }
+
+    /// The response to `req` if it is a `GET` or `HEAD` request for one of the probes.
+    ///
+    /// A server answering the liveness probe is alive. The readiness probe answers `200 OK` if
+    /// all checks pass and `503 Service Unavailable` otherwise, with a JSON report of every
+    /// check, e.g. `{"status":"unavailable","checks":{"db":"connection refused"}}`.
+    pub(crate) fn probe(&self, req: &Request, data: &Data) -> Option<FutureObj<'static, Response>> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return None;
+        }
+        let path = req.uri().path();
+        if path == self.live {
+            Some(FutureObj::new(Box::new(future::ready("ok".into_response()))))
+        } else if path == self.ready {
+            Some(ready(self.checks.clone(), self.timeout, data.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Run the `checks` concurrently, each failing if it takes longer than `timeout`.
+fn ready<Data>(
+    checks: Arc<Vec<Check<Data>>>,
+    timeout: Duration,
+    data: Data,
+) -> FutureObj<'static, Response>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    FutureObj::new(Box::new(
+        async move {
+            let pending = checks
+                .iter()
+                .map(|check| longpoll::wait((check.check)(data.clone()), timeout));
+            let outcomes = await!(future::join_all(pending));
+            let mut ready = true;
+            let mut results = serde_json::Map::new();
+            for (check, outcome) in checks.iter().zip(outcomes) {
+                let result = match outcome {
+                    LongPoll::Ready(result) => result,
+                    LongPoll::TimedOut => Err(format!("timed out after {:?}", timeout)),
+                };
+                ready &= result.is_ok();
+                let result = result.err().unwrap_or_else(|| "ok".to_owned());
+                results.insert(check.name.clone(), result.into());
+            }
+            let (status, report) = if ready {
+                (StatusCode::OK, "ready")
+            } else {
+                (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+            };
+            let body = serde_json::json!({ "status": report, "checks": results });
+            WithStatus(status, Json(body)).into_response()
+        },
+    ))
+}
@@ -0,0 +1,68 @@
+//! Serving build and version information, for dashboards and deploy verification.
+//!
+//! The information is supplied as a `BuildInfo` configuration item, typically filled in from
+//! environment variables set at build time:
+//!
+//! ```rust, no_run
+//! use tide::info::{self, BuildInfo};
+//!
+//! let mut app = tide::App::new(());
+//! app.config(BuildInfo {
+//!     version: env!("CARGO_PKG_VERSION").to_owned(),
+//!     git_sha: option_env!("GIT_SHA").map(str::to_owned),
+//!     build_timestamp: option_env!("BUILD_TIMESTAMP").map(str::to_owned),
+//! });
+//! app.at("/info").get(info::endpoint());
+//! ```
+//!
+//! `GET /info` then responds with:
+//!
+//! ```json
+//! {"version":"1.2.0","git_sha":"4cff7a9","build_timestamp":"2019-03-01T12:00:00Z","uptime":3600}
+//! ```
+
+use futures::future;
+use serde_derive::Serialize;
+use std::time::Instant;
+
+use crate::{body::Json, configuration::ExtractConfiguration, IntoResponse, Response};
+
+/// Build information about the app, served by `endpoint`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BuildInfo {
+    /// The version of the app.
+    pub version: String,
+    /// The commit the app was built from.
+    pub git_sha: Option<String>,
+    /// When the app was built.
+    pub build_timestamp: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Info<'a> {
+    #[serde(flatten)]
+    build: &'a BuildInfo,
+    /// Seconds since the endpoint was created, i.e. roughly since the app was started.
+    uptime: u64,
+}
+
+/// An endpoint serving the app's `BuildInfo` as JSON, along with its uptime in seconds.
+///
+/// Responds with `500 Internal Server Error` if no `BuildInfo` is configured.
+pub fn endpoint(
+) -> impl Fn(ExtractConfiguration<BuildInfo>) -> future::Ready<Response> + Send + Sync + 'static {
+    let started = Instant::now();
+    move |ExtractConfiguration(build): ExtractConfiguration<BuildInfo>| {
+        let res = match build {
+            Some(build) => Json(Info {
+                build: &build,
+                uptime: started.elapsed().as_secs(),
+            })
+            .into_response(),
+            None => "info::endpoint requires a BuildInfo configuration item"
+                .with_status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .into_response(),
+        };
+        future::ready(res)
+    }
+}
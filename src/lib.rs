@@ -16,24 +16,48 @@ pub mod body;
 pub mod configuration;
 mod cookies;
 mod endpoint;
+mod error;
 mod extract;
+mod forwarded;
+mod hash;
 pub mod head;
+mod health;
+pub mod info;
+pub mod longpoll;
 pub mod middleware;
 mod request;
-mod response;
+pub mod response;
+mod route_table;
 mod router;
+mod sampler;
 #[cfg(feature = "hyper")]
 mod serve;
+pub mod split;
+pub mod sse;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+pub mod tls;
+pub mod wellknown;
 
 pub use crate::{
-    app::{App, AppData, Server},
+    app::{App, AppData, Dispatcher, Server, SharedData},
     configuration::ExtractConfiguration,
     cookies::Cookies,
     endpoint::Endpoint,
+    error::Error,
     extract::Extract,
     middleware::Middleware,
-    request::{Compute, Computed, Request},
+    request::{Compute, Computed, ConnectionInfo, FromExtension, Request},
     response::{IntoResponse, Response},
-    router::{Resource, Router},
+    router::{EndpointHandle, Resource, RouteMeta, Router, Urls},
 };
 pub use path_table::RouteMatch;
+
+#[cfg(feature = "hyper")]
+pub use crate::app::BoundServer;
+#[cfg(feature = "hyper")]
+pub use crate::serve::HyperService;
+#[cfg(all(unix, feature = "hyper"))]
+pub use crate::serve::systemd_listener;
+
+#[cfg(feature = "signals")]
+pub use crate::serve::shutdown_signal;
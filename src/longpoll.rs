@@ -0,0 +1,107 @@
+//! Helpers for serving long-polling endpoints.
+//!
+//! Long polling is a common fallback where WebSockets or server-sent events are blocked: the
+//! client issues a `GET`, and the server holds on to it until new data is available or a timeout
+//! expires, after which the client simply polls again.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #![feature(async_await, futures_api, await_macro)]
+//! use std::time::Duration;
+//! use tide::{body::Json, longpoll::{self, LongPoll}};
+//!
+//! async fn next_messages() -> Vec<String> {
+//!     // wait for new messages to arrive
+//!     # vec![]
+//! }
+//!
+//! async fn poll() -> LongPoll<Json<Vec<String>>> {
+//!     let messages = await!(longpoll::wait(next_messages(), Duration::from_secs(30)));
+//!     messages.map(Json)
+//! }
+//!
+//! fn main() {
+//!     let mut app = tide::App::new(());
+//!     app.at("/messages/poll").get(poll);
+//!     app.serve().unwrap();
+//! }
+//! ```
+//!
+//! When the client disconnects, the pending response is dropped, and with it the event source
+//! future.
+
+use futures::future::{self, Future};
+use futures_timer::Delay;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::{IntoResponse, Response};
+
+/// The outcome of waiting on an event source.
+///
+/// Responds with the available data, or with `204 No Content` if the wait timed out.
+pub enum LongPoll<T> {
+    /// Data became available in time.
+    Ready(T),
+    /// No data became available before the timeout.
+    TimedOut,
+}
+
+impl<T> LongPoll<T> {
+    /// Map the available data, if any.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> LongPoll<U> {
+        match self {
+            LongPoll::Ready(t) => LongPoll::Ready(f(t)),
+            LongPoll::TimedOut => LongPoll::TimedOut,
+        }
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for LongPoll<T> {
+    fn into_response(self) -> Response {
+        match self {
+            LongPoll::Ready(t) => t.into_response(),
+            LongPoll::TimedOut => http::status::StatusCode::NO_CONTENT.into_response(),
+        }
+    }
+}
+
+/// Wait up to `timeout` for `source` to produce data.
+pub fn wait<F>(source: F, timeout: Duration) -> impl Future<Output = LongPoll<F::Output>>
+where
+    F: Future + Send + 'static,
+{
+    let mut source = Box::pin(source);
+    let mut delay = Delay::new(timeout);
+    future::poll_fn(move |waker| {
+        if let Poll::Ready(t) = source.as_mut().poll(waker) {
+            return Poll::Ready(LongPoll::Ready(t));
+        }
+        match Pin::new(&mut delay).poll(waker) {
+            Poll::Ready(_) => Poll::Ready(LongPoll::TimedOut),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn ready_before_timeout() {
+        let res = block_on(wait(future::ready("foo"), Duration::from_secs(10))).into_response();
+        assert_eq!(res.status(), http::status::StatusCode::OK);
+    }
+
+    #[test]
+    fn timed_out() {
+        let never = future::poll_fn(|_| Poll::Pending::<&str>);
+        let res = block_on(wait(never, Duration::from_millis(10))).into_response();
+        assert_eq!(res.status(), http::status::StatusCode::NO_CONTENT);
+    }
+}
@@ -0,0 +1,31 @@
+use futures::future::FutureObj;
+
+use crate::{body, middleware::RequestContext, Middleware, Response};
+
+/// Middleware buffering the body of every request in memory, making it replayable.
+///
+/// See `body::buffer` for details. Requests whose body cannot be read are answered with
+/// `400 Bad Request`, or `413 Payload Too Large` beyond the `max_buffered_body_size` of the
+/// `Configuration`, 2 MiB by default.
+#[derive(Clone, Default, Debug)]
+pub struct BufferBody;
+
+impl BufferBody {
+    /// Create the middleware.
+    pub fn new() -> BufferBody {
+        BufferBody::default()
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for BufferBody {
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                if let Err(e) = await!(body::buffer(&mut ctx.req)) {
+                    return body::read_err(e);
+                }
+                await!(ctx.next())
+            },
+        ))
+    }
+}
@@ -0,0 +1,72 @@
+use futures::future::FutureObj;
+use http::header::{HeaderValue, CACHE_CONTROL};
+use crate::{middleware::RequestContext, route_table::RouteTable, Middleware, Response};
+
+/// Middleware setting `Cache-Control` on responses according to per-path rules.
+///
+/// Rules use the same path syntax as `Router::at`, so fingerprinted assets can be cached forever
+/// while their entry point is always revalidated:
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// use tide::middleware::CacheControl;
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(
+///     CacheControl::new()
+///         .rule("/assets/*", "public, max-age=31536000, immutable")
+///         .rule("/index.html", "no-cache"),
+/// );
+/// ```
+///
+/// Only successful responses are affected, and a `Cache-Control` header set by the endpoint
+/// always takes precedence.
+#[derive(Clone)]
+pub struct CacheControl {
+    rules: RouteTable<HeaderValue>,
+}
+
+impl Default for CacheControl {
+    fn default() -> CacheControl {
+        CacheControl {
+            rules: RouteTable::new(),
+        }
+    }
+}
+
+impl CacheControl {
+    /// Create the middleware without any rules.
+    pub fn new() -> CacheControl {
+        CacheControl::default()
+    }
+
+    /// Use `value` as `Cache-Control` for responses to paths matching `path`.
+    ///
+    /// Panics if `value` is not a valid header value, or if `path` is not a valid route.
+    pub fn rule(mut self, path: &str, value: &str) -> Self {
+        let value = HeaderValue::from_str(value).expect("Cannot create Cache-Control header");
+        *self.rules.setup_table(path).resource_mut() = Some(value);
+        self
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for CacheControl {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let value = self
+                    .rules
+                    .route(ctx.req.uri().path())
+                    .map(|(value, _)| value.clone());
+
+                let mut res = await!(ctx.next());
+                if let Some(value) = value {
+                    if res.status().is_success() {
+                        res.headers_mut().entry(CACHE_CONTROL).unwrap().or_insert(value);
+                    }
+                }
+                res
+            },
+        ))
+    }
+}
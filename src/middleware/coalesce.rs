@@ -0,0 +1,171 @@
+use futures::{channel::oneshot, future::FutureObj};
+use http::header::{HeaderName, AUTHORIZATION, COOKIE, HOST};
+use http_service::Body;
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::Mutex;
+
+use crate::{middleware::RequestContext, IntoResponse, Middleware, Request, Response};
+
+/// A buffered response, handed to every request waiting for it.
+#[derive(Clone)]
+struct Shared {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl IntoResponse for Shared {
+    fn into_response(self) -> Response {
+        let mut res = http::Response::new(Body::from(self.body));
+        *res.status_mut() = self.status;
+        *res.headers_mut() = self.headers;
+        res
+    }
+}
+
+/// Middleware collapsing identical concurrent `GET` requests into a single endpoint call.
+///
+/// When a cache entry of an expensive endpoint expires, many clients can ask for the same resource
+/// at once. With this middleware, only the first request runs the endpoint, and identical requests
+/// arriving meanwhile wait for its response, which is buffered and copied to each of them:
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// use tide::middleware::Coalesce;
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(Coalesce::new().vary("Accept-Language"));
+/// app.at("/report").get(async || "expensive report");
+/// ```
+///
+/// Requests are identical if they have the same scheme, host, path and query, and the same values
+/// of the `Authorization` and `Cookie` headers as well as of any header added with `vary`, so
+/// responses are never shared between users or virtual hosts. Endpoints streaming their response
+/// indefinitely must not be coalesced, as their response cannot be buffered. If the first
+/// request fails or is cancelled, each waiting request runs the endpoint itself.
+pub struct Coalesce {
+    vary: Vec<HeaderName>,
+    in_flight: Mutex<HashMap<String, Vec<oneshot::Sender<Shared>>>>,
+}
+
+impl Default for Coalesce {
+    fn default() -> Self {
+        Coalesce {
+            vary: vec![AUTHORIZATION, COOKIE],
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Coalesce {
+    /// Create the middleware.
+    pub fn new() -> Coalesce {
+        Coalesce::default()
+    }
+
+    /// Only coalesce requests with the same value of the header `name`.
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn vary(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("Invalid header name `{}`", name));
+        self.vary.push(name);
+        self
+    }
+
+    fn key(&self, req: &Request) -> String {
+        let uri = req.uri();
+        let host = uri
+            .authority_part()
+            .map(|authority| authority.as_str())
+            .or_else(|| req.headers().get(HOST)?.to_str().ok())
+            .unwrap_or("");
+        let mut key = format!(
+            "{}://{}{}",
+            uri.scheme_str().unwrap_or(""),
+            host.to_ascii_lowercase(),
+            uri.path_and_query().map_or("/", |path| path.as_str())
+        );
+        for name in &self.vary {
+            for value in req.headers().get_all(name) {
+                key.push('\n');
+                key.push_str(name.as_str());
+                key.push(':');
+                key.push_str(&String::from_utf8_lossy(value.as_bytes()));
+            }
+        }
+        key
+    }
+}
+
+/// The first of a group of identical requests; withdraws the group if dropped early.
+struct Leader<'a> {
+    in_flight: &'a Mutex<HashMap<String, Vec<oneshot::Sender<Shared>>>>,
+    key: Option<String>,
+}
+
+impl<'a> Leader<'a> {
+    fn finish(mut self) -> Vec<oneshot::Sender<Shared>> {
+        let key = self.key.take().unwrap();
+        self.in_flight.lock().unwrap().remove(&key).unwrap_or_default()
+    }
+}
+
+impl<'a> Drop for Leader<'a> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.in_flight.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for Coalesce {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                if ctx.req.method() != http::Method::GET {
+                    return await!(ctx.next());
+                }
+
+                let key = self.key(&ctx.req);
+                let waiting = match self.in_flight.lock().unwrap().entry(key.clone()) {
+                    Entry::Occupied(mut group) => {
+                        let (tx, rx) = oneshot::channel();
+                        group.get_mut().push(tx);
+                        Some(rx)
+                    }
+                    Entry::Vacant(group) => {
+                        group.insert(Vec::new());
+                        None
+                    }
+                };
+                if let Some(rx) = waiting {
+                    return match await!(rx) {
+                        Ok(shared) => shared.into_response(),
+                        Err(_) => await!(ctx.next()),
+                    };
+                }
+
+                let leader = Leader {
+                    in_flight: &self.in_flight,
+                    key: Some(key),
+                };
+                let res = await!(ctx.next());
+                let (parts, body) = res.into_parts();
+                let body = match await!(body.into_vec()) {
+                    Ok(body) => bytes::Bytes::from(body),
+                    Err(_) => return http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                };
+                let shared = Shared {
+                    status: parts.status,
+                    headers: parts.headers.clone(),
+                    body: body.clone(),
+                };
+                for waiter in leader.finish() {
+                    let _ = waiter.send(shared.clone());
+                }
+                http::Response::from_parts(parts, Body::from(body))
+            },
+        ))
+    }
+}
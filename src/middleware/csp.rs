@@ -0,0 +1,119 @@
+use futures::future::{self, FutureObj};
+use http::header::{HeaderValue, CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY_REPORT_ONLY};
+use http::StatusCode;
+use std::fmt;
+
+use crate::{
+    configuration::Store, middleware::RequestContext, Extract, IntoResponse, Middleware, Request,
+    Response, RouteMatch,
+};
+
+/// Middleware setting a `Content-Security-Policy` header with a fresh nonce for each request.
+///
+/// Every occurrence of `{nonce}` in the policy is replaced by the nonce, which endpoints obtain
+/// with the `CspNonce` extractor to mark their inline scripts and styles:
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// use tide::middleware::{ContentSecurityPolicy, CspNonce};
+///
+/// async fn page(nonce: CspNonce) -> String {
+///     format!("<script nonce=\"{}\">init()</script>", nonce)
+/// }
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(ContentSecurityPolicy::new(
+///     "script-src 'nonce-{nonce}' 'strict-dynamic'; object-src 'none'; base-uri 'none'",
+/// ));
+/// app.at("/").get(page);
+/// ```
+///
+/// Responses that already have the header keep it.
+#[derive(Clone, Debug)]
+pub struct ContentSecurityPolicy {
+    policy: String,
+    report_only: bool,
+}
+
+impl ContentSecurityPolicy {
+    /// Create the middleware for `policy`.
+    pub fn new(policy: impl Into<String>) -> ContentSecurityPolicy {
+        ContentSecurityPolicy {
+            policy: policy.into(),
+            report_only: false,
+        }
+    }
+
+    /// Send the policy as `Content-Security-Policy-Report-Only`, reporting violations without
+    /// blocking anything.
+    pub fn report_only(mut self, report_only: bool) -> Self {
+        self.report_only = report_only;
+        self
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for ContentSecurityPolicy {
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let nonce = CspNonce::generate();
+                let policy = self.policy.replace("{nonce}", &nonce.0);
+                ctx.req.extensions_mut().insert(nonce);
+
+                let mut res = await!(ctx.next());
+                let name = if self.report_only {
+                    CONTENT_SECURITY_POLICY_REPORT_ONLY
+                } else {
+                    CONTENT_SECURITY_POLICY
+                };
+                if let Ok(policy) = HeaderValue::from_str(&policy) {
+                    res.headers_mut().entry(name).unwrap().or_insert(policy);
+                }
+                res
+            },
+        ))
+    }
+}
+
+/// An extractor for the nonce of the request's Content Security Policy.
+///
+/// Requires the `ContentSecurityPolicy` middleware; extraction fails with `500 Internal Server
+/// Error` without it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    /// 128 random bits, hex-encoded.
+    fn generate() -> CspNonce {
+        let bytes: [u8; 16] = rand::random();
+        CspNonce(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// The nonce, as it appears in the policy.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<Data: 'static> Extract<Data> for CspNonce {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let nonce = req.extensions().get::<CspNonce>().cloned().ok_or_else(|| {
+            "CspNonce requires the ContentSecurityPolicy middleware"
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+                .into_response()
+        });
+        future::ready(nonce)
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use futures::future::FutureObj;
+use http::header::{HeaderValue, LINK};
+
+use crate::{middleware::RequestContext, Middleware, Response};
+
+/// The deprecation of an endpoint, set through `Resource::deprecated`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deprecation {
+    sunset: HeaderValue,
+    link: HeaderValue,
+}
+
+impl Deprecation {
+    /// Describe a deprecation, with the date the endpoint will stop working and a link to
+    /// migration documentation.
+    ///
+    /// Panics if `link` cannot be used in a header.
+    pub fn new(sunset: DateTime<Utc>, link: &str) -> Deprecation {
+        let sunset = sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let link = format!("<{}>; rel=\"deprecation\"", link);
+        Deprecation {
+            sunset: HeaderValue::from_str(&sunset).unwrap(),
+            link: HeaderValue::from_str(&link).expect("Cannot create Link header"),
+        }
+    }
+}
+
+/// Middleware stamping `Deprecation`, `Sunset` and `Link` headers on responses of deprecated
+/// endpoints.
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// use chrono::{TimeZone, Utc};
+/// use tide::middleware::DeprecationHeaders;
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(DeprecationHeaders::new());
+/// app.at("/v1/users")
+///     .deprecated(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0), "https://example.com/migrate-to-v2")
+///     .get(async || "[]");
+/// ```
+///
+/// Headers set by the endpoint itself are left untouched.
+#[derive(Clone, Default, Debug)]
+pub struct DeprecationHeaders;
+
+impl DeprecationHeaders {
+    /// Create the middleware.
+    pub fn new() -> DeprecationHeaders {
+        DeprecationHeaders::default()
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for DeprecationHeaders {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let deprecation = ctx.get_item::<Deprecation>().cloned();
+                let mut res = await!(ctx.next());
+                if let Some(deprecation) = deprecation {
+                    let headers = res.headers_mut();
+                    headers
+                        .entry("Deprecation")
+                        .unwrap()
+                        .or_insert(HeaderValue::from_static("true"));
+                    headers
+                        .entry("Sunset")
+                        .unwrap()
+                        .or_insert(deprecation.sunset);
+                    // Other `Link`s of the response, such as preload hints, stay alongside.
+                    headers.append(LINK, deprecation.link);
+                }
+                res
+            },
+        ))
+    }
+}
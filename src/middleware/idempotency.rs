@@ -0,0 +1,274 @@
+//! Replaying responses to retried requests carrying an `Idempotency-Key` header.
+//!
+//! Clients retrying a `POST` or `PATCH` after a timeout cannot know whether the first attempt
+//! took effect. With the `Idempotency` middleware, they send the same `Idempotency-Key` with every
+//! attempt; the first response is stored, and retries get it replayed instead of running the
+//! endpoint again:
+//!
+//! ```rust, no_run
+//! # #![feature(async_await)]
+//! use std::time::Duration;
+//! use tide::middleware::idempotency::Idempotency;
+//!
+//! let mut app = tide::App::new(());
+//! app.middleware(Idempotency::new().ttl(Duration::from_secs(60 * 60)));
+//! app.at("/payments").post(async || "charged");
+//! ```
+//!
+//! Keys are scoped to the method and path of the request, and to the caller: by default its
+//! `Authorization` and `Cookie` headers, so that a client reusing the key of another cannot get
+//! their response. Use `Idempotency::scope` to identify callers otherwise, e.g. by session.
+//!
+//! A retry arriving while the first attempt is still running is rejected with `409 Conflict`,
+//! and one with a different body than the first attempt with `422 Unprocessable Entity`. Server
+//! errors are not stored, so a retry gets another chance to succeed. Responses are kept in memory
+//! by default; implement `ResponseStore` to share them between instances.
+
+use futures::future::{self, FutureObj};
+use http::header::HeaderValue;
+use http_service::Body;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{
+    body, hash::Fnv, middleware::RequestContext, IntoResponse, Middleware, Request, Response,
+};
+
+/// A response stored for replay.
+#[derive(Clone, Debug)]
+pub struct StoredResponse {
+    /// The status of the response.
+    pub status: http::StatusCode,
+    /// The headers of the response.
+    pub headers: http::HeaderMap,
+    /// The complete body of the response.
+    pub body: bytes::Bytes,
+    /// A fingerprint of the body of the request the response was given for.
+    pub fingerprint: u64,
+}
+
+/// Storage for the responses of idempotent requests.
+pub trait ResponseStore: Send + Sync {
+    /// Look up the response stored under `key`, if it has not expired.
+    fn get<'a>(&'a self, key: &'a str) -> FutureObj<'a, Option<StoredResponse>>;
+
+    /// Store `response` under `key` for `ttl`.
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        response: StoredResponse,
+        ttl: Duration,
+    ) -> FutureObj<'a, ()>;
+}
+
+/// A `ResponseStore` keeping responses in the memory of the process.
+///
+/// Expired responses are dropped when looked up, and in a sweep whenever the number of entries
+/// has doubled since the last one.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    responses: HashMap<String, (Instant, StoredResponse)>,
+    sweep_at: usize,
+}
+
+// The fewest entries worth sweeping for expired ones.
+const MIN_SWEEP: usize = 64;
+
+impl MemoryStore {
+    /// Create an empty store.
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl ResponseStore for MemoryStore {
+    fn get<'a>(&'a self, key: &'a str) -> FutureObj<'a, Option<StoredResponse>> {
+        let mut entries = self.entries.lock().unwrap();
+        let response = match entries.responses.get(key) {
+            Some((expires, response)) if *expires > Instant::now() => Some(response.clone()),
+            Some(_) => {
+                entries.responses.remove(key);
+                None
+            }
+            None => None,
+        };
+        FutureObj::new(Box::new(future::ready(response)))
+    }
+
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        response: StoredResponse,
+        ttl: Duration,
+    ) -> FutureObj<'a, ()> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.len() >= entries.sweep_at.max(MIN_SWEEP) {
+            entries.responses.retain(|_, (expires, _)| *expires > now);
+            entries.sweep_at = 2 * entries.responses.len();
+        }
+        entries.responses.insert(key.to_owned(), (now + ttl, response));
+        FutureObj::new(Box::new(future::ready(())))
+    }
+}
+
+/// Middleware storing and replaying the responses of requests with an `Idempotency-Key`.
+///
+/// See the [module documentation](index.html) for details.
+pub struct Idempotency<S = MemoryStore> {
+    store: S,
+    ttl: Duration,
+    scope: Box<dyn Fn(&Request) -> String + Send + Sync>,
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Idempotency<MemoryStore> {
+    /// Create the middleware, keeping responses in memory for 24 hours.
+    pub fn new() -> Idempotency<MemoryStore> {
+        Idempotency::with_store(MemoryStore::new())
+    }
+}
+
+impl Default for Idempotency<MemoryStore> {
+    fn default() -> Self {
+        Idempotency::new()
+    }
+}
+
+impl<S: ResponseStore> Idempotency<S> {
+    /// Create the middleware, keeping responses in `store` for 24 hours.
+    pub fn with_store(store: S) -> Idempotency<S> {
+        Idempotency {
+            store,
+            ttl: Duration::from_secs(24 * 60 * 60),
+            scope: Box::new(credentials),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Set how long responses are replayed for.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Scope keys to the caller identified by `scope`, instead of the `Authorization` and
+    /// `Cookie` headers of the request.
+    ///
+    /// Requests for which `scope` returns the same string share their keys.
+    pub fn scope<F>(mut self, scope: F) -> Self
+    where
+        F: Fn(&Request) -> String + Send + Sync + 'static,
+    {
+        self.scope = Box::new(scope);
+        self
+    }
+}
+
+/// The default scope: a fingerprint of the credentials of the request.
+fn credentials(req: &Request) -> String {
+    let mut hasher = Fnv::default();
+    for name in &[http::header::AUTHORIZATION, http::header::COOKIE] {
+        for value in req.headers().get_all(name) {
+            hasher.write(value.as_bytes());
+            hasher.write_u8(0);
+        }
+        hasher.write_u8(0xff);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Removes a key from the in-flight set when the request is done, even if it is cancelled.
+struct InFlight {
+    keys: Arc<Mutex<HashSet<String>>>,
+    key: String,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.keys.lock().unwrap().remove(&self.key);
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut res = http::Response::new(Body::from(stored.body));
+    *res.status_mut() = stored.status;
+    *res.headers_mut() = stored.headers;
+    res.headers_mut()
+        .insert("Idempotent-Replayed", HeaderValue::from_static("true"));
+    res
+}
+
+impl<Data: Clone + Send, S: ResponseStore> Middleware<Data> for Idempotency<S> {
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let method = ctx.req.method().clone();
+                if method != http::Method::POST && method != http::Method::PATCH {
+                    return await!(ctx.next());
+                }
+                let key = ctx.req.headers().get("Idempotency-Key").map(|key| {
+                    let key = String::from_utf8_lossy(key.as_bytes());
+                    let scope = (self.scope)(&ctx.req);
+                    format!("{} {} {} {}", method, ctx.req.uri().path(), scope, key)
+                });
+                let key = match key {
+                    Some(key) => key,
+                    None => return await!(ctx.next()),
+                };
+                // The body stays readable by the endpoint once buffered.
+                let fingerprint = match await!(body::buffer(&mut ctx.req)) {
+                    Ok(body) => crate::hash::fnv(&body),
+                    Err(e) => return body::read_err(e),
+                };
+                let replay_matching = |stored: StoredResponse| {
+                    if stored.fingerprint == fingerprint {
+                        replay(stored)
+                    } else {
+                        http::StatusCode::UNPROCESSABLE_ENTITY.into_response()
+                    }
+                };
+
+                if let Some(stored) = await!(self.store.get(&key)) {
+                    return replay_matching(stored);
+                }
+                if !self.in_flight.lock().unwrap().insert(key.clone()) {
+                    return http::StatusCode::CONFLICT.into_response();
+                }
+                let _in_flight = InFlight {
+                    keys: self.in_flight.clone(),
+                    key: key.clone(),
+                };
+                // The first attempt may have finished between the lookup and claiming the key.
+                if let Some(stored) = await!(self.store.get(&key)) {
+                    return replay_matching(stored);
+                }
+
+                let res = await!(ctx.next());
+                if res.status().is_server_error() {
+                    return res;
+                }
+                let (parts, body) = res.into_parts();
+                let body = match await!(body.into_vec()) {
+                    Ok(body) => bytes::Bytes::from(body),
+                    Err(_) => return http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                };
+                let stored = StoredResponse {
+                    status: parts.status,
+                    headers: parts.headers.clone(),
+                    body: body.clone(),
+                    fingerprint,
+                };
+                await!(self.store.put(&key, stored, self.ttl));
+                http::Response::from_parts(parts, Body::from(body))
+            },
+        ))
+    }
+}
@@ -2,9 +2,105 @@ use slog::{info, o, Drain};
 use slog_async;
 use slog_term;
 
-use futures::future::FutureObj;
+use futures::{future::FutureObj, prelude::*};
+use http::header::HeaderName;
+use http_service::Body;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::{middleware::RequestContext, Middleware, Response};
+use crate::{
+    head::RoutePattern,
+    middleware::RequestContext,
+    request::ConnectionInfo,
+    sampler::{self, Sampler},
+    Middleware, Request, Response,
+};
+
+/// Output format of the access log written by the root logger.
+///
+/// The format is selected through the app configuration:
+///
+/// ```rust, no_run
+/// let mut app = tide::App::new(());
+/// app.config(tide::middleware::logger::Format::Json);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Human-readable lines like `GET /hello 200`.
+    Compact,
+    /// One JSON object per request and line, ready to be ingested by log pipelines.
+    ///
+    /// The fields are `timestamp`, `method`, `path`, `route` (the pattern of the matched
+    /// resource), `status`, `latency_ms` (until the response head), `bytes` (of the response
+    /// body, as sent), `client_ip` and `request_id`. The line is written once the response body
+    /// has been sent.
+    Json,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format::Compact
+    }
+}
+
+/// Sampling policy of the access log, selected through the app configuration.
+///
+/// At high request rates, logging every request dominates the cost of serving it. With sampling,
+/// only a fraction of successful requests is logged, evenly spread over the request sequence.
+/// Client and server errors are always logged, as are requests carrying the debug header, if one
+/// is configured.
+///
+/// ```rust, no_run
+/// use tide::middleware::logger::Sampling;
+///
+/// let mut app = tide::App::new(());
+/// app.config(Sampling::new(0.01).always_log_with("x-debug-log"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sampling {
+    rate: f64,
+    debug_header: Option<HeaderName>,
+}
+
+impl Sampling {
+    /// Log the given fraction, between `0.0` and `1.0`, of successful requests.
+    pub fn new(rate: f64) -> Sampling {
+        Sampling {
+            rate: sampler::clamp_rate(rate),
+            debug_header: None,
+        }
+    }
+
+    /// Always log requests carrying the given header.
+    ///
+    /// Panics if `header` is not a valid header name.
+    pub fn always_log_with(mut self, header: &str) -> Self {
+        let header = HeaderName::from_bytes(header.as_bytes()).expect("Invalid header name");
+        self.debug_header = Some(header);
+        self
+    }
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling::new(1.0)
+    }
+}
+
+/// A drain writing the bare message of every record as a line to stdout.
+struct Lines;
+
+impl Drain for Lines {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        writeln!(out, "{}", record.msg())
+    }
+}
 
 /// Root logger for Tide. Wraps over logger provided by slog.SimpleLogger
 ///
@@ -12,6 +108,9 @@ use crate::{middleware::RequestContext, Middleware, Response};
 pub(crate) struct RootLogger {
     // drain: dyn slog::Drain,
     inner_logger: slog::Logger,
+    /// The logger of the JSON lines, started by the first request logged in that format.
+    json_logger: Mutex<Option<slog::Logger>>,
+    sampler: Sampler,
 }
 
 impl RootLogger {
@@ -21,7 +120,28 @@ impl RootLogger {
         let drain = slog_async::Async::new(drain).build().fuse();
 
         let log = slog::Logger::root(drain, o!());
-        RootLogger { inner_logger: log }
+
+        RootLogger {
+            inner_logger: log,
+            json_logger: Mutex::new(None),
+            sampler: Sampler::default(),
+        }
+    }
+
+    /// The logger of the JSON lines, starting its thread if this is the first line.
+    fn json_logger(&self) -> slog::Logger {
+        let mut json_logger = self.json_logger.lock().unwrap();
+        json_logger
+            .get_or_insert_with(|| {
+                let drain = slog_async::Async::new(Lines.ignore_res()).build().fuse();
+                slog::Logger::root(drain, o!())
+            })
+            .clone()
+    }
+
+    /// The logger for messages of the server itself, such as those about its shutdown.
+    pub(crate) fn logger(&self) -> slog::Logger {
+        self.inner_logger.clone()
     }
 }
 
@@ -31,14 +151,159 @@ impl<Data: Clone + Send> Middleware<Data> for RootLogger {
     fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
         FutureObj::new(Box::new(
             async move {
-                let path = ctx.req.uri().path().to_owned();
-                let method = ctx.req.method().as_str().to_owned();
+                let format = ctx.get_item::<Format>().cloned().unwrap_or_default();
+                let sampling = ctx.get_item::<Sampling>().cloned().unwrap_or_default();
+                let debug = sampling
+                    .debug_header
+                    .as_ref()
+                    .map_or(false, |header| ctx.req.headers().contains_key(header));
+                let line = JsonLine::new(&ctx.req);
+                let start = Instant::now();
 
                 let res = await!(ctx.next());
                 let status = res.status();
-                info!(self.inner_logger, "{} {} {}", method, path, status.as_str());
-                res
+                let error = status.is_client_error() || status.is_server_error();
+                if !(error || debug || self.sampler.sample(sampling.rate)) {
+                    return res;
+                }
+
+                match format {
+                    Format::Compact => {
+                        let (method, path) = (&line.method, &line.path);
+                        info!(self.inner_logger, "{} {} {}", method, path, status.as_str());
+                        res
+                    }
+                    Format::Json => {
+                        let mut pending = PendingLine {
+                            logger: self.json_logger(),
+                            line,
+                            status,
+                            latency: start.elapsed(),
+                            bytes: 0,
+                        };
+                        let (parts, body) = res.into_parts();
+                        let body = body.map(move |chunk| {
+                            if let Ok(bytes) = &chunk {
+                                pending.bytes += bytes.len() as u64;
+                            }
+                            chunk
+                        });
+                        http::Response::from_parts(parts, Body::from_stream(body))
+                    }
+                }
             },
         ))
     }
 }
+
+/// The fields of a log line known before the request is handled.
+struct JsonLine {
+    timestamp: String,
+    method: String,
+    path: String,
+    route: Option<String>,
+    client_ip: Option<String>,
+    request_id: Option<String>,
+}
+
+impl JsonLine {
+    fn new(req: &Request) -> JsonLine {
+        JsonLine {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            method: req.method().as_str().to_owned(),
+            path: req.uri().path().to_owned(),
+            route: req
+                .extensions()
+                .get::<RoutePattern>()
+                .and_then(|RoutePattern(pattern)| pattern.clone()),
+            client_ip: req
+                .extensions()
+                .get::<ConnectionInfo>()
+                .and_then(ConnectionInfo::peer_addr)
+                .map(|addr| addr.ip().to_string()),
+            request_id: req
+                .headers()
+                .get("X-Request-Id")
+                .and_then(|id| id.to_str().ok())
+                .map(ToOwned::to_owned),
+        }
+    }
+
+    fn to_json(&self, status: http::StatusCode, latency: Duration, bytes: u64) -> String {
+        let latency_ms = latency.as_secs() as f64 * 1e3 + f64::from(latency.subsec_nanos()) / 1e6;
+        let line = serde_json::json!({
+            "timestamp": self.timestamp,
+            "method": self.method,
+            "path": self.path,
+            "route": self.route,
+            "status": status.as_u16(),
+            "latency_ms": latency_ms,
+            "bytes": bytes,
+            "client_ip": self.client_ip,
+            "request_id": self.request_id,
+        });
+        line.to_string()
+    }
+}
+
+/// A JSON line whose response body is still being sent; written when dropped.
+struct PendingLine {
+    logger: slog::Logger,
+    line: JsonLine,
+    status: http::StatusCode,
+    latency: Duration,
+    bytes: u64,
+}
+
+impl Drop for PendingLine {
+    fn drop(&mut self) {
+        let line = self.line.to_json(self.status, self.latency, self.bytes);
+        info!(self.logger, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_line() {
+        let mut req = http::Request::get("/users/42")
+            .header("X-Request-Id", "abc")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(RoutePattern(Some("/users/{id}".to_owned())));
+        let peer = "192.0.2.7:4711".parse().unwrap();
+        req.extensions_mut()
+            .insert(ConnectionInfo::new(Some(peer), None));
+
+        let line = JsonLine::new(&req).to_json(http::StatusCode::OK, Duration::from_millis(3), 12);
+        let line: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(line["method"], "GET");
+        assert_eq!(line["path"], "/users/42");
+        assert_eq!(line["route"], "/users/{id}");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["latency_ms"], 3.0);
+        assert_eq!(line["bytes"], 12);
+        assert_eq!(line["client_ip"], "192.0.2.7");
+        assert_eq!(line["request_id"], "abc");
+    }
+
+    #[test]
+    fn forwarded_client_ip() {
+        let mut req = http::Request::get("/")
+            .header("X-Forwarded-For", "203.0.113.9")
+            .body(Body::empty())
+            .unwrap();
+        let proxy = "10.0.0.1:4711".parse().unwrap();
+        req.extensions_mut()
+            .insert(ConnectionInfo::new(Some(proxy), None));
+        let trusted = [crate::forwarded::IpRange::parse("10.0.0.0/8").unwrap()];
+        crate::forwarded::apply(&mut req, &trusted);
+
+        let line = JsonLine::new(&req).to_json(http::StatusCode::OK, Duration::from_millis(0), 0);
+        let line: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(line["client_ip"], "203.0.113.9");
+    }
+}
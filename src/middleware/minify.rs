@@ -0,0 +1,286 @@
+use futures::{future::FutureObj, prelude::*};
+use http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+use http_service::Body;
+
+use crate::{middleware::RequestContext, Middleware, Response};
+
+/// Middleware stripping insignificant whitespace from HTML and JSON responses.
+///
+/// JSON bodies are compacted exactly. In HTML, runs of whitespace are collapsed into a single
+/// space or newline, except within `<pre>`, `<textarea>`, `<script>` and `<style>` elements,
+/// comments and quoted attribute values. Bodies are transformed as they are streamed, without
+/// buffering, and compressed responses are left untouched.
+///
+/// Minified output is hard to read, so it can be turned off in debug builds:
+///
+/// ```rust, no_run
+/// use tide::middleware::Minify;
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(Minify::new().debug(cfg!(debug_assertions)));
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct Minify {
+    debug: bool,
+}
+
+impl Minify {
+    /// Create the middleware.
+    pub fn new() -> Minify {
+        Minify::default()
+    }
+
+    /// Leave responses untouched if `debug` is set.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+}
+
+/// A streaming minifier, fed with consecutive chunks of a body.
+trait Minifier: Send + 'static {
+    fn push(&mut self, input: &[u8], out: &mut Vec<u8>);
+}
+
+impl<Data: Clone + Send> Middleware<Data> for Minify {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let res = await!(ctx.next());
+                if self.debug || res.headers().contains_key(CONTENT_ENCODING) {
+                    return res;
+                }
+                let content_type = res
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.split(';').next())
+                    .map(|value| value.trim().to_ascii_lowercase())
+                    .unwrap_or_default();
+                if content_type == "text/html" {
+                    minify(res, HtmlMinifier::default())
+                } else if content_type == "application/json" || content_type.ends_with("+json") {
+                    minify(res, JsonMinifier::default())
+                } else {
+                    res
+                }
+            },
+        ))
+    }
+}
+
+fn minify(res: Response, mut minifier: impl Minifier) -> Response {
+    let (mut parts, body) = res.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = body.map(move |chunk| {
+        chunk.map(|chunk| {
+            let mut out = Vec::with_capacity(chunk.len());
+            minifier.push(&chunk, &mut out);
+            bytes::Bytes::from(out)
+        })
+    });
+    http::Response::from_parts(parts, Body::from_stream(body))
+}
+
+/// Removes all whitespace outside of strings.
+#[derive(Default)]
+struct JsonMinifier {
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Minifier for JsonMinifier {
+    fn push(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        for &b in input {
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                out.push(b);
+            } else if b == b'"' {
+                self.in_string = true;
+                out.push(b);
+            } else if !b.is_ascii_whitespace() {
+                out.push(b);
+            }
+        }
+    }
+}
+
+/// Elements whose content is passed through verbatim.
+const RAW_ELEMENTS: &[&[u8]] = &[b"pre", b"textarea", b"script", b"style"];
+
+/// Collapses whitespace in HTML outside of raw elements, comments and attribute values.
+#[derive(Default)]
+struct HtmlMinifier {
+    /// Pending whitespace: a newline if the run contained one, a space otherwise.
+    whitespace: Option<u8>,
+    /// The (lowercased) name of the tag being read, right after a `<`.
+    tag: Option<Vec<u8>>,
+    /// Whether we are between the name and the `>` of a tag.
+    in_tag: bool,
+    /// The quote of the attribute value being read.
+    quote: Option<u8>,
+    /// The raw element we are in.
+    raw: Option<&'static [u8]>,
+    /// Whether we are in a comment, and the number of consecutive `-` just read.
+    comment: Option<usize>,
+}
+
+impl HtmlMinifier {
+    /// Handle the end of a tag name.
+    fn end_tag_name(&mut self, name: Vec<u8>) {
+        if name.starts_with(b"!--") {
+            self.comment = Some(0);
+            return;
+        }
+        match self.raw {
+            Some(raw) => {
+                if name.len() == raw.len() + 1 && name[0] == b'/' && &name[1..] == raw {
+                    self.raw = None;
+                    self.in_tag = true;
+                }
+            }
+            None => {
+                let element = if name.starts_with(b"/") { &name[1..] } else { &name[..] };
+                self.in_tag = !element.is_empty();
+                if !name.starts_with(b"/") {
+                    self.raw = RAW_ELEMENTS.iter().cloned().find(|raw| *raw == element);
+                }
+            }
+        }
+    }
+}
+
+impl Minifier for HtmlMinifier {
+    fn push(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        for &b in input {
+            if let Some(dashes) = self.comment {
+                out.push(b);
+                self.comment = match b {
+                    b'-' => Some(dashes + 1),
+                    b'>' if dashes >= 2 => None,
+                    _ => Some(0),
+                };
+                continue;
+            }
+
+            if let Some(mut name) = self.tag.take() {
+                let first = name.is_empty();
+                let name_char = b.is_ascii_alphanumeric()
+                    || (first && (b == b'/' || b == b'!'))
+                    || (b == b'-' && name.starts_with(b"!"));
+                if name_char {
+                    name.push(b.to_ascii_lowercase());
+                    out.push(b);
+                    if name == b"!--" {
+                        self.end_tag_name(name);
+                    } else {
+                        self.tag = Some(name);
+                    }
+                    continue;
+                }
+                self.end_tag_name(name);
+                if self.comment.is_some() {
+                    out.push(b);
+                    continue;
+                }
+            }
+
+            if let Some(quote) = self.quote {
+                out.push(b);
+                if b == quote {
+                    self.quote = None;
+                }
+                continue;
+            }
+
+            if self.raw.is_some() && !self.in_tag {
+                out.push(b);
+                if b == b'<' {
+                    self.tag = Some(Vec::new());
+                }
+                continue;
+            }
+
+            if b.is_ascii_whitespace() {
+                let newline = b == b'\n' || self.whitespace == Some(b'\n');
+                self.whitespace = Some(if newline { b'\n' } else { b' ' });
+                continue;
+            }
+            if let Some(whitespace) = self.whitespace.take() {
+                out.push(whitespace);
+            }
+            out.push(b);
+            match b {
+                b'<' if !self.in_tag => self.tag = Some(Vec::new()),
+                b'>' if self.in_tag => self.in_tag = false,
+                b'"' | b'\'' if self.in_tag => self.quote = Some(b),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(mut minifier: impl Minifier, chunks: &[&str]) -> String {
+        let mut out = Vec::new();
+        for chunk in chunks {
+            minifier.push(chunk.as_bytes(), &mut out);
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json() {
+        let json = "{\n  \"a b\": [1, 2],\n  \"c\": \"\\\" x \"\n}";
+        assert_eq!(
+            run(JsonMinifier::default(), &[json]),
+            r#"{"a b":[1,2],"c":"\" x "}"#
+        );
+        assert_eq!(
+            run(JsonMinifier::default(), &["{ \"a ", " b\" :", " 1 }"]),
+            r#"{"a  b":1}"#
+        );
+    }
+
+    #[test]
+    fn html() {
+        let html = concat!(
+            "<html>\n  <body   class=\"a   b\">\n",
+            "    <p>Hello,   world</p>\n  </body>\n</html>",
+        );
+        assert_eq!(
+            run(HtmlMinifier::default(), &[html]),
+            "<html>\n<body class=\"a   b\">\n<p>Hello, world</p>\n</body>\n</html>"
+        );
+    }
+
+    #[test]
+    fn html_raw_elements() {
+        let html = concat!(
+            "<div>  <pre>  a\n   b </pre>  ",
+            "<!--  c  -->  <SCRIPT> x  =  1 </script> </div>",
+        );
+        assert_eq!(
+            run(HtmlMinifier::default(), &[html]),
+            "<div> <pre>  a\n   b </pre> <!--  c  --> <SCRIPT> x  =  1 </script> </div>"
+        );
+    }
+
+    #[test]
+    fn html_across_chunks() {
+        let chunks = ["<p>a  ", "  b</p><pr", "e>  c  </p", "re>  d"];
+        assert_eq!(
+            run(HtmlMinifier::default(), &chunks),
+            "<p>a b</p><pre>  c  </pre> d"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use futures::future::FutureObj;
+
+use crate::{
+    body,
+    middleware::RequestContext,
+    sampler::{self, Sampler},
+    Middleware, Response,
+};
+
+/// Middleware handing a copy of a sample of requests to a callback, e.g. to shadow production
+/// traffic to a new version of a service.
+///
+/// The copy carries the method, URI, version and headers of the request, as well as its body,
+/// which is buffered (see `body::buffer`) so that the endpoint can still read it. Only bodies
+/// whose `Content-Length` is announced and at most the `max_body_size` of the mirror are
+/// buffered. The primary response is never affected: requests with larger bodies, bodies of
+/// unknown length or bodies that cannot be read are simply not mirrored.
+///
+/// The callback runs on the request path, so it should only hand the copy off, for instance over a
+/// channel to a task forwarding it to the secondary upstream:
+///
+/// ```rust, no_run
+/// use std::sync::{mpsc, Mutex};
+/// use tide::middleware::Mirror;
+///
+/// let (tx, rx) = mpsc::channel();
+/// let tx = Mutex::new(tx);
+/// let mut app = tide::App::new(());
+/// app.middleware(
+///     Mirror::new(move |req| {
+///         let _ = tx.lock().unwrap().send(req);
+///     })
+///     .sample(0.1),
+/// );
+/// // Forward the requests received on `rx` to the shadow deployment.
+/// ```
+pub struct Mirror<F> {
+    callback: F,
+    rate: f64,
+    sampler: Sampler,
+    max_body_size: u64,
+}
+
+/// The largest body a mirror buffers by default, 64 KiB.
+const DEFAULT_MAX_BODY_SIZE: u64 = 64 * 1024;
+
+impl<F> Mirror<F>
+where
+    F: Fn(http::Request<bytes::Bytes>) + Send + Sync,
+{
+    /// Create the middleware, mirroring every request to `callback`.
+    pub fn new(callback: F) -> Mirror<F> {
+        Mirror {
+            callback,
+            rate: 1.0,
+            sampler: Sampler::default(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Only mirror the given fraction (between `0.0` and `1.0`) of requests, spread evenly.
+    ///
+    /// Rates outside that range are clamped to it.
+    pub fn sample(mut self, rate: f64) -> Self {
+        self.rate = sampler::clamp_rate(rate);
+        self
+    }
+
+    /// Only mirror requests whose body is announced to be at most `bytes` long, 64 KiB by
+    /// default.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+}
+
+/// The length of the body of `req`, if it is known before reading it.
+fn body_length(req: &crate::Request) -> Option<u64> {
+    let headers = req.headers();
+    match headers.get(http::header::CONTENT_LENGTH) {
+        Some(len) => len.to_str().ok()?.parse().ok(),
+        // An HTTP/1 request announcing neither has no body.
+        None if req.version() < http::Version::HTTP_2
+            && !headers.contains_key(http::header::TRANSFER_ENCODING) =>
+        {
+            Some(0)
+        }
+        None => None,
+    }
+}
+
+impl<Data, F> Middleware<Data> for Mirror<F>
+where
+    Data: Clone + Send,
+    F: Fn(http::Request<bytes::Bytes>) + Send + Sync,
+{
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let mirrored = self.sampler.sample(self.rate)
+                    && (body::buffered(&ctx.req).is_some()
+                        || body_length(&ctx.req).map_or(false, |len| len <= self.max_body_size));
+                let body = if mirrored {
+                    await!(body::buffer(&mut ctx.req)).ok()
+                } else {
+                    None
+                };
+                if let Some(body) = body {
+                    let mut copy = http::Request::new(body);
+                    *copy.method_mut() = ctx.req.method().clone();
+                    *copy.uri_mut() = ctx.req.uri().clone();
+                    *copy.version_mut() = ctx.req.version();
+                    *copy.headers_mut() = ctx.req.headers().clone();
+                    (self.callback)(copy);
+                }
+                await!(ctx.next())
+            },
+        ))
+    }
+}
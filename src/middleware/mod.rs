@@ -1,15 +1,45 @@
 use std::any::Any;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use futures::future::FutureObj;
+use futures::future::{self, FutureObj};
 
-use crate::{configuration::Store, router::EndpointData, Request, Response, RouteMatch};
+use crate::{
+    configuration::Store,
+    router::{EndpointData, RouteMeta},
+    Request, Response, RouteMatch,
+};
 
+mod buffer_body;
+mod cache_control;
+mod coalesce;
+mod csp;
 mod default_headers;
+mod deprecation;
+pub mod idempotency;
 pub mod logger;
+mod minify;
+mod mirror;
+mod problem_details;
+mod recorder;
+pub(crate) mod timing;
+mod when;
 
-pub use self::default_headers::DefaultHeaders;
+pub use self::{
+    buffer_body::BufferBody,
+    cache_control::CacheControl,
+    coalesce::Coalesce,
+    csp::{ContentSecurityPolicy, CspNonce},
+    default_headers::DefaultHeaders,
+    deprecation::{Deprecation, DeprecationHeaders},
+    minify::Minify,
+    mirror::Mirror,
+    problem_details::ProblemDetails,
+    recorder::Recorder,
+    timing::{ServerTiming, Timed},
+    when::When,
+};
 
 /// Middleware that wraps around remaining middleware chain.
 pub trait Middleware<Data>: Send + Sync {
@@ -32,6 +62,12 @@ pub struct RequestContext<'a, Data> {
     pub params: Option<RouteMatch<'a>>,
     pub(crate) endpoint: &'a EndpointData<Data>,
     pub(crate) next_middleware: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
+    pub(crate) route_pattern: Option<&'a str>,
+    /// A response the server answers with itself, such as a redirect, ending the chain in place
+    /// of the endpoint.
+    pub(crate) response: Option<Response>,
+    /// When the endpoint is cancelled, answering `503 Service Unavailable` instead, if ever.
+    pub(crate) deadline: Option<Instant>,
 }
 
 impl<'a, Data: Clone + Send> RequestContext<'a, Data> {
@@ -48,18 +84,46 @@ impl<'a, Data: Clone + Send> RequestContext<'a, Data> {
         &self.endpoint.store
     }
 
+    /// Get the pattern of the resource the request was routed to, such as `/message/{id}`.
+    ///
+    /// This is the path from the root of the app as the resource was added, without wildcard
+    /// constraints. Requests handled by a fallback or the default handler have no pattern. Unlike
+    /// the request path, it is suitable for aggregating metrics and logs by route.
+    pub fn route_pattern(&self) -> Option<&'a str> {
+        self.route_pattern
+    }
+
+    /// Get the metadata and tags of the endpoint handling the request.
+    pub fn route_meta(&self) -> &'a RouteMeta {
+        &self.endpoint.meta
+    }
+
     /// Consume this context, and run remaining middleware chain to completion.
     pub fn next(mut self) -> FutureObj<'a, Response> {
         if let Some((current, next)) = self.next_middleware.split_first() {
             self.next_middleware = next;
             current.handle(self)
+        } else if let Some(res) = self.response.take() {
+            FutureObj::new(Box::new(future::ready(res)))
         } else {
-            FutureObj::new(Box::new(self.endpoint.endpoint.call(
-                self.app_data.clone(),
+            let res = FutureObj::new(Box::new(self.endpoint.endpoint.call(
+                self.app_data,
                 self.req,
                 self.params,
                 &self.endpoint.store,
-            )))
+            )));
+            match self.deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    let remaining = if deadline > now {
+                        deadline - now
+                    } else {
+                        Duration::from_secs(0)
+                    };
+                    FutureObj::new(Box::new(crate::app::within(res, remaining)))
+                }
+                None => res,
+            }
         }
     }
 }
@@ -0,0 +1,57 @@
+use futures::future::FutureObj;
+use http::header::{ACCEPT, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+
+use crate::{middleware::RequestContext, response::Problem, IntoResponse, Middleware, Response};
+
+/// Middleware turning bodyless error responses into `application/problem+json` documents.
+///
+/// Framework-generated errors, like a `404` for an unknown path or a `400` from a failed
+/// extraction, carry no body. When the client accepts `application/problem+json`, such 4xx/5xx
+/// responses without a `Content-Type` are replaced by a `Problem` with the same status. Headers
+/// of the original response are kept, except for those describing its body, such as
+/// `Content-Length`.
+#[derive(Clone, Default, Debug)]
+pub struct ProblemDetails;
+
+impl ProblemDetails {
+    /// Create the middleware.
+    pub fn new() -> ProblemDetails {
+        ProblemDetails::default()
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for ProblemDetails {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let accepts_problem = ctx
+                    .req
+                    .headers()
+                    .get_all(ACCEPT)
+                    .iter()
+                    .filter_map(|accept| accept.to_str().ok())
+                    .any(|accept| accept.contains("application/problem+json"));
+
+                let res = await!(ctx.next());
+                let status = res.status();
+                if !accepts_problem
+                    || !(status.is_client_error() || status.is_server_error())
+                    || res.headers().contains_key(CONTENT_TYPE)
+                {
+                    return res;
+                }
+
+                let mut problem = Problem::new(status).into_response();
+                let describes_body = |key: &http::header::HeaderName| {
+                    key == CONTENT_LENGTH || key == CONTENT_ENCODING || key == TRANSFER_ENCODING
+                };
+                for (key, value) in res.headers().iter() {
+                    if !describes_body(key) {
+                        problem.headers_mut().append(key, value.clone());
+                    }
+                }
+                problem
+            },
+        ))
+    }
+}
@@ -0,0 +1,416 @@
+use futures::{
+    future::{self, FutureObj},
+    prelude::*,
+};
+use http::header::{HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE};
+use http_service::Body;
+use serde_derive::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{body, middleware::RequestContext, Middleware, Request, Response};
+
+/// Middleware recording the most recent requests and responses, for export as a HAR file.
+///
+/// Recording is meant for debugging hard-to-reproduce client issues, and opt-in: request and
+/// response bodies are copied as they are streamed through, without buffering them. Only the
+/// first `max_body_size` bytes of each body are kept, and only the last `max_entries`
+/// exchanges; the rest of a body is counted but not copied. An entry is recorded once its
+/// response body has been sent, with as much of the request body as the app read.
+///
+/// The values of credential headers, `Authorization`, `Proxy-Authorization`, `Cookie` and
+/// `Set-Cookie`, are recorded as `[redacted]`, as is any header added with `redact`, since the
+/// recording is served to whoever can reach its endpoint.
+///
+/// The recorder is a shared handle, so a clone of it can serve the recording:
+///
+/// ```rust, no_run
+/// use tide::middleware::Recorder;
+///
+/// let recorder = Recorder::new().max_entries(50);
+/// let mut app = tide::App::new(());
+/// app.middleware(recorder.clone());
+/// app.at("/admin/requests.har").get(recorder.endpoint());
+/// ```
+#[derive(Clone)]
+pub struct Recorder {
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+    max_entries: usize,
+    max_body_size: usize,
+    redacted: Vec<HeaderName>,
+}
+
+/// The value recorded in place of that of a redacted header.
+const REDACTED: &str = "[redacted]";
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Recorder {
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            max_entries: 100,
+            max_body_size: 64 * 1024,
+            redacted: vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE],
+        }
+    }
+}
+
+impl Recorder {
+    /// Create a recorder keeping the last 100 exchanges, with bodies of up to 64 KiB.
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// Set the number of exchanges to keep.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Set the number of bytes to keep of each request and response body.
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Also record the value of the header `name` as `[redacted]`.
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn redact(mut self, name: &str) -> Self {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("Invalid header name `{}`", name));
+        self.redacted.push(name);
+        self
+    }
+
+    /// Record the values of all headers as they are, credentials included.
+    pub fn record_all_headers(mut self) -> Self {
+        self.redacted.clear();
+        self
+    }
+
+    /// Clear the recording.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Export the recording as HAR 1.2 JSON.
+    pub fn to_har(&self) -> String {
+        let entries = self.entries.lock().unwrap();
+        let har = Har {
+            log: Log {
+                version: "1.2",
+                creator: Creator {
+                    name: "tide",
+                    version: env!("CARGO_PKG_VERSION"),
+                },
+                entries: entries.iter().collect(),
+            },
+        };
+        serde_json::to_string(&har).unwrap()
+    }
+
+    /// An endpoint serving the recording as a HAR file download.
+    pub fn endpoint(
+        &self,
+    ) -> impl Fn() -> future::Ready<Response> + Clone + Send + Sync + 'static {
+        let recorder = self.clone();
+        move || {
+            let res = http::Response::builder()
+                .header("Content-Type", "application/json")
+                .header("Content-Disposition", "attachment; filename=\"tide.har\"")
+                .body(Body::from(recorder.to_har()))
+                .unwrap();
+            future::ready(res)
+        }
+    }
+
+    fn record(&self, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push_back(entry);
+        while entries.len() > self.max_entries {
+            entries.pop_front();
+        }
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for Recorder {
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let started_date_time = chrono::Utc::now().to_rfc3339();
+                let start = Instant::now();
+                let request = HarRequest::new(&ctx.req, &self.redacted);
+                let max_body_size = self.max_body_size;
+                let mut request_body = Captured::default();
+                if let Some(bytes) = body::buffered(&ctx.req) {
+                    request_body.capture(bytes, max_body_size);
+                }
+                let request_body = Arc::new(Mutex::new(request_body));
+                let tee = request_body.clone();
+                let req_body = std::mem::replace(ctx.req.body_mut(), Body::empty());
+                let req_body = req_body.map(move |chunk| {
+                    if let Ok(bytes) = &chunk {
+                        tee.lock().unwrap().capture(bytes, max_body_size);
+                    }
+                    chunk
+                });
+                *ctx.req.body_mut() = Body::from_stream(req_body);
+
+                let res = await!(ctx.next());
+                let (parts, res_body) = res.into_parts();
+                let mut pending = PendingEntry {
+                    recorder: self.clone(),
+                    started_date_time,
+                    start,
+                    wait: start.elapsed(),
+                    request: Some(request),
+                    request_body,
+                    status: parts.status,
+                    version: parts.version,
+                    headers: parts.headers.clone(),
+                    body: Captured::default(),
+                };
+                let res_body = res_body.map(move |chunk| {
+                    if let Ok(bytes) = &chunk {
+                        pending.body.capture(bytes, max_body_size);
+                    }
+                    chunk
+                });
+                http::Response::from_parts(parts, Body::from_stream(res_body))
+            },
+        ))
+    }
+}
+
+/// An exchange whose response body is still being sent; recorded when dropped.
+struct PendingEntry {
+    recorder: Recorder,
+    started_date_time: String,
+    start: Instant,
+    wait: Duration,
+    request: Option<HarRequest>,
+    request_body: Arc<Mutex<Captured>>,
+    status: http::StatusCode,
+    version: http::Version,
+    headers: http::HeaderMap,
+    body: Captured,
+}
+
+/// The start of a body streamed through the recorder, and its full size.
+#[derive(Default)]
+struct Captured {
+    body: Vec<u8>,
+    size: usize,
+}
+
+impl Captured {
+    fn capture(&mut self, bytes: &[u8], max_body_size: usize) {
+        let keep = max_body_size.saturating_sub(self.body.len());
+        self.body.extend_from_slice(&bytes[..keep.min(bytes.len())]);
+        self.size += bytes.len();
+    }
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        let request = match self.request.take() {
+            Some(request) => request.with_body(&self.request_body.lock().unwrap()),
+            None => return,
+        };
+        let total = millis(self.start.elapsed());
+        let wait = millis(self.wait);
+        let entry = Entry {
+            started_date_time: std::mem::replace(&mut self.started_date_time, String::new()),
+            time: total,
+            request,
+            response: HarResponse {
+                status: self.status.as_u16(),
+                status_text: self.status.canonical_reason().unwrap_or(""),
+                http_version: format!("{:?}", self.version),
+                cookies: Vec::new(),
+                headers: headers(&self.headers, &self.recorder.redacted),
+                content: Content {
+                    size: self.body.size,
+                    mime_type: content_type(&self.headers),
+                    text: String::from_utf8_lossy(&self.body.body).into_owned(),
+                },
+                redirect_url: self
+                    .headers
+                    .get(http::header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or("")
+                    .to_owned(),
+                headers_size: -1,
+                body_size: self.body.size as i64,
+            },
+            cache: Cache {},
+            timings: Timings {
+                send: 0.0,
+                wait,
+                receive: total - wait,
+            },
+        };
+        self.recorder.record(entry);
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e3 + f64::from(duration.subsec_nanos()) / 1e6
+}
+
+fn headers(headers: &http::HeaderMap, redacted: &[HeaderName]) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.as_str().to_owned(),
+            value: if redacted.contains(name) {
+                REDACTED.to_owned()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            },
+        })
+        .collect()
+}
+
+fn content_type(headers: &http::HeaderMap) -> String {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_owned()
+}
+
+#[derive(Serialize)]
+struct Har<'a> {
+    log: Log<'a>,
+}
+
+#[derive(Serialize)]
+struct Log<'a> {
+    version: &'static str,
+    creator: Creator,
+    entries: Vec<&'a Entry>,
+}
+
+#[derive(Serialize)]
+struct Creator {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    cache: Cache,
+    timings: Timings,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<PostData>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+impl HarRequest {
+    fn new(req: &Request, redacted: &[HeaderName]) -> HarRequest {
+        let query_string = req
+            .uri()
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                NameValue {
+                    name: pair.next().unwrap_or("").to_owned(),
+                    value: pair.next().unwrap_or("").to_owned(),
+                }
+            })
+            .collect();
+        let post_data = Some(PostData {
+            mime_type: content_type(req.headers()),
+            text: String::new(),
+        });
+        HarRequest {
+            method: req.method().as_str().to_owned(),
+            url: req.uri().to_string(),
+            http_version: format!("{:?}", req.version()),
+            cookies: Vec::new(),
+            headers: headers(req.headers(), redacted),
+            query_string,
+            post_data,
+            headers_size: -1,
+            body_size: 0,
+        }
+    }
+
+    /// Fill in the body, once the app is done reading it.
+    fn with_body(mut self, body: &Captured) -> HarRequest {
+        self.body_size = body.size as i64;
+        self.post_data = self.post_data.filter(|_| body.size > 0).map(|post_data| PostData {
+            text: String::from_utf8_lossy(&body.body).into_owned(),
+            ..post_data
+        });
+        self
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u16,
+    status_text: &'static str,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: usize,
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct Cache {}
+
+#[derive(Serialize)]
+struct Timings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
@@ -0,0 +1,127 @@
+use futures::future::FutureObj;
+use http::header::HeaderValue;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::{middleware::RequestContext, Middleware, Request, Response};
+
+/// Durations of the phases a request went through, kept in the response's `extensions`.
+#[derive(Default)]
+struct Timings(Vec<(Cow<'static, str>, Duration)>);
+
+/// The time it took to route a request, kept in the request's `extensions`.
+pub(crate) struct RouteTiming(pub(crate) Duration);
+
+/// Marks a request as served under `ServerTiming`, kept in the request's `extensions`, so that
+/// phases are only timed when they are reported.
+struct Enabled;
+
+/// Whether the phases of serving `req` are reported, i.e. worth recording.
+pub(crate) fn enabled(req: &Request) -> bool {
+    req.extensions().get::<Enabled>().is_some()
+}
+
+/// Record that the phase `name` took `duration` to produce `res`.
+pub(crate) fn record(res: &mut Response, name: impl Into<Cow<'static, str>>, duration: Duration) {
+    if res.extensions().get::<Timings>().is_none() {
+        res.extensions_mut().insert(Timings::default());
+    }
+    let timings = res.extensions_mut().get_mut::<Timings>().unwrap();
+    timings.0.push((name.into(), duration));
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e3 + f64::from(duration.subsec_nanos()) / 1e6
+}
+
+/// Middleware reporting where the time to serve a request was spent in a `Server-Timing` header.
+///
+/// The header lists the time spent on routing (`route`), on running the extractors (`extract`),
+/// in the endpoint itself (`endpoint`), in every middleware wrapped in `Timed`, and in total
+/// (`total`), measured from the point this middleware runs. Durations of `Timed` middleware
+/// include everything they wrap.
+///
+/// ```rust, no_run
+/// # #![feature(futures_api)]
+/// # use futures::future::FutureObj;
+/// # use tide::{middleware::RequestContext, Response};
+/// # fn auth(ctx: RequestContext<()>) -> FutureObj<Response> { ctx.next() }
+/// use tide::middleware::{ServerTiming, Timed};
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(ServerTiming::new());
+/// app.middleware(Timed::new("auth", auth));
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct ServerTiming;
+
+impl ServerTiming {
+    /// Create the middleware.
+    pub fn new() -> ServerTiming {
+        ServerTiming::default()
+    }
+}
+
+impl<Data: Clone + Send> Middleware<Data> for ServerTiming {
+    fn handle<'a>(&'a self, mut ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                let route = ctx.req.extensions().get::<RouteTiming>().map(|t| t.0);
+                ctx.req.extensions_mut().insert(Enabled);
+                let start = Instant::now();
+
+                let mut res = await!(ctx.next());
+                let total = start.elapsed();
+
+                let mut header = String::new();
+                if let Some(route) = route {
+                    write!(header, "route;dur={:.3}, ", millis(route)).unwrap();
+                }
+                if let Some(timings) = res.extensions_mut().remove::<Timings>() {
+                    for (name, duration) in timings.0 {
+                        write!(header, "{};dur={:.3}, ", name, millis(duration)).unwrap();
+                    }
+                }
+                write!(header, "total;dur={:.3}", millis(total)).unwrap();
+
+                if let Ok(value) = HeaderValue::from_str(&header) {
+                    res.headers_mut().append("Server-Timing", value);
+                }
+                res
+            },
+        ))
+    }
+}
+
+/// A wrapper recording the time spent in the wrapped middleware for `ServerTiming`.
+pub struct Timed<M> {
+    name: Cow<'static, str>,
+    inner: M,
+}
+
+impl<M> Timed<M> {
+    /// Wrap `inner`, reporting its duration under `name`.
+    pub fn new(name: impl Into<Cow<'static, str>>, inner: M) -> Timed<M> {
+        Timed {
+            name: name.into(),
+            inner,
+        }
+    }
+}
+
+impl<Data: Clone + Send, M: Middleware<Data>> Middleware<Data> for Timed<M> {
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        FutureObj::new(Box::new(
+            async move {
+                if !enabled(&ctx.req) {
+                    return await!(self.inner.handle(ctx));
+                }
+                let start = Instant::now();
+                let mut res = await!(self.inner.handle(ctx));
+                record(&mut res, self.name.clone(), start.elapsed());
+                res
+            },
+        ))
+    }
+}
@@ -0,0 +1,39 @@
+use futures::future::FutureObj;
+
+use crate::{middleware::RequestContext, Middleware, Request, Response};
+
+/// Middleware applying the wrapped middleware only to requests matching a predicate.
+///
+/// Other requests skip straight to the rest of the middleware chain. This way an expensive
+/// middleware can be bypassed for e.g. health checks or CORS preflight requests without
+/// restructuring the router:
+///
+/// ```rust, no_run
+/// # #![feature(futures_api)]
+/// # use futures::future::FutureObj;
+/// # use tide::{middleware::RequestContext, Response};
+/// # fn auth(ctx: RequestContext<()>) -> FutureObj<Response> { ctx.next() }
+/// use tide::middleware::When;
+///
+/// let mut app = tide::App::new(());
+/// app.middleware(When(
+///     |req: &tide::Request| req.method() != http::Method::OPTIONS && req.uri().path() != "/healthz",
+///     auth,
+/// ));
+/// ```
+pub struct When<P, M>(pub P, pub M);
+
+impl<Data, P, M> Middleware<Data> for When<P, M>
+where
+    Data: Clone + Send,
+    P: Fn(&Request) -> bool + Send + Sync,
+    M: Middleware<Data>,
+{
+    fn handle<'a>(&'a self, ctx: RequestContext<'a, Data>) -> FutureObj<'a, Response> {
+        if (self.0)(&ctx.req) {
+            self.1.handle(ctx)
+        } else {
+            ctx.next()
+        }
+    }
+}
@@ -1,14 +1,67 @@
 use futures::future;
 use http_service::Body;
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
+use std::time::Instant;
 
-use crate::{configuration::Store, Extract, Response, RouteMatch};
+use crate::{configuration::Store, Extract, IntoResponse, Response, RouteMatch};
 
 /// An HTTP request.
 ///
 /// A convenient alias for the `http::Request` type, using Tide's `Body`.
 pub type Request = http::Request<Body>;
 
+/// The time a request was received at, kept in its `extensions`.
+pub(crate) struct ReceivedAt(pub(crate) Instant);
+
+/// An extractor for the addresses of the connection a request arrived on.
+///
+/// The addresses are known when the app is run with `App::serve`; other hosts of the app's
+/// `HttpService` (such as mock servers in tests) leave them unset. With
+/// `ConfigurationBuilder::proxy_protocol`, these are the addresses the PROXY protocol header
+/// reports, i.e. those of the original client connection rather than of the load balancer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionInfo {
+    peer_addr: Option<SocketAddr>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl ConnectionInfo {
+    pub(crate) fn new(peer_addr: Option<SocketAddr>, local_addr: Option<SocketAddr>) -> Self {
+        ConnectionInfo {
+            peer_addr,
+            local_addr,
+        }
+    }
+
+    /// The address of the client.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// The address the client connected to.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+impl<Data: 'static> Extract<Data> for ConnectionInfo {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        future::ok(
+            req.extensions()
+                .get::<ConnectionInfo>()
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+}
+
 /// A value that can be computed on-demand from a request.
 pub trait Compute: 'static + Sync + Send + Clone + Sized {
     /// Compute the value directly from the given request.
@@ -63,3 +116,65 @@ impl<Data: 'static, T: Compute> Extract<Data> for Computed<T> {
         future::ok(Computed(T::compute(req)))
     }
 }
+
+/// An extractor for values injected into the request by middleware.
+///
+/// Middleware can hand data to endpoints (an authenticated user, a parsed tenant, ...) by
+/// inserting it into the request's `extensions`:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// use futures::future::FutureObj;
+/// use tide::{middleware::RequestContext, FromExtension, Response};
+///
+/// #[derive(Clone)]
+/// struct User(String);
+///
+/// fn authenticate(mut ctx: RequestContext<()>) -> FutureObj<Response> {
+///     ctx.req.extensions_mut().insert(User("alice".to_string()));
+///     ctx.next()
+/// }
+///
+/// async fn whoami(FromExtension(user): FromExtension<User>) -> String {
+///     user.0
+/// }
+///
+/// fn main() {
+///     let mut app = tide::App::new(());
+///     app.middleware(authenticate);
+///     app.at("/whoami").get(whoami);
+///     app.serve().unwrap()
+/// }
+/// ```
+///
+/// Fails with an `INTERNAL_SERVER_ERROR` response if no value of type `T` has been inserted.
+#[derive(Clone)]
+pub struct FromExtension<T>(pub T);
+
+impl<T> Deref for FromExtension<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for FromExtension<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<Data: 'static, T: Clone + Send + Sync + 'static> Extract<Data> for FromExtension<T> {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        match req.extensions().get::<T>() {
+            Some(t) => future::ok(FromExtension(t.clone())),
+            None => future::err(http::status::StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        }
+    }
+}
@@ -1,4 +1,11 @@
+//! Types for building responses.
+//!
+//! Endpoints can return any value implementing `IntoResponse`, and wrappers like `WithStatus`
+//! can be used to adjust the resulting response.
+
 use http_service::Body;
+use serde_derive::Serialize;
+use std::borrow::Cow;
 
 use crate::body;
 
@@ -20,17 +27,15 @@ pub trait IntoResponse: Send + Sized {
     /// assert_eq!(resp.status(), http::status::StatusCode::NOT_FOUND);
     /// ```
     fn with_status(self, status: http::status::StatusCode) -> WithStatus<Self> {
-        WithStatus {
-            inner: self,
-            status,
-        }
+        WithStatus(status, self)
     }
 }
 
+/// `()` is a success without a body, so it is turned into `204 No Content`.
 impl IntoResponse for () {
     fn into_response(self) -> Response {
         http::Response::builder()
-            .status(http::status::StatusCode::OK)
+            .status(http::status::StatusCode::NO_CONTENT)
             .body(Body::empty())
             .unwrap()
     }
@@ -46,9 +51,29 @@ impl IntoResponse for Vec<u8> {
     }
 }
 
+impl IntoResponse for &'static [u8] {
+    fn into_response(self) -> Response {
+        http::Response::builder()
+            .status(http::status::StatusCode::OK)
+            .header("Content-Type", "application/octet-stream")
+            .body(Body::from(self))
+            .unwrap()
+    }
+}
+
+impl IntoResponse for bytes::Bytes {
+    fn into_response(self) -> Response {
+        http::Response::builder()
+            .status(http::status::StatusCode::OK)
+            .header("Content-Type", "application/octet-stream")
+            .body(Body::from(self))
+            .unwrap()
+    }
+}
+
 impl IntoResponse for body::Bytes {
     fn into_response(self) -> Response {
-        self.to_vec().into_response()
+        self.0.into_response()
     }
 }
 
@@ -68,6 +93,19 @@ impl IntoResponse for &'_ str {
     }
 }
 
+impl IntoResponse for Cow<'static, str> {
+    fn into_response(self) -> Response {
+        match self {
+            Cow::Borrowed(s) => http::Response::builder()
+                .status(http::status::StatusCode::OK)
+                .header("Content-Type", "text/plain; charset=utf-8")
+                .body(Body::from(s))
+                .unwrap(),
+            Cow::Owned(s) => s.into_response(),
+        }
+    }
+}
+
 impl IntoResponse for http::status::StatusCode {
     fn into_response(self) -> Response {
         http::Response::builder()
@@ -95,26 +133,214 @@ impl<T: IntoResponse, U: IntoResponse> IntoResponse for Result<T, U> {
     }
 }
 
+/// `None` is turned into an empty `404 Not Found` response.
+impl<T: IntoResponse> IntoResponse for Option<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Some(r) => r.into_response(),
+            None => http::status::StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+}
+
 impl<T: Send + Into<Body>> IntoResponse for http::Response<T> {
     fn into_response(self) -> Response {
         self.map(Into::into)
     }
 }
 
-/// A response type that modifies the status code.
-pub struct WithStatus<R> {
-    inner: R,
-    status: http::status::StatusCode,
-}
+/// A response type that overrides the status code of the wrapped response.
+///
+/// Headers and body of the wrapped response are kept as they are.
+///
+/// ```
+/// # use tide::{body::Json, response::WithStatus, IntoResponse};
+/// let resp = WithStatus(http::StatusCode::CREATED, Json(42)).into_response();
+/// assert_eq!(resp.status(), http::StatusCode::CREATED);
+/// assert_eq!(resp.headers()["Content-Type"], "application/json");
+/// ```
+pub struct WithStatus<R>(pub http::status::StatusCode, pub R);
 
 impl<R: IntoResponse> IntoResponse for WithStatus<R> {
     fn into_response(self) -> Response {
-        let mut resp = self.inner.into_response();
-        *resp.status_mut() = self.status;
+        let mut resp = self.1.into_response();
+        *resp.status_mut() = self.0;
+        resp
+    }
+}
+
+/// A response type announcing resources the client will need next, to be pushed over HTTP/2.
+///
+/// Each resource is announced with a `Link: <path>; rel=preload; as=...` header. The server
+/// itself does not push, but HTTP/2 proxies and CDNs such as nginx (with `http2_push_preload`)
+/// push the announced resources, and other clients can start fetching them early. Over HTTP/1
+/// the headers are only hints.
+///
+/// ```
+/// # use tide::{response::Preload, IntoResponse};
+/// let resp = Preload::new("<html>...</html>")
+///     .push("/style.css")
+///     .push_as("/app", "script")
+///     .into_response();
+/// assert_eq!(resp.headers().get_all("Link").iter().count(), 2);
+/// assert_eq!(resp.headers()["Link"], "</style.css>; rel=preload; as=style");
+/// ```
+pub struct Preload<R> {
+    response: R,
+    resources: Vec<(String, Option<String>)>,
+}
+
+impl<R: IntoResponse> Preload<R> {
+    /// Wrap `response`, announcing no resources yet.
+    pub fn new(response: R) -> Preload<R> {
+        Preload {
+            response,
+            resources: Vec::new(),
+        }
+    }
+
+    /// Announce the resource at `path`, with a destination inferred from its extension.
+    ///
+    /// Stylesheets, scripts, fonts, images, JSON and text tracks are recognized. Browsers
+    /// ignore a preload without a destination, so use `push_as` for other paths.
+    pub fn push<T: Into<String>>(mut self, path: T) -> Self {
+        self.resources.push((path.into(), None));
+        self
+    }
+
+    /// Announce the resource at `path`, to be used as `destination`, such as `"script"`,
+    /// `"style"`, `"font"`, `"image"` or `"fetch"`.
+    pub fn push_as<T: Into<String>, D: Into<String>>(mut self, path: T, destination: D) -> Self {
+        self.resources.push((path.into(), Some(destination.into())));
+        self
+    }
+}
+
+/// The preload destination of the resource at `path`, according to its extension.
+fn preload_destination(path: &str) -> Option<&'static str> {
+    let path = path.split(|c| c == '?' || c == '#').next().unwrap_or(path);
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let extension = file.rsplit('.').next().filter(|extension| *extension != file)?;
+    let destination = match &*extension.to_ascii_lowercase() {
+        "css" => "style",
+        "js" | "mjs" => "script",
+        "woff" | "woff2" | "ttf" | "otf" | "eot" => "font",
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico" => "image",
+        "json" => "fetch",
+        "vtt" => "track",
+        _ => return None,
+    };
+    Some(destination)
+}
+
+impl<R: IntoResponse> IntoResponse for Preload<R> {
+    fn into_response(self) -> Response {
+        let mut resp = self.response.into_response();
+        for (path, destination) in self.resources {
+            let inferred = || preload_destination(&path).map(str::to_owned);
+            let link = match destination.or_else(inferred) {
+                // Fonts and fetches are always requested in CORS mode, and a preload only
+                // serves a request made in the same mode.
+                Some(destination) if destination == "font" || destination == "fetch" => {
+                    format!("<{}>; rel=preload; as={}; crossorigin", path, destination)
+                }
+                Some(destination) => format!("<{}>; rel=preload; as={}", path, destination),
+                None => format!("<{}>; rel=preload", path),
+            };
+            if let Ok(value) = http::header::HeaderValue::from_str(&link) {
+                resp.headers_mut().append(http::header::LINK, value);
+            }
+        }
         resp
     }
 }
 
+/// An RFC 7807 problem details object, served as `application/problem+json`.
+///
+/// ```
+/// # use tide::{response::Problem, IntoResponse};
+/// let resp = Problem::new(http::StatusCode::FORBIDDEN)
+///     .problem_type("https://example.com/probs/out-of-credit")
+///     .detail("Your current balance is 30, but that costs 50.")
+///     .extension("balance", 30)
+///     .into_response();
+/// assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+/// assert_eq!(resp.headers()["Content-Type"], "application/problem+json");
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    problem_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+    #[serde(flatten)]
+    extensions: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Problem {
+    /// Create a new problem with the given status, titled with the status' canonical reason.
+    pub fn new(status: http::status::StatusCode) -> Problem {
+        Problem {
+            problem_type: None,
+            title: status.canonical_reason().map(ToOwned::to_owned),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            extensions: serde_json::Map::new(),
+        }
+    }
+
+    /// Set the URI identifying the problem type.
+    pub fn problem_type<T: Into<String>>(mut self, problem_type: T) -> Self {
+        self.problem_type = Some(problem_type.into());
+        self
+    }
+
+    /// Set the short, human-readable summary of the problem type.
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Set the human-readable explanation specific to this occurrence of the problem.
+    pub fn detail<T: Into<String>>(mut self, detail: T) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Set the URI identifying this occurrence of the problem.
+    pub fn instance<T: Into<String>>(mut self, instance: T) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Add an extension member to the problem.
+    ///
+    /// Panics if `value` cannot be serialized to JSON.
+    pub fn extension<K: Into<String>, V: serde::Serialize>(mut self, key: K, value: V) -> Self {
+        let value = serde_json::to_value(value).expect("Cannot serialize problem extension");
+        self.extensions.insert(key.into(), value);
+        self
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = http::status::StatusCode::from_u16(self.status)
+            .unwrap_or(http::status::StatusCode::INTERNAL_SERVER_ERROR);
+        http::Response::builder()
+            .status(status)
+            .header("Content-Type", "application/problem+json")
+            .body(Body::from(serde_json::to_vec(&self).unwrap()))
+            .unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,12 +353,98 @@ mod tests {
         assert_eq!(resp.status(), http::status::StatusCode::NOT_FOUND);
     }
 
+    #[test]
+    fn status_keeps_headers() {
+        let resp = WithStatus(http::status::StatusCode::CREATED, String::from("foo"))
+            .into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::CREATED);
+        assert_eq!(resp.headers()["Content-Type"], "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn unit_no_content() {
+        let resp = ().into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::NO_CONTENT);
+        let resp = Ok::<(), http::status::StatusCode>(()).into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::NO_CONTENT);
+    }
+
+    #[test]
+    fn option_none_not_found() {
+        let resp = Some("foo").into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::OK);
+        let resp = None::<&str>.into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn preload_destinations() {
+        let resp = Preload::new("")
+            .push("/app.js?v=3")
+            .push("/fonts/Inter.WOFF2")
+            .push("/data")
+            .push_as("/data", "fetch")
+            .into_response();
+        let links: Vec<_> = resp.headers().get_all(http::header::LINK).iter().collect();
+        assert_eq!(
+            links,
+            [
+                "</app.js?v=3>; rel=preload; as=script",
+                "</fonts/Inter.WOFF2>; rel=preload; as=font; crossorigin",
+                "</data>; rel=preload",
+                "</data>; rel=preload; as=fetch; crossorigin",
+            ]
+        );
+    }
+
+    #[test]
+    fn problem_members() {
+        let resp = Problem::new(http::status::StatusCode::NOT_FOUND)
+            .detail("no such message")
+            .extension("id", 3)
+            .into_response();
+        assert_eq!(resp.status(), http::status::StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()["Content-Type"], "application/problem+json");
+
+        let body = futures::executor::block_on(resp.into_body().into_vec()).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "title": "Not Found",
+                "status": 404,
+                "detail": "no such message",
+                "id": 3,
+            })
+        );
+    }
+
     #[test]
     fn byte_vec_content_type() {
         let resp = String::from("foo").into_bytes().into_response();
         assert_eq!(resp.headers()["Content-Type"], "application/octet-stream");
     }
 
+    #[test]
+    fn static_bytes_content_type() {
+        let resp = (&b"foo"[..]).into_response();
+        assert_eq!(resp.headers()["Content-Type"], "application/octet-stream");
+    }
+
+    #[test]
+    fn shared_bytes_content_type() {
+        let resp = bytes::Bytes::from_static(b"foo").into_response();
+        assert_eq!(resp.headers()["Content-Type"], "application/octet-stream");
+    }
+
+    #[test]
+    fn cow_str_content_type() {
+        let resp = Cow::Borrowed("foo").into_response();
+        assert_eq!(resp.headers()["Content-Type"], "text/plain; charset=utf-8");
+        let resp = Cow::<'static, str>::Owned(String::from("foo")).into_response();
+        assert_eq!(resp.headers()["Content-Type"], "text/plain; charset=utf-8");
+    }
+
     #[test]
     fn string_content_type() {
         let resp = String::from("foo").into_response();
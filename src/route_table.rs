@@ -0,0 +1,441 @@
+//! The routing table of a router.
+//!
+//! The table is a tree with one level per path segment. At every level, concrete segments take
+//! precedence over a `{}` wildcard, which takes precedence over a `*` wildcard. Wildcards with
+//! a constraint, such as `{id:u32}`, take precedence over those without, and are only taken by
+//! segments satisfying it. If the preferred branch does not lead to a resource, or the resource
+//! declines the request (see `RouteTable::route_by`), matching backtracks to the next one, so
+//! the outcome only depends on the set of routes, never on the order they were added in.
+
+use std::collections::HashMap;
+
+use crate::{head::percent_decode, RouteMatch};
+
+/// A routing table, terminating with resources `R`.
+#[derive(Clone)]
+pub(crate) struct RouteTable<R> {
+    accept: Option<R>,
+    next: HashMap<String, RouteTable<R>>,
+    /// The branches of `{name}` wildcards, matching a single segment, by precedence.
+    wildcards: Vec<Wildcard<R>>,
+    /// The branches of `{name}*` wildcards, matching the rest of the path, by precedence.
+    catch_alls: Vec<Wildcard<R>>,
+}
+
+#[derive(Clone)]
+struct Wildcard<R> {
+    name: String,
+    constraint: Option<Constraint>,
+    table: RouteTable<R>,
+}
+
+impl<R> Wildcard<R> {
+    /// Whether the wildcard matches `segment`, checking its constraint against the
+    /// percent-decoded segment.
+    fn accepts(&self, segment: &str) -> bool {
+        self.constraint.as_ref().map_or(true, |constraint| {
+            percent_decode(segment, false).map_or(false, |segment| (constraint.check)(&segment))
+        })
+    }
+
+    /// The precedence of the wildcard among those at the same position, lowest first.
+    fn rank(&self) -> usize {
+        self.constraint.as_ref().map_or(usize::max_value(), |constraint| constraint.rank)
+    }
+}
+
+/// A check on the value of a wildcard segment, declared as `{name:constraint}`.
+#[derive(Clone)]
+struct Constraint {
+    name: String,
+    /// The precedence of the constraint, lowest first: narrower constraints are tried before
+    /// the ones they overlap with.
+    rank: usize,
+    check: fn(&str) -> bool,
+}
+
+fn parse_constraint(name: &str) -> Option<Constraint> {
+    let (rank, check): (usize, fn(&str) -> bool) = match name {
+        "u8" => (0, |segment| segment.parse::<u8>().is_ok()),
+        "i8" => (1, |segment| segment.parse::<i8>().is_ok()),
+        "u16" => (2, |segment| segment.parse::<u16>().is_ok()),
+        "i16" => (3, |segment| segment.parse::<i16>().is_ok()),
+        "u32" => (4, |segment| segment.parse::<u32>().is_ok()),
+        "i32" => (5, |segment| segment.parse::<i32>().is_ok()),
+        "u64" => (6, |segment| segment.parse::<u64>().is_ok()),
+        "i64" => (7, |segment| segment.parse::<i64>().is_ok()),
+        "usize" => (8, |segment| segment.parse::<usize>().is_ok()),
+        "isize" => (9, |segment| segment.parse::<isize>().is_ok()),
+        "uuid" => (10, is_uuid),
+        "alpha" => (11, |segment| {
+            !segment.is_empty() && segment.chars().all(char::is_alphabetic)
+        }),
+        "alnum" => (12, |segment| {
+            !segment.is_empty() && segment.chars().all(char::is_alphanumeric)
+        }),
+        _ => return None,
+    };
+    Some(Constraint {
+        name: name.to_owned(),
+        rank,
+        check,
+    })
+}
+
+/// Whether `segment` is a UUID in its hyphenated form.
+fn is_uuid(segment: &str) -> bool {
+    segment.len() == 36
+        && segment.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+impl<R> RouteTable<R> {
+    /// Create an empty routing table.
+    pub(crate) fn new() -> RouteTable<R> {
+        RouteTable {
+            accept: None,
+            next: HashMap::new(),
+            wildcards: Vec::new(),
+            catch_alls: Vec::new(),
+        }
+    }
+
+    /// Get the resource of the root of this table.
+    pub(crate) fn resource(&self) -> Option<&R> {
+        self.accept.as_ref()
+    }
+
+    /// Get the resource slot of the root of this table.
+    pub(crate) fn resource_mut(&mut self) -> &mut Option<R> {
+        &mut self.accept
+    }
+
+    /// Iterate over all resources of the table.
+    pub(crate) fn iter(&self) -> Resources<'_, R> {
+        Resources { stack: vec![self] }
+    }
+
+    /// Iterate mutably over all resources of the table.
+    pub(crate) fn iter_mut(&mut self) -> ResourcesMut<'_, R> {
+        ResourcesMut { stack: vec![self] }
+    }
+
+    /// Find the resource the concrete `path` is routed to, if any.
+    pub(crate) fn route<'a>(&'a self, path: &'a str) -> Option<(&'a R, RouteMatch<'a>)> {
+        self.route_by(path, |resource, route_match| Some((resource, route_match)))
+    }
+
+    /// Find the first resource matching the concrete `path` for which `select` returns a value,
+    /// trying the candidates by precedence, and return that value.
+    ///
+    /// This lets a resource decline a request, e.g. when its guards reject it, so that the next
+    /// candidate is tried as if the declining resource did not exist.
+    pub(crate) fn route_by<'a, T>(
+        &'a self,
+        path: &'a str,
+        mut select: impl FnMut(&'a R, RouteMatch<'a>) -> Option<T>,
+    ) -> Option<T> {
+        let mut start = 0;
+        let mut segments = Vec::new();
+        for segment in path.split('/') {
+            if !segment.is_empty() {
+                segments.push((start, segment));
+            }
+            start += segment.len() + 1;
+        }
+        self.route_segments(path, &segments, &mut Vec::new(), &mut select)
+    }
+
+    /// Match `segments`, which start at the given offsets of `path`, against this table, with
+    /// `params` holding the wildcard matches so far, until `select` accepts a resource.
+    fn route_segments<'a, T, F>(
+        &'a self,
+        path: &'a str,
+        segments: &[(usize, &'a str)],
+        params: &mut Vec<(&'a str, &'a str)>,
+        select: &mut F,
+    ) -> Option<T>
+    where
+        F: FnMut(&'a R, RouteMatch<'a>) -> Option<T>,
+    {
+        let rest = match segments.first() {
+            Some(&(start, segment)) => {
+                if let Some(table) = self.next.get(segment) {
+                    let found = table.route_segments(path, &segments[1..], params, select);
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+                for wildcard in self.wildcards.iter().filter(|w| w.accepts(segment)) {
+                    params.push((&wildcard.name, segment));
+                    let found = wildcard
+                        .table
+                        .route_segments(path, &segments[1..], params, select);
+                    params.pop();
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+                &path[start..]
+            }
+            None => {
+                if let Some(resource) = &self.accept {
+                    let found = select(resource, route_match(params));
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+                ""
+            }
+        };
+        for catch_all in self.catch_alls.iter().filter(|w| w.accepts(rest)) {
+            if let Some(resource) = &catch_all.table.accept {
+                params.push((&catch_all.name, rest));
+                let found = select(resource, route_match(params));
+                params.pop();
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+        None
+    }
+
+    /// Return the table of the given routing `path` (which may contain wildcards), creating it if
+    /// it does not exist yet.
+    ///
+    /// Panics if a wildcard segment has a different name than the wildcard with the same
+    /// constraint already defined at the same position, if a constraint is unknown, or if
+    /// segments follow a `*` wildcard.
+    pub(crate) fn setup_table(&mut self, path: &str) -> &mut RouteTable<R> {
+        let mut table = self;
+        let mut forbid_next = false;
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            if forbid_next {
+                panic!("No segments are allowed after wildcard with `*` modifier");
+            }
+
+            let wildcard = if segment.starts_with('{') && segment.ends_with("}*") {
+                Some((&segment[1..segment.len() - 2], true))
+            } else if segment.starts_with('{') && segment.ends_with('}') {
+                Some((&segment[1..segment.len() - 1], false))
+            } else if segment == "*" {
+                Some(("", true))
+            } else {
+                None
+            };
+
+            table = match wildcard {
+                Some((declared, catch_all)) => {
+                    forbid_next = catch_all;
+                    let mut declared = declared.splitn(2, ':');
+                    let name = declared.next().unwrap_or("");
+                    let constraint = declared.next().map(|constraint| {
+                        parse_constraint(constraint).unwrap_or_else(|| {
+                            panic!("Unknown constraint `{}` in path `{}`", constraint, path)
+                        })
+                    });
+                    let (wildcards, modifier) = if catch_all {
+                        (&mut table.catch_alls, "*")
+                    } else {
+                        (&mut table.wildcards, "")
+                    };
+                    let same_constraint = |wildcard: &Wildcard<R>| {
+                        wildcard.constraint.as_ref().map(|c| &c.name)
+                            == constraint.as_ref().map(|c| &c.name)
+                    };
+                    let idx = match wildcards.iter().position(same_constraint) {
+                        Some(idx) => idx,
+                        None => {
+                            let wildcard = Wildcard {
+                                name: name.to_owned(),
+                                constraint,
+                                table: RouteTable::new(),
+                            };
+                            let idx = wildcards
+                                .iter()
+                                .position(|other| other.rank() > wildcard.rank())
+                                .unwrap_or_else(|| wildcards.len());
+                            wildcards.insert(idx, wildcard);
+                            idx
+                        }
+                    };
+                    let wildcard = &mut wildcards[idx];
+                    if wildcard.name != name {
+                        panic!(
+                            "Route {} segment `{{{}}}{}` conflicts with existing wildcard segment \
+                             `{{{}}}{}`",
+                            path, name, modifier, wildcard.name, modifier
+                        );
+                    }
+                    &mut wildcard.table
+                }
+                None => table
+                    .next
+                    .entry(segment.to_owned())
+                    .or_insert_with(RouteTable::new),
+            };
+        }
+        table
+    }
+}
+
+/// The match of the wildcard segments `params`, by position and by name.
+fn route_match<'a>(params: &[(&'a str, &'a str)]) -> RouteMatch<'a> {
+    RouteMatch {
+        vec: params.iter().map(|&(_, segment)| segment).collect(),
+        map: params
+            .iter()
+            .cloned()
+            .filter(|(name, _)| !name.is_empty())
+            .collect(),
+    }
+}
+
+/// An iterator over the resources of a `RouteTable`.
+pub(crate) struct Resources<'a, R> {
+    stack: Vec<&'a RouteTable<R>>,
+}
+
+impl<'a, R> Iterator for Resources<'a, R> {
+    type Item = &'a R;
+
+    fn next(&mut self) -> Option<&'a R> {
+        while let Some(table) = self.stack.pop() {
+            self.stack.extend(table.next.values());
+            self.stack
+                .extend(table.wildcards.iter().map(|wildcard| &wildcard.table));
+            self.stack
+                .extend(table.catch_alls.iter().map(|wildcard| &wildcard.table));
+            if let Some(resource) = &table.accept {
+                return Some(resource);
+            }
+        }
+        None
+    }
+}
+
+/// A mutable iterator over the resources of a `RouteTable`.
+pub(crate) struct ResourcesMut<'a, R> {
+    stack: Vec<&'a mut RouteTable<R>>,
+}
+
+impl<'a, R> Iterator for ResourcesMut<'a, R> {
+    type Item = &'a mut R;
+
+    fn next(&mut self) -> Option<&'a mut R> {
+        while let Some(table) = self.stack.pop() {
+            let RouteTable {
+                accept,
+                next,
+                wildcards,
+                catch_alls,
+            } = table;
+            self.stack.extend(next.values_mut());
+            self.stack
+                .extend(wildcards.iter_mut().map(|wildcard| &mut wildcard.table));
+            self.stack
+                .extend(catch_alls.iter_mut().map(|wildcard| &mut wildcard.table));
+            if let Some(resource) = accept {
+                return Some(resource);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(paths: &[&'static str]) -> RouteTable<&'static str> {
+        let mut table = RouteTable::new();
+        for path in paths {
+            *table.setup_table(path).resource_mut() = Some(*path);
+        }
+        table
+    }
+
+    fn route<'a>(
+        table: &'a RouteTable<&'static str>,
+        path: &'a str,
+    ) -> Option<(&'a str, Vec<&'a str>)> {
+        table
+            .route(path)
+            .map(|(resource, route_match)| (*resource, route_match.vec))
+    }
+
+    #[test]
+    fn precedence() {
+        let table = table(&["/a/b/c", "/{x}/b/d", "/{x}/{y}", "*"]);
+        assert_eq!(route(&table, "/a/b/c"), Some(("/a/b/c", vec![])));
+        assert_eq!(route(&table, "/a/b/d"), Some(("/{x}/b/d", vec!["a"])));
+        assert_eq!(route(&table, "/a/b"), Some(("/{x}/{y}", vec!["a", "b"])));
+        assert_eq!(route(&table, "/a/b/e"), Some(("*", vec!["a/b/e"])));
+        assert_eq!(route(&table, "/"), Some(("*", vec![""])));
+    }
+
+    #[test]
+    fn wildcards_at_same_position() {
+        let table = table(&["/files/{name}", "/files/{rest}*"]);
+        assert_eq!(
+            route(&table, "/files/a"),
+            Some(("/files/{name}", vec!["a"]))
+        );
+        assert_eq!(
+            route(&table, "/files/a//b/"),
+            Some(("/files/{rest}*", vec!["a//b/"]))
+        );
+        assert_eq!(route(&table, "/files"), Some(("/files/{rest}*", vec![""])));
+
+        let (_, route_match) = table.route("/files/a/b").unwrap();
+        assert_eq!(route_match.map["rest"], "a/b");
+    }
+
+    #[test]
+    fn constraints() {
+        let table = table(&["/users/{id:u32}", "/users/{name:alpha}", "/users/{other}"]);
+        assert_eq!(
+            route(&table, "/users/42"),
+            Some(("/users/{id:u32}", vec!["42"]))
+        );
+        assert_eq!(
+            route(&table, "/users/jane"),
+            Some(("/users/{name:alpha}", vec!["jane"]))
+        );
+        assert_eq!(
+            route(&table, "/users/j%C3%A9r%C3%B4me"),
+            Some(("/users/{name:alpha}", vec!["j%C3%A9r%C3%B4me"]))
+        );
+        assert_eq!(
+            route(&table, "/users/jane-doe"),
+            Some(("/users/{other}", vec!["jane-doe"]))
+        );
+
+        let nested = table(&["/a/{x:u8}/b", "/a/{y:u32}/c"]);
+        assert_eq!(route(&nested, "/a/7/c"), Some(("/a/{y:u32}/c", vec!["7"])));
+        assert_eq!(route(&nested, "/a/700/b"), None);
+    }
+
+    #[test]
+    fn declined_resources() {
+        let table = table(&["/a/b", "/a/{x}", "*"]);
+        let route_by = |path: &'static str, declined: &'static str| {
+            table.route_by(path, |resource, _| Some(*resource).filter(|r| *r != declined))
+        };
+        assert_eq!(route_by("/a/b", "/a/b"), Some("/a/{x}"));
+        assert_eq!(route_by("/a/b", "/a/{x}"), Some("/a/b"));
+        assert_eq!(route_by("/a/c", "/a/{x}"), Some("*"));
+        assert_eq!(route_by("/a/c", "*"), Some("/a/{x}"));
+    }
+
+    #[test]
+    fn no_match() {
+        let table = table(&["/a/{x}", "/b"]);
+        assert_eq!(route(&table, "/a"), None);
+        assert_eq!(route(&table, "/a/b/c"), None);
+        assert_eq!(route(&table, "/c"), None);
+    }
+}
@@ -1,52 +1,125 @@
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
 use std::fmt::Debug;
 use std::sync::Arc;
 
 use crate::{
+    app::ScopedData,
     configuration::Store,
-    endpoint::{BoxedEndpoint, Endpoint},
-    Middleware,
+    endpoint::{BoxedEndpoint, Endpoint, ServiceEndpoint},
+    middleware::Deprecation,
+    Extract, Middleware, Request, Response,
 };
-use path_table::{PathTable, RouteMatch};
+use futures::future;
+use http::header::HeaderValue;
+use http_service::HttpService;
+use path_table::RouteMatch;
 
 /// A core type for routing.
 ///
 /// The `Router` type can be used to set up routes and resources, and to apply middleware.
 pub struct Router<Data> {
-    table: PathTable<ResourceData<Data>>,
+    table: RouteTable<ResourceData<Data>>,
+    fallbacks: RouteTable<Fallback<Data>>,
     middleware_base: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
     pub(crate) store_base: Store,
+    prefix: String,
+    pub(crate) names: HashMap<String, String>,
+    case_insensitive: bool,
+    /// The patterns set up in this router, including those of subrouters, as keyed in `table`.
+    paths: Vec<String>,
 }
 
+/// The fallback endpoint of a (sub)router, together with the middleware applying to it.
+struct Fallback<Data> {
+    endpoint: EndpointData<Data>,
+    middleware: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+}
+
+/// The outcome of routing a request.
+///
+/// The effective middleware chain of every resource and fallback is assembled when the middleware
+/// is registered, so `middleware` borrows a ready-made slice and routing does no per-request
+/// composition.
 pub(crate) struct RouteResult<'a, Data> {
     pub(crate) endpoint: &'a EndpointData<Data>,
     pub(crate) params: Option<RouteMatch<'a>>,
     pub(crate) middleware: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
+    /// The pattern of the matched resource, if any.
+    pub(crate) pattern: Option<&'a str>,
 }
 
 fn route_match_success<'a, Data>(
     route: &'a ResourceData<Data>,
     route_match: RouteMatch<'a>,
     method: &http::Method,
+    req: &Request,
 ) -> Option<RouteResult<'a, Data>> {
     // If it is a HTTP HEAD request then check if there is a callback in the endpoints map
-    // if not then fallback to the behavior of HTTP GET else proceed as usual
-    let endpoint =
-        if method == http::Method::HEAD && !route.endpoints.contains_key(&http::Method::HEAD) {
-            route.endpoints.get(&http::Method::GET)?
-        } else {
-            route.endpoints.get(method)?
-        };
-    let middleware = &*route.middleware;
+    // if not then fallback to the behavior of HTTP GET else proceed as usual. Methods without
+    // an endpoint of their own are handled by the catch-all endpoint, if any, and are otherwise
+    // answered with `405 Method Not Allowed`.
+    let method = if method == http::Method::HEAD && !route.has_endpoint(&http::Method::HEAD) {
+        &http::Method::GET
+    } else {
+        method
+    };
+    let guarded = |wanted: Option<&http::Method>| {
+        route
+            .guarded
+            .iter()
+            .find(|guarded| guarded.method.as_ref() == wanted && (guarded.guard)(req))
+            .map(|guarded| &guarded.endpoint)
+    };
+    let endpoint = guarded(Some(method))
+        .or_else(|| route.endpoints.get(method))
+        .or_else(|| guarded(None))
+        .or_else(|| route.any.as_ref());
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint,
+        // Requests rejected by guards move on to the next resource matching the path, if any,
+        // and otherwise to the fallback.
+        None if !route.has_endpoint(method) && route.guarded.iter().all(|g| g.method.is_some()) => {
+            route.method_not_allowed.as_ref()?
+        }
+        None => return None,
+    };
+    let middleware = endpoint
+        .middleware
+        .as_ref()
+        .map_or(&*route.middleware, |middleware| &**middleware);
 
     Some(RouteResult {
         endpoint,
         params: Some(route_match),
         middleware,
+        pattern: Some(&route.pattern),
     })
 }
 
+impl<'a, Data> RouteResult<'a, Data> {
+    /// Re-borrow the wildcard segments matched against `lowered`, the ASCII-lowercased version of
+    /// `path`, from `path`, so that endpoints see them as sent.
+    pub(crate) fn restore_case(self, lowered: &str, path: &'a str) -> RouteResult<'a, Data> {
+        let restore = |segment: &'a str| {
+            if segment.is_empty() {
+                return segment;
+            }
+            let start = segment.as_ptr() as usize - lowered.as_ptr() as usize;
+            &path[start..start + segment.len()]
+        };
+        let params = self.params.map(|params| RouteMatch {
+            vec: params.vec.into_iter().map(restore).collect(),
+            map: params
+                .map
+                .into_iter()
+                .map(|(name, segment)| (name, restore(segment)))
+                .collect(),
+        });
+        RouteResult { params, ..self }
+    }
+}
+
 fn route_match_failure<'a, Data>(
     endpoint: &'a EndpointData<Data>,
     middleware: &'a [Arc<dyn Middleware<Data> + Send + Sync>],
@@ -55,6 +128,7 @@ fn route_match_failure<'a, Data>(
         endpoint,
         params: None,
         middleware: &*middleware,
+        pattern: None,
     }
 }
 
@@ -85,8 +159,15 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// only one modifier: `*`, which means that the wildcard will match to the end of given path,
     /// no matter how many segments are left, even nothing. If there is a modifier for unnamed
     /// wildcard definition, `{}` may be omitted. That is, `{}*` can be written as `*`. It is an
-    /// error to define two wildcard segments with different wildcard modifiers, or to write other
-    /// path segment after a segment with wildcard modifier.
+    /// error to write other path segment after a segment with wildcard modifier.
+    ///
+    /// A wildcard segment can also declare a constraint on its value, as in `{id:u32}` or `{:u32}`.
+    /// Only segments satisfying the constraint once percent-decoded match the wildcard; others
+    /// are routed as if it did not exist, to another resource or to the fallback or default
+    /// handler (a `404 Not Found` by default), instead of failing extraction in the endpoint.
+    /// The constraints are the integer types (`u8` to `u64`, `i8` to `i64`, `usize` and
+    /// `isize`), `alpha`, `alnum` and `uuid`. Wildcards at the same position may differ in their
+    /// constraint, as in `users/{id:u32}` and `users/{name:alpha}`.
     ///
     /// Here are some examples omitting the HTTP verb based endpoint selection:
     ///
@@ -96,26 +177,215 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// app.at("/hello");
     /// app.at("/message/{}");
     /// app.at("add_two/{num}");
+    /// app.at("users/{id:u32}");
     /// app.at("static/{path}*");
     /// app.at("single_page_app/*");
     /// ```
     ///
-    /// Notice that there is no fallback route matching, i.e. either a resource is a full match or
-    /// not, which means that the order of adding resources has no effect.
+    /// When several resources could match a request, the most specific one wins, segment by
+    /// segment from the left: a concrete segment takes precedence over a wildcard, and a wildcard
+    /// over one with the `*` modifier; a wildcard with a constraint takes precedence over one
+    /// without. Given these resources, `/users/me` is served by the first one, `/users/42` by the
+    /// second, `/users/42/avatar.png` by the third and `/teams/7/posts` by the fourth, while
+    /// `/users/7/posts` goes to the third, `users` being more specific than `{collection}`:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.at("/users/me").get(async || "current user");
+    /// app.at("/users/{id}").get(async || "user");
+    /// app.at("/users/*").get(async || "user files");
+    /// app.at("/{collection}/{id}/posts").get(async || "posts");
+    /// ```
+    ///
+    /// The next candidate is tried if the most specific one cannot match the rest of the path, if
+    /// a segment fails its constraint, or if the guards of its endpoints reject the request (see
+    /// `Resource::guard`). A resource without any endpoint for the request method does not defer
+    /// to the next candidate, but answers `405 Method Not Allowed`. Apart from the order among
+    /// the guarded endpoints of a resource, this only depends on the set of resources, never on
+    /// the order they were added in.
     pub fn at<'a>(&'a mut self, path: &'a str) -> Resource<'a, Data> {
-        let table = self.table.setup_table(path);
+        let pattern = strip_constraints(path);
+        let key = if self.case_insensitive {
+            lowercase_concrete(path)
+        } else {
+            path.to_owned()
+        };
+        self.paths.push(key.clone());
+        let table = self.table.setup_table(&key);
         Resource {
+            path,
+            full_path: join_path(&self.prefix, &pattern),
             table,
+            fallbacks: &mut self.fallbacks,
             middleware_base: &self.middleware_base,
+            names: &mut self.names,
+            overwrite: false,
+            guard: None,
+            case_insensitive: self.case_insensitive,
+            key,
+            paths: &mut self.paths,
         }
     }
 
-    /// Create a new top-level router.
-    pub(crate) fn new() -> Router<Data> {
+    /// Group routes sharing a `prefix`, and possibly middleware and configuration.
+    ///
+    /// This is a shorthand for `router.at(prefix).nest(builder)`: middleware and configuration
+    /// added inside `builder` only apply to the routes of the group.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # use std::time::Duration;
+    /// # use tide::configuration::RequestTimeout;
+    /// # let mut app = tide::App::new(());
+    /// app.scope("/admin", |admin| {
+    ///     admin.config(RequestTimeout(Duration::from_secs(60)));
+    ///     admin.at("/users").get(async || "users");
+    ///     admin.at("/reports").get(async || "reports");
+    /// });
+    /// ```
+    pub fn scope(&mut self, prefix: &str, builder: impl FnOnce(&mut Router<Data>)) -> &mut Self {
+        self.at(prefix).nest(builder);
+        self
+    }
+
+    /// Set the fallback endpoint of this router, used when no route inside it matches.
+    ///
+    /// For a subrouter, this covers all paths under the subrouter's prefix, so different parts of
+    /// an app can handle misses differently:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.at("/api").nest(|router| {
+    ///     router.default_handler(async || r#"{"error": "not found"}"#);
+    ///     router.at("/users").get(async || "[]");
+    /// });
+    /// ```
+    ///
+    /// The most deeply nested matching fallback wins; if there is none, the app's default handler
+    /// is used. The middleware of the router applies to its fallback as well.
+    pub fn default_handler<T: Endpoint<Data, U>, U>(
+        &mut self,
+        handler: T,
+    ) -> &mut EndpointData<Data> {
+        let fallback = Fallback {
+            endpoint: EndpointData {
+                endpoint: BoxedEndpoint::new(handler),
+                store: Store::new(),
+                meta: RouteMeta::default(),
+                middleware: None,
+            },
+            middleware: self.middleware_base.clone(),
+        };
+        let slot = self.fallbacks.resource_mut();
+        *slot = Some(fallback);
+        &mut slot.as_mut().unwrap().endpoint
+    }
+
+    /// Forward all requests under `prefix` to an external `HttpService`.
+    ///
+    /// This makes it possible to graft existing, non-Tide components into the URL space of the
+    /// app. The service receives every request whose path is `prefix` or starts with `prefix/`,
+    /// regardless of the HTTP method, with the request left untouched (including its full path).
+    /// Middleware of this router is applied as for any other resource.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(futures_api)]
+    /// # let mut app = tide::App::new(());
+    /// let legacy = |_req: http_service::Request| {
+    ///     futures::future::ok::<_, std::io::Error>(http_service::Response::new("legacy".into()))
+    /// };
+    /// app.router().mount_service("/legacy", legacy);
+    /// ```
+    pub fn mount_service<S: HttpService>(&mut self, prefix: &str, service: S) {
+        let path = format!("{}/*", prefix.trim_end_matches('/'));
+        self.at(&path).any(ServiceEndpoint::new(service));
+    }
+
+    /// Add the routes of `other` to this router.
+    ///
+    /// This lets libraries ship reusable sets of routes, with their own middleware and
+    /// configuration, for apps to absorb into theirs:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// fn auth_routes() -> tide::Router<()> {
+    ///     let mut router = tide::Router::new();
+    ///     router.at("/login").post(async || "logged in");
+    ///     router.at("/logout").post(async || "logged out");
+    ///     router
+    /// }
+    ///
+    /// let mut app = tide::App::new(());
+    /// app.router().merge(auth_routes());
+    /// ```
+    ///
+    /// The middleware and configuration of `other` only apply to its own routes, while those of
+    /// this router apply to all routes, as if `other` was nested at the root. Resources both
+    /// routers have are combined, each endpoint keeping its own middleware.
+    ///
+    /// Panics if both routers have an endpoint for the same path and method, or a fallback for the
+    /// same path, if their wildcard segments conflict, if route names clash, or if `other`
+    /// matches paths case-insensitively while this router does not.
+    pub fn merge(&mut self, mut other: Router<Data>) -> &mut Self {
+        if other.case_insensitive && !self.case_insensitive {
+            panic!(
+                "Cannot merge a router matching paths case-insensitively into one that does not"
+            );
+        }
+        other.apply_default_config();
+        let paths = std::iter::once(String::new()).chain(other.paths.drain(..));
+        for path in paths {
+            let key = if self.case_insensitive {
+                lowercase_concrete(&path)
+            } else {
+                path.clone()
+            };
+            if let Some(mut resource) = other.table.setup_table(&path).resource_mut().take() {
+                resource.middleware = prepend(&self.middleware_base, resource.middleware);
+                for endpoint in resource.endpoints_mut() {
+                    if let Some(middleware) = endpoint.middleware.take() {
+                        endpoint.middleware = Some(prepend(&self.middleware_base, middleware));
+                    }
+                }
+                resource.pattern = join_path(&self.prefix, &resource.pattern);
+                let slot = self.table.setup_table(&key).resource_mut();
+                *slot = match slot.take() {
+                    Some(mut existing) => {
+                        existing.absorb(resource);
+                        Some(existing)
+                    }
+                    None => Some(resource),
+                };
+            }
+            if let Some(mut fallback) = other.fallbacks.setup_table(&path).resource_mut().take() {
+                let slot = self.fallbacks.setup_table(&key).resource_mut();
+                if slot.is_some() {
+                    panic!("Both merged routers have a fallback for path `{}`", path);
+                }
+                fallback.middleware = prepend(&self.middleware_base, fallback.middleware);
+                *slot = Some(fallback);
+            }
+            self.paths.push(key);
+        }
+        for (name, path) in other.names {
+            insert_name(&mut self.names, name, join_path(&self.prefix, &path));
+        }
+        self
+    }
+
+    /// Create a new top-level router, to be combined with others using `merge`.
+    pub fn new() -> Router<Data> {
         Router {
-            table: PathTable::new(),
+            table: RouteTable::new(),
+            fallbacks: RouteTable::new(),
             middleware_base: Vec::new(),
             store_base: Store::new(),
+            prefix: String::new(),
+            names: HashMap::new(),
+            case_insensitive: false,
+            paths: Vec::new(),
         }
     }
 
@@ -148,12 +418,51 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
     /// });
     /// ```
     pub fn middleware(&mut self, middleware: impl Middleware<Data> + 'static) -> &mut Self {
-        let middleware = Arc::new(middleware);
+        self.apply_middleware(Arc::new(middleware));
+        self
+    }
+
+    pub(crate) fn apply_middleware(&mut self, middleware: Arc<dyn Middleware<Data> + Send + Sync>) {
         for resource in self.table.iter_mut() {
             resource.middleware.push(middleware.clone());
+            for endpoint in resource.endpoints_mut() {
+                if let Some(own) = &mut endpoint.middleware {
+                    own.push(middleware.clone());
+                }
+            }
+        }
+        for fallback in self.fallbacks.iter_mut() {
+            fallback.middleware.push(middleware.clone());
         }
         self.middleware_base.push(middleware);
-        self
+    }
+
+    /// Create a new top-level router, starting out with the middleware of this one.
+    pub(crate) fn sibling(&self) -> Router<Data> {
+        Router {
+            middleware_base: self.middleware_base.clone(),
+            case_insensitive: self.case_insensitive,
+            ..Router::new()
+        }
+    }
+
+    /// Match the concrete segments of paths regardless of their ASCII case.
+    ///
+    /// Panics if resources were already added, since their paths were registered as written.
+    pub(crate) fn set_case_insensitive(&mut self, enabled: bool) {
+        if self.table.iter().next().is_some() || self.fallbacks.iter().next().is_some() {
+            panic!("Case-insensitive path matching must be configured before adding resources");
+        }
+        self.case_insensitive = enabled;
+    }
+
+    /// The path to route a request for `path` by, if it differs from `path`.
+    pub(crate) fn routing_path(&self, path: &str) -> Option<String> {
+        if self.case_insensitive {
+            Some(path.to_ascii_lowercase())
+        } else {
+            None
+        }
     }
 
     /// Add a default configuration `item` for this router.
@@ -164,16 +473,63 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
         self
     }
 
+    /// Give the endpoints of this router their own `data`, in place of the data of the app.
+    ///
+    /// This lets a mounted module bring its own state. `AppData` extractors within this router
+    /// and its subrouters receive a clone of `data`, unless a more deeply nested router overrides
+    /// it again. Middleware keeps seeing the data of the app in `RequestContext::app_data`.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// use tide::AppData;
+    ///
+    /// let mut app = tide::App::new("site");
+    /// app.at("/").get(async move |name: AppData<&'static str>| *name);
+    /// app.at("/admin").nest(|router| {
+    ///     router.data("admin");
+    ///     router.at("/").get(async move |name: AppData<&'static str>| *name);
+    /// });
+    /// ```
+    pub fn data(&mut self, data: Data) -> &mut Self {
+        self.config(ScopedData(Arc::new(data)))
+    }
+
     pub(crate) fn route<'a>(
         &'a self,
         path: &'a str,
         method: &http::Method,
+        req: &Request,
         default_handler: &'a Arc<EndpointData<Data>>,
     ) -> RouteResult<'a, Data> {
-        match self.table.route(path) {
-            Some((route, route_match)) => route_match_success(route, route_match, method)
-                .unwrap_or_else(|| route_match_failure(default_handler, &self.middleware_base)),
-            None => route_match_failure(default_handler, &self.middleware_base),
+        let found = self.table.route_by(path, |route, route_match| {
+            route_match_success(route, route_match, method, req)
+        });
+        match found {
+            Some(result) => result,
+            None => match self.fallback(path) {
+                Some((fallback, route_match)) => RouteResult {
+                    endpoint: &fallback.endpoint,
+                    params: Some(route_match),
+                    middleware: &fallback.middleware,
+                    pattern: None,
+                },
+                None => route_match_failure(default_handler, &self.middleware_base),
+            },
+        }
+    }
+
+    /// Find the fallback of the most deeply nested (sub)router whose prefix matches `path`.
+    fn fallback<'a>(&'a self, path: &'a str) -> Option<(&'a Fallback<Data>, RouteMatch<'a>)> {
+        let mut prefix = path.trim_end_matches('/');
+        loop {
+            if let Some(found) = self.fallbacks.route(prefix) {
+                return Some(found);
+            }
+            match prefix.rfind('/') {
+                Some(idx) => prefix = prefix[..idx].trim_end_matches('/'),
+                None if !prefix.is_empty() => prefix = "",
+                None => return None,
+            }
         }
     }
 }
@@ -181,23 +537,172 @@ impl<Data: Clone + Send + Sync + 'static> Router<Data> {
 impl<Data> Router<Data> {
     pub(crate) fn apply_default_config(&mut self) {
         for resource in self.table.iter_mut() {
-            for endpoint in resource.endpoints.values_mut() {
+            let store = resource.store.clone();
+            for endpoint in resource.endpoints_mut() {
+                endpoint.store.merge(&store);
                 endpoint.store.merge(&self.store_base);
             }
         }
+        for fallback in self.fallbacks.iter_mut() {
+            fallback.endpoint.store.merge(&self.store_base);
+        }
     }
 
-    pub(crate) fn get_item<T: Any + Debug + Clone + Send + Sync>(&self) -> Option<&T> {
+    /// Get the default configuration item of given type for this router.
+    pub fn get_item<T: Any + Debug + Clone + Send + Sync>(&self) -> Option<&T> {
         self.store_base.read()
     }
 }
 
+/// Strip the constraints from the wildcard segments of `path`.
+fn strip_constraints(path: &str) -> String {
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| match (segment.find(':'), segment.rfind('}')) {
+            (Some(colon), Some(end)) if segment.starts_with('{') && colon < end => {
+                format!("{}{}", &segment[..colon], &segment[end..])
+            }
+            _ => segment.to_owned(),
+        })
+        .collect();
+    segments.join("/")
+}
+
+/// Lowercase the concrete segments of a route pattern, leaving wildcard names as written.
+fn lowercase_concrete(pattern: &str) -> String {
+    let segments: Vec<String> = pattern
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with('{') || segment.starts_with('*') {
+                segment.to_owned()
+            } else {
+                segment.to_ascii_lowercase()
+            }
+        })
+        .collect();
+    segments.join("/")
+}
+
+fn prepend<T: Clone>(base: &[T], items: Vec<T>) -> Vec<T> {
+    base.iter().cloned().chain(items).collect()
+}
+
+/// Join a path to the prefix of its router, normalizing slashes.
+fn join_path(prefix: &str, path: &str) -> String {
+    let segments: Vec<&str> = prefix
+        .split('/')
+        .chain(path.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+pub(crate) fn insert_name(names: &mut HashMap<String, String>, name: String, path: String) {
+    match names.entry(name) {
+        Entry::Occupied(entry) => panic!(
+            "Route name `{}` is already used for path `{}`",
+            entry.key(),
+            entry.get()
+        ),
+        Entry::Vacant(entry) => {
+            entry.insert(path);
+        }
+    }
+}
+
+/// The named resources of an app, for generating URLs to them.
+///
+/// Resources are named with `Resource::name`; endpoints obtain the URLs through this extractor:
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// use tide::Urls;
+///
+/// async fn create(urls: Urls) -> http::Response<String> {
+///     let location = urls.url_for("message", &["42"]).unwrap();
+///     http::Response::builder()
+///         .status(http::StatusCode::SEE_OTHER)
+///         .header("Location", location)
+///         .body(String::new())
+///         .unwrap()
+/// }
+///
+/// let mut app = tide::App::new(());
+/// app.at("/message/{id}").name("message").get(async || "");
+/// app.at("/message").post(create);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Urls(Arc<HashMap<String, String>>);
+
+impl Urls {
+    pub(crate) fn new(names: HashMap<String, String>) -> Urls {
+        Urls(Arc::new(names))
+    }
+
+    /// Generate the path of the resource named `name`, filling its wildcard segments with
+    /// `params` in order.
+    ///
+    /// Parameters are percent-encoded, except for slashes in a final `*` wildcard. Returns `None`
+    /// if there is no such resource, or if the number of parameters does not match.
+    pub fn url_for(&self, name: &str, params: &[&str]) -> Option<String> {
+        let path = self.0.get(name)?;
+        let mut params = params.iter();
+        let mut url = String::new();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            url.push('/');
+            if segment.starts_with('{') || segment == "*" {
+                let rest = segment.ends_with('*');
+                url.push_str(&encode_segment(params.next()?, rest));
+            } else {
+                url.push_str(segment);
+            }
+        }
+        if params.next().is_some() {
+            return None;
+        }
+        if url.is_empty() {
+            url.push('/');
+        }
+        Some(url)
+    }
+}
+
+impl<Data> Extract<Data> for Urls {
+    type Fut = future::Ready<Result<Self, Response>>;
+    fn extract(
+        data: &mut Data,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        future::ok(req.extensions().get::<Urls>().cloned().unwrap_or_default())
+    }
+}
+
+fn encode_segment(param: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::with_capacity(param.len());
+    for byte in param.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            b'/' if keep_slashes => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
 /// A handle to the endpoint.
 ///
 /// This can be used to add configuration items to the endpoint.
 pub struct EndpointData<Data> {
     pub(crate) endpoint: BoxedEndpoint<Data>,
     pub(crate) store: Store,
+    pub(crate) meta: RouteMeta,
+    /// The middleware of the endpoint, if it differs from that of its resource, as for endpoints
+    /// merged into a resource from another router.
+    pub(crate) middleware: Option<Vec<Arc<dyn Middleware<Data> + Send + Sync>>>,
 }
 
 impl<Data> EndpointData<Data> {
@@ -206,6 +711,69 @@ impl<Data> EndpointData<Data> {
         self.store.write(item);
         self
     }
+
+    /// Attach the metadata `value` under `key` to this endpoint, replacing any previous value.
+    pub fn meta(&mut self, key: &str, value: &str) -> &mut Self {
+        self.meta.values.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Tag this endpoint with `tag`.
+    pub fn tag(&mut self, tag: &str) -> &mut Self {
+        if !self.meta.has_tag(tag) {
+            self.meta.tags.push(tag.to_owned());
+        }
+        self
+    }
+
+    /// Get the metadata and tags of this endpoint.
+    pub fn route_meta(&self) -> &RouteMeta {
+        &self.meta
+    }
+}
+
+/// Arbitrary metadata and tags attached to an endpoint with `EndpointData::meta` and
+/// `EndpointData::tag`.
+///
+/// Tide itself does not interpret them. Middleware reads them through
+/// `RequestContext::route_meta`, e.g. to rate limit by tag, and tools can collect them for
+/// documentation:
+///
+/// ```rust, no_run
+/// # #![feature(async_await)]
+/// let mut app = tide::App::new(());
+/// app.at("/messages")
+///     .get(async || "messages")
+///     .tag("public")
+///     .post(async || "created")
+///     .meta("auth", "required");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RouteMeta {
+    values: HashMap<String, String>,
+    tags: Vec<String>,
+}
+
+impl RouteMeta {
+    /// The metadata value stored under `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| &**value)
+    }
+
+    /// All metadata values, indexed by key.
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    /// The tags, in the order they were added.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Whether the endpoint is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 }
 
 /// A handle to a resource (identified by a path).
@@ -214,16 +782,160 @@ impl<Data> EndpointData<Data> {
 /// establish a resource path, the `Resource` type can be used to establish endpoints for various
 /// HTTP methods at that path. Also, using `nest`, it can be used to set up a subrouter.
 ///
-/// After establishing an endpoint, the method will return `&mut EndpointData`. This can be used to
-/// set per-endpoint configuration.
+/// After establishing an endpoint, the method will return an `EndpointHandle`. This can be used to
+/// set per-endpoint configuration, or to establish endpoints for other methods.
 pub struct Resource<'a, Data> {
-    table: &'a mut PathTable<ResourceData<Data>>,
+    path: &'a str,
+    /// The path from the root of the app, without constraints.
+    full_path: String,
+    table: &'a mut RouteTable<ResourceData<Data>>,
+    /// The fallbacks of the router, only set up at `key` when nesting a router with any.
+    fallbacks: &'a mut RouteTable<Fallback<Data>>,
     middleware_base: &'a Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+    names: &'a mut HashMap<String, String>,
+    overwrite: bool,
+    guard: Option<Guard>,
+    case_insensitive: bool,
+    /// The path relative to the router, as keyed in its table.
+    key: String,
+    paths: &'a mut Vec<String>,
 }
 
 struct ResourceData<Data> {
     endpoints: HashMap<http::Method, EndpointData<Data>>,
+    any: Option<EndpointData<Data>>,
     middleware: Vec<Arc<dyn Middleware<Data> + Send + Sync>>,
+    store: Store,
+    /// Answers methods without an endpoint; set once the resource has an endpoint.
+    method_not_allowed: Option<EndpointData<Data>>,
+    /// The endpoints registered with a guard, tried in order before those in `endpoints` and
+    /// `any`.
+    guarded: Vec<Guarded<Data>>,
+    /// The path from the root of the app, without constraints.
+    pattern: String,
+}
+
+impl<Data> ResourceData<Data> {
+    /// All endpoints of the resource, including the one answering `405 Method Not Allowed`.
+    fn endpoints_mut(&mut self) -> Vec<&mut EndpointData<Data>> {
+        let endpoints = self.endpoints.values_mut().chain(self.any.as_mut());
+        let guarded = self.guarded.iter_mut().map(|guarded| &mut guarded.endpoint);
+        endpoints
+            .chain(guarded)
+            .chain(self.method_not_allowed.as_mut())
+            .collect()
+    }
+
+    /// Set up the endpoint answering `405 Method Not Allowed`, listing the current methods.
+    fn update_method_not_allowed(&mut self) {
+        let guarded = self.guarded.iter().filter_map(|guarded| guarded.method.as_ref());
+        let allowed = self.endpoints.keys().chain(guarded);
+        self.method_not_allowed = Some(EndpointData {
+            endpoint: BoxedEndpoint::new(MethodNotAllowed::new(allowed)),
+            store: Store::new(),
+            meta: RouteMeta::default(),
+            middleware: None,
+        });
+    }
+
+    /// Take over the endpoints of `other`, a resource for the same path from a merged router.
+    ///
+    /// Panics if both resources have an endpoint for the same method.
+    fn absorb(&mut self, other: ResourceData<Data>) {
+        let same_middleware = other.middleware.len() == self.middleware.len()
+            && other
+                .middleware
+                .iter()
+                .zip(&self.middleware)
+                .all(|(a, b)| Arc::ptr_eq(a, b));
+        let middleware = other.middleware;
+        let adopt = |mut endpoint: EndpointData<Data>| {
+            if endpoint.middleware.is_none() && !same_middleware {
+                endpoint.middleware = Some(middleware.clone());
+            }
+            endpoint
+        };
+        for (method, endpoint) in other.endpoints {
+            if self.endpoints.contains_key(&method) {
+                panic!(
+                    "Both merged routers have a {} endpoint for path `{}`",
+                    method, self.pattern
+                );
+            }
+            self.endpoints.insert(method, adopt(endpoint));
+        }
+        if let Some(endpoint) = other.any {
+            if self.any.is_some() {
+                panic!(
+                    "Both merged routers have a catch-all endpoint for path `{}`",
+                    self.pattern
+                );
+            }
+            self.any = Some(adopt(endpoint));
+        }
+        for guarded in other.guarded {
+            let endpoint = adopt(guarded.endpoint);
+            self.guarded.push(Guarded { endpoint, ..guarded });
+        }
+        if !self.endpoints.is_empty() || !self.guarded.is_empty() {
+            self.update_method_not_allowed();
+        }
+    }
+
+    /// Whether the resource has an endpoint of its own for `method`, guarded or not.
+    fn has_endpoint(&self, method: &http::Method) -> bool {
+        self.endpoints.contains_key(method)
+            || self.guarded.iter().any(|guarded| guarded.method.as_ref() == Some(method))
+    }
+}
+
+/// A predicate a request must satisfy to be routed to an endpoint.
+type Guard = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// An endpoint only serving requests satisfying its guard.
+struct Guarded<Data> {
+    /// The method of the endpoint, or `None` for a catch-all endpoint.
+    method: Option<http::Method>,
+    guard: Guard,
+    endpoint: EndpointData<Data>,
+}
+
+/// An endpoint responding `405 Method Not Allowed`, listing the methods of the resource.
+struct MethodNotAllowed {
+    allow: HeaderValue,
+}
+
+impl MethodNotAllowed {
+    fn new<'a>(methods: impl Iterator<Item = &'a http::Method>) -> MethodNotAllowed {
+        // A method can have several endpoints, e.g. guarded ones, but is only listed once.
+        let mut allow: BTreeSet<&str> = methods.map(http::Method::as_str).collect();
+        if allow.contains("GET") {
+            allow.insert("HEAD");
+        }
+        let allow: Vec<&str> = allow.into_iter().collect();
+        MethodNotAllowed {
+            allow: HeaderValue::from_str(&allow.join(", ")).unwrap(),
+        }
+    }
+}
+
+impl<Data> Endpoint<Data, ()> for MethodNotAllowed {
+    type Fut = future::Ready<Response>;
+
+    fn call(
+        &self,
+        data: Data,
+        req: Request,
+        params: Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let res = http::Response::builder()
+            .status(http::StatusCode::METHOD_NOT_ALLOWED)
+            .header(http::header::ALLOW, self.allow.clone())
+            .body(http_service::Body::empty())
+            .unwrap();
+        future::ready(res)
+    }
 }
 
 impl<'a, Data> Resource<'a, Data> {
@@ -236,90 +948,405 @@ impl<'a, Data> Resource<'a, Data> {
     /// If resources are already present, they will be discarded.
     pub fn nest(self, builder: impl FnOnce(&mut Router<Data>)) {
         let mut subrouter = Router {
-            table: PathTable::new(),
+            table: RouteTable::new(),
+            fallbacks: RouteTable::new(),
             middleware_base: self.middleware_base.clone(),
             store_base: Store::new(),
+            prefix: self.full_path.clone(),
+            names: HashMap::new(),
+            case_insensitive: self.case_insensitive,
+            paths: Vec::new(),
         };
         builder(&mut subrouter);
         subrouter.apply_default_config();
         *self.table = subrouter.table;
+        if subrouter.fallbacks.iter().next().is_some() {
+            *self.fallbacks.setup_table(&self.key) = subrouter.fallbacks;
+        }
+        for (name, path) in subrouter.names {
+            insert_name(self.names, name, path);
+        }
+        for path in subrouter.paths {
+            self.paths.push(join_path(&self.key, &path));
+        }
     }
 
-    /// Add an endpoint for the given HTTP method
-    pub fn method<T: Endpoint<Data, U>, U>(
-        &mut self,
-        method: http::Method,
-        ep: T,
-    ) -> &mut EndpointData<Data> {
+    /// Name this resource, so that URLs to it can be generated with `Urls::url_for`.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.at("/message/{id}").name("message").get(async || "");
+    /// ```
+    ///
+    /// Panics if another resource already has this name.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        insert_name(self.names, name.to_owned(), self.full_path.clone());
+        self
+    }
+
+    fn resource_data(&mut self) -> &mut ResourceData<Data> {
         let resource = self.table.resource_mut();
         if resource.is_none() {
             let new_resource = ResourceData {
                 endpoints: HashMap::new(),
+                any: None,
                 middleware: self.middleware_base.clone(),
+                store: Store::new(),
+                method_not_allowed: None,
+                guarded: Vec::new(),
+                pattern: self.full_path.clone(),
             };
             *resource = Some(new_resource);
         }
-        let resource = resource.as_mut().unwrap();
+        resource.as_mut().unwrap()
+    }
+
+    /// Allow endpoints registered through this handle to replace existing ones.
+    ///
+    /// By default, registering a second endpoint for the same path and method panics, since it
+    /// is most likely a mistake. With `overwrite(true)`, the new endpoint replaces the old one
+    /// instead:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.at("/message").post(async || "old");
+    /// app.at("/message").overwrite(true).post(async || "new");
+    /// ```
+    pub fn overwrite(&mut self, overwrite: bool) -> &mut Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Mark all endpoints of this resource as deprecated, to be removed at `sunset`.
+    ///
+    /// With the `middleware::DeprecationHeaders` middleware, responses from these endpoints carry
+    /// `Deprecation`, `Sunset` and `Link` headers pointing clients to `link` for migration
+    /// instructions.
+    ///
+    /// Panics if `link` cannot be used in a header.
+    pub fn deprecated(&mut self, sunset: chrono::DateTime<chrono::Utc>, link: &str) -> &mut Self {
+        let deprecation = Deprecation::new(sunset, link);
+        self.resource_data().store.write(deprecation);
+        self
+    }
 
-        let entry = resource.endpoints.entry(method);
-        if let std::collections::hash_map::Entry::Occupied(ep) = entry {
-            panic!("A {} endpoint already exists for this path", ep.key())
+    /// Only route requests satisfying `guard` to the endpoints registered through this handle.
+    ///
+    /// A method can have several guarded endpoints, tried in the order they were registered and
+    /// before the endpoint without a guard, if any. Requests rejected by all guards are routed as
+    /// if the guarded endpoints did not exist, except that they get no `405 Method Not Allowed`:
+    /// they go to the catch-all endpoint of the resource, if any, then to the next resource
+    /// matching the path (see `Router::at`), and otherwise to the fallback or default handler.
+    ///
+    /// ```rust,no_run
+    /// # #![feature(async_await)]
+    /// # let mut app = tide::App::new(());
+    /// app.at("/upload")
+    ///     .guard(|req| req.headers().contains_key("X-Admin-Token"))
+    ///     .post(async || "uploaded as admin");
+    /// app.at("/upload")
+    ///     .guard(|req| req.headers().contains_key("X-Token"))
+    ///     .post(async || "uploaded");
+    /// app.at("/upload").post(async || "sign in first");
+    /// ```
+    ///
+    /// Guards set on the same handle must all hold.
+    pub fn guard(&mut self, guard: impl Fn(&Request) -> bool + Send + Sync + 'static) -> &mut Self {
+        let guard: Guard = match self.guard.take() {
+            Some(previous) => Arc::new(move |req: &Request| previous(req) && guard(req)),
+            None => Arc::new(guard),
+        };
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Add an endpoint for the given HTTP method
+    pub fn method<T: Endpoint<Data, U>, U>(
+        &mut self,
+        method: http::Method,
+        ep: T,
+    ) -> EndpointHandle<'_, 'a, Data> {
+        let (path, overwrite, guard) = (self.path, self.overwrite, self.guard.clone());
+        let resource = self.resource_data();
+
+        if guard.is_none() && !overwrite && resource.endpoints.contains_key(&method) {
+            panic!(
+                "A {} endpoint already exists for path `{}`; use `Resource::overwrite(true)` \
+                 to replace it",
+                method, path
+            )
         }
 
         let endpoint = EndpointData {
             endpoint: BoxedEndpoint::new(ep),
             store: Store::new(),
+            meta: RouteMeta::default(),
+            middleware: None,
         };
 
-        entry.or_insert(endpoint)
+        let slot = match guard {
+            Some(guard) => {
+                resource.guarded.push(Guarded {
+                    method: Some(method),
+                    guard,
+                    endpoint,
+                });
+                Slot::Guarded(resource.guarded.len() - 1)
+            }
+            None => {
+                resource.endpoints.insert(method.clone(), endpoint);
+                Slot::Method(method)
+            }
+        };
+        resource.update_method_not_allowed();
+        EndpointHandle {
+            resource: self,
+            slot,
+        }
+    }
+
+    /// Add an endpoint for all HTTP methods that have no endpoint of their own
+    ///
+    /// This is useful for proxies and method-agnostic webhooks. `HEAD` requests are still served by
+    /// the `GET` endpoint if there is one.
+    pub fn any<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
+        let (path, overwrite, guard) = (self.path, self.overwrite, self.guard.clone());
+        let resource = self.resource_data();
+        if guard.is_none() && !overwrite && resource.any.is_some() {
+            panic!(
+                "A catch-all endpoint already exists for path `{}`; use \
+                 `Resource::overwrite(true)` to replace it",
+                path
+            )
+        }
+
+        let endpoint = EndpointData {
+            endpoint: BoxedEndpoint::new(ep),
+            store: Store::new(),
+            meta: RouteMeta::default(),
+            middleware: None,
+        };
+
+        let slot = match guard {
+            Some(guard) => {
+                resource.guarded.push(Guarded {
+                    method: None,
+                    guard,
+                    endpoint,
+                });
+                Slot::Guarded(resource.guarded.len() - 1)
+            }
+            None => {
+                resource.any = Some(endpoint);
+                Slot::Any
+            }
+        };
+        EndpointHandle {
+            resource: self,
+            slot,
+        }
     }
 
     /// Add an endpoint for `GET` requests
-    pub fn get<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn get<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::GET, ep)
     }
 
     /// Add an endpoint for `HEAD` requests
-    pub fn head<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn head<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::HEAD, ep)
     }
 
     /// Add an endpoint for `PUT` requests
-    pub fn put<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn put<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::PUT, ep)
     }
 
     /// Add an endpoint for `POST` requests
-    pub fn post<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn post<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::POST, ep)
     }
 
     /// Add an endpoint for `DELETE` requests
-    pub fn delete<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn delete<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::DELETE, ep)
     }
 
     /// Add an endpoint for `OPTIONS` requests
-    pub fn options<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn options<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::OPTIONS, ep)
     }
 
     /// Add an endpoint for `CONNECT` requests
-    pub fn connect<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn connect<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::CONNECT, ep)
     }
 
     /// Add an endpoint for `PATCH` requests
-    pub fn patch<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn patch<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::PATCH, ep)
     }
 
     /// Add an endpoint for `TRACE` requests
-    pub fn trace<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> &mut EndpointData<Data> {
+    pub fn trace<T: Endpoint<Data, U>, U>(&mut self, ep: T) -> EndpointHandle<'_, 'a, Data> {
         self.method(http::Method::TRACE, ep)
     }
 }
 
+impl<'a, Data: Clone + Send + Sync + 'static> Resource<'a, Data> {
+    /// Forward all requests under this path to an external `HttpService`.
+    ///
+    /// This is a shorthand for `router.mount_service(path, service)`, for use where only the
+    /// `Resource` is at hand:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(futures_api)]
+    /// # let mut app = tide::App::new(());
+    /// let legacy = |_req: http_service::Request| {
+    ///     futures::future::ok::<_, std::io::Error>(http_service::Response::new("legacy".into()))
+    /// };
+    /// app.at("/legacy").mount_service(legacy);
+    /// ```
+    ///
+    /// As with `nest`, resources already present under the path are discarded.
+    pub fn mount_service<S: HttpService>(self, service: S) {
+        self.nest(|router| router.mount_service("/", service));
+    }
+}
+
+/// A handle to an endpoint just added to a resource.
+///
+/// This can be used to add configuration items to the endpoint, or to add more endpoints to the
+/// same resource, so that all methods of a path can be registered in one expression:
+///
+/// ```rust,no_run
+/// # #![feature(async_await)]
+/// # let mut app = tide::App::new(());
+/// app.at("/message/{}")
+///     .get(async || "message")
+///     .post(async || "created");
+/// ```
+///
+/// It dereferences to the `EndpointData` of the endpoint.
+pub struct EndpointHandle<'r, 'a, Data> {
+    resource: &'r mut Resource<'a, Data>,
+    slot: Slot,
+}
+
+/// Where the endpoint of an `EndpointHandle` is kept in its resource.
+enum Slot {
+    Method(http::Method),
+    Any,
+    /// The index of the endpoint among the guarded ones.
+    Guarded(usize),
+}
+
+impl<'r, 'a, Data> EndpointHandle<'r, 'a, Data> {
+    /// Add a configuration `item` for this endpoint.
+    pub fn config<T: Any + Debug + Clone + Send + Sync>(mut self, item: T) -> Self {
+        (*self).config(item);
+        self
+    }
+
+    /// Attach the metadata `value` under `key` to this endpoint.
+    pub fn meta(mut self, key: &str, value: &str) -> Self {
+        (*self).meta(key, value);
+        self
+    }
+
+    /// Tag this endpoint with `tag`.
+    pub fn tag(mut self, tag: &str) -> Self {
+        (*self).tag(tag);
+        self
+    }
+
+    /// Add an endpoint for the given HTTP method to the same resource.
+    pub fn method<T: Endpoint<Data, U>, U>(
+        self,
+        method: http::Method,
+        ep: T,
+    ) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(method, ep)
+    }
+
+    /// Add an endpoint for all HTTP methods that have no endpoint of their own to the same
+    /// resource.
+    pub fn any<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.any(ep)
+    }
+
+    /// Add an endpoint for `GET` requests to the same resource
+    pub fn get<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::GET, ep)
+    }
+
+    /// Add an endpoint for `HEAD` requests to the same resource
+    pub fn head<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::HEAD, ep)
+    }
+
+    /// Add an endpoint for `PUT` requests to the same resource
+    pub fn put<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::PUT, ep)
+    }
+
+    /// Add an endpoint for `POST` requests to the same resource
+    pub fn post<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::POST, ep)
+    }
+
+    /// Add an endpoint for `DELETE` requests to the same resource
+    pub fn delete<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::DELETE, ep)
+    }
+
+    /// Add an endpoint for `OPTIONS` requests to the same resource
+    pub fn options<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::OPTIONS, ep)
+    }
+
+    /// Add an endpoint for `CONNECT` requests to the same resource
+    pub fn connect<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::CONNECT, ep)
+    }
+
+    /// Add an endpoint for `PATCH` requests to the same resource
+    pub fn patch<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::PATCH, ep)
+    }
+
+    /// Add an endpoint for `TRACE` requests to the same resource
+    pub fn trace<T: Endpoint<Data, U>, U>(self, ep: T) -> EndpointHandle<'r, 'a, Data> {
+        self.resource.method(http::Method::TRACE, ep)
+    }
+}
+
+impl<'r, 'a, Data> std::ops::Deref for EndpointHandle<'r, 'a, Data> {
+    type Target = EndpointData<Data>;
+
+    fn deref(&self) -> &EndpointData<Data> {
+        let resource = self.resource.table.resource().unwrap();
+        match &self.slot {
+            Slot::Method(method) => &resource.endpoints[method],
+            Slot::Any => resource.any.as_ref().unwrap(),
+            Slot::Guarded(idx) => &resource.guarded[*idx].endpoint,
+        }
+    }
+}
+
+impl<'r, 'a, Data> std::ops::DerefMut for EndpointHandle<'r, 'a, Data> {
+    fn deref_mut(&mut self) -> &mut EndpointData<Data> {
+        let resource = self.resource.table.resource_mut().as_mut().unwrap();
+        match &self.slot {
+            Slot::Method(method) => resource.endpoints.get_mut(method).unwrap(),
+            Slot::Any => resource.any.as_mut().unwrap(),
+            Slot::Guarded(idx) => &mut resource.guarded[*idx].endpoint,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use futures::{executor::block_on, future::FutureObj};
@@ -341,18 +1368,21 @@ mod tests {
         let default_handler = Arc::new(EndpointData {
             endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
             store: Store::new(),
+            meta: RouteMeta::default(),
+            middleware: None,
         });
+        let req = http::Request::builder()
+            .method(method)
+            .body(http_service::Body::empty())
+            .unwrap();
         let RouteResult {
             endpoint,
             params,
             middleware,
-        } = router.route(path, method, &default_handler);
+            pattern,
+        } = router.route(path, method, &req, &default_handler);
 
         let data = Data::default();
-        let req = http::Request::builder()
-            .method(method)
-            .body(http_service::Body::empty())
-            .unwrap();
 
         let ctx = RequestContext {
             app_data: data,
@@ -360,6 +1390,9 @@ mod tests {
             params,
             endpoint,
             next_middleware: middleware,
+            route_pattern: pattern,
+            response: None,
+            deadline: None,
         };
         let res = await!(ctx.next());
         Some(res.map(Into::into))
@@ -373,8 +1406,14 @@ mod tests {
         let default_handler = Arc::new(EndpointData {
             endpoint: BoxedEndpoint::new(async || http::status::StatusCode::NOT_FOUND),
             store: Store::new(),
+            meta: RouteMeta::default(),
+            middleware: None,
         });
-        let route_result = router.route(path, method, &default_handler);
+        let req = http::Request::builder()
+            .method(method)
+            .body(http_service::Body::empty())
+            .unwrap();
+        let route_result = router.route(path, method, &req, &default_handler);
         Some(route_result.middleware.len())
     }
 
@@ -397,6 +1436,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn route_specificity() {
+        let routes: [(&str, &'static str); 4] = [
+            ("/users/me", "me"),
+            ("/users/{id}", "user"),
+            ("/users/*", "files"),
+            ("/{collection}/{id}/posts", "posts"),
+        ];
+        let mut forward: Router<()> = Router::new();
+        let mut backward: Router<()> = Router::new();
+        for &(path, body) in routes.iter() {
+            forward.at(path).get(move || futures::future::ready(body));
+        }
+        for &(path, body) in routes.iter().rev() {
+            backward.at(path).get(move || futures::future::ready(body));
+        }
+
+        for router in &[forward, backward] {
+            for (path, expected) in &[
+                ("/users/me", "me"),
+                ("/users/42", "user"),
+                ("/users/42/avatar.png", "files"),
+                ("/teams/7/posts", "posts"),
+                ("/users/7/posts", "files"),
+            ] {
+                let res = block_on(simulate_request(router, path, &http::Method::GET)).unwrap();
+                let body = block_on(res.into_body().into_vec()).unwrap();
+                assert_eq!(&*body, expected.as_bytes(), "path {}", path);
+            }
+        }
+    }
+
     #[test]
     fn nested_static() {
         let mut router: Router<()> = Router::new();
@@ -476,6 +1547,379 @@ mod tests {
         router.at("/a/b").get(async || "duplicate");
     }
 
+    #[test]
+    fn all_verbs() {
+        let mut router: Router<()> = Router::new();
+        let mut resource = router.at("/a");
+        resource.get(async || "GET");
+        resource.head(async || "HEAD");
+        resource.put(async || "PUT");
+        resource.post(async || "POST");
+        resource.delete(async || "DELETE");
+        resource.options(async || "OPTIONS");
+        resource.connect(async || "CONNECT");
+        resource.patch(async || "PATCH");
+        resource.trace(async || "TRACE");
+
+        for method in &[
+            http::Method::GET,
+            http::Method::HEAD,
+            http::Method::PUT,
+            http::Method::POST,
+            http::Method::DELETE,
+            http::Method::OPTIONS,
+            http::Method::CONNECT,
+            http::Method::PATCH,
+            http::Method::TRACE,
+        ] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, method.as_str().as_bytes());
+        }
+    }
+
+    #[test]
+    fn any_method() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "GET");
+        router.at("/a").any(async || "any");
+
+        for (method, expected) in &[
+            (http::Method::GET, "GET"),
+            (http::Method::HEAD, "GET"),
+            (http::Method::POST, "any"),
+            (http::Method::PATCH, "any"),
+        ] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_any_fails() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").any(async || "");
+        router.at("/a").any(async || "duplicate");
+    }
+
+    #[test]
+    fn mounted_service() {
+        let mut router: Router<()> = Router::new();
+        router.at("/").get(async || "/");
+        router.mount_service("/svc/", |_req: http_service::Request| {
+            futures::future::ok::<_, std::io::Error>(http_service::Response::new("svc".into()))
+        });
+
+        for (path, method) in &[
+            ("/svc", http::Method::GET),
+            ("/svc/a", http::Method::POST),
+            ("/svc/a/b", http::Method::DELETE),
+        ] {
+            let res = block_on(simulate_request(&router, path, method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, &*b"svc");
+        }
+
+        let res = block_on(simulate_request(&router, "/", &http::Method::GET)).unwrap();
+        let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+        assert_eq!(&*body, &*b"/");
+    }
+
+    #[test]
+    fn mounted_service_on_resource() {
+        let mut router: Router<()> = Router::new();
+        router.at("/legacy").mount_service(|_req: http_service::Request| {
+            futures::future::ok::<_, std::io::Error>(http_service::Response::new("legacy".into()))
+        });
+
+        for path in &["/legacy", "/legacy/a/b"] {
+            let res = block_on(simulate_request(&router, path, &http::Method::PUT)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, &*b"legacy");
+        }
+    }
+
+    #[test]
+    fn nested_fallback() {
+        let mut router: Router<()> = Router::new();
+        router.at("/").get(async || "/");
+        router.at("/api").nest(|router| {
+            router.default_handler(async || "api fallback");
+            router.at("/users").get(async || "/api/users");
+            router.at("/v2").nest(|router| {
+                router.default_handler(async || "v2 fallback");
+            });
+        });
+
+        for (path, expected) in &[
+            ("/api/users", "/api/users"),
+            ("/api", "api fallback"),
+            ("/api/unknown/deep", "api fallback"),
+            ("/api/users/unknown", "api fallback"),
+            ("/api/v2/unknown", "v2 fallback"),
+        ] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, expected.as_bytes());
+        }
+
+        let res = block_on(simulate_request(&router, "/api/users", &http::Method::POST)).unwrap();
+        assert_eq!(res.status(), 405);
+
+        let res = block_on(simulate_request(&router, "/other", &http::Method::GET)).unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[test]
+    fn scope() {
+        let mut router: Router<()> = Router::new();
+        router.scope("/admin", |admin| {
+            admin.middleware(passthrough_middleware);
+            admin.at("/users").get(async || "/admin/users");
+        });
+        router.at("/public").get(async || "/public");
+
+        let res = block_on(simulate_request(&router, "/admin/users", &http::Method::GET)).unwrap();
+        let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+        assert_eq!(&*body, &*b"/admin/users");
+        let count = route_middleware_count(&router, "/admin/users", &http::Method::GET);
+        assert_eq!(count, Some(1));
+        let count = route_middleware_count(&router, "/public", &http::Method::GET);
+        assert_eq!(count, Some(0));
+    }
+
+    #[test]
+    fn guards() {
+        let mut router: Router<()> = Router::new();
+        router
+            .at("/upload")
+            .guard(|req| req.method() == http::Method::POST)
+            .post(async || "guarded");
+        router
+            .at("/upload")
+            .guard(|req| req.headers().contains_key("X-Token"))
+            .put(async || "guarded");
+        router
+            .at("/any")
+            .guard(|req| req.headers().contains_key("X-Token"))
+            .post(async || "guarded");
+        router.at("/any").any(async || "any");
+
+        let res = block_on(simulate_request(&router, "/upload", &http::Method::POST)).unwrap();
+        assert_eq!(res.status(), 200);
+
+        // `simulate_request` sends no headers, so the `X-Token` guards reject the requests.
+        let res = block_on(simulate_request(&router, "/upload", &http::Method::PUT)).unwrap();
+        assert_eq!(res.status(), 404);
+        let res = block_on(simulate_request(&router, "/any", &http::Method::POST)).unwrap();
+        let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+        assert_eq!(&*body, &*b"any");
+    }
+
+    #[test]
+    fn guards_fall_through() {
+        let mut router: Router<()> = Router::new();
+        router.at("/upload").guard(|_| false).post(async || "never");
+        router.at("/upload").guard(|_| true).post(async || "second");
+        router.at("/upload").post(async || "unguarded");
+        router.at("/items/me").guard(|_| false).get(async || "me");
+        router.at("/items/{id}").get(async || "item");
+
+        for (path, method, expected) in &[
+            ("/upload", http::Method::POST, "second"),
+            ("/items/me", http::Method::GET, "item"),
+            ("/items/42", http::Method::GET, "item"),
+        ] {
+            let res = block_on(simulate_request(&router, path, method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, expected.as_bytes(), "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn method_not_allowed_lists_methods_once() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "get");
+        router.at("/a").overwrite(true).get(async || "new get");
+        router.at("/a").guard(|_| true).post(async || "guarded post");
+        router.at("/a").post(async || "post");
+
+        let res = block_on(simulate_request(&router, "/a", &http::Method::PUT)).unwrap();
+        assert_eq!(res.status(), 405);
+        assert_eq!(res.headers()["Allow"], "GET, HEAD, POST");
+    }
+
+    #[test]
+    fn method_not_allowed() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "get");
+        router.at("/a").delete(async || "delete");
+        router.at("/b").any(async || "any");
+
+        let res = block_on(simulate_request(&router, "/a", &http::Method::POST)).unwrap();
+        assert_eq!(res.status(), 405);
+        assert_eq!(res.headers()["Allow"], "DELETE, GET, HEAD");
+
+        let res = block_on(simulate_request(&router, "/b", &http::Method::POST)).unwrap();
+        assert_eq!(res.status(), 200);
+
+        let res = block_on(simulate_request(&router, "/c", &http::Method::POST)).unwrap();
+        assert_eq!(res.status(), 404);
+    }
+
+    #[test]
+    fn overwrite_endpoint() {
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "old");
+        router.at("/a").any(async || "old");
+        router.at("/a").overwrite(true).get(async || "new");
+        router.at("/a").overwrite(true).any(async || "new");
+
+        for method in &[http::Method::GET, http::Method::POST] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).expect("Reading body should succeed");
+            assert_eq!(&*body, &*b"new");
+        }
+    }
+
+    #[test]
+    fn merge() {
+        let mut auth: Router<()> = Router::new();
+        auth.middleware(passthrough_middleware);
+        auth.at("/login").name("login").post(async || "login");
+        auth.at("/users/{id}").get(async || "user");
+        auth.default_handler(async || "auth fallback");
+
+        let mut router: Router<()> = Router::new();
+        router.middleware(passthrough_middleware);
+        router.at("/").get(async || "/");
+        router.at("/api").nest(|api| {
+            api.at("/users").get(async || "users");
+            api.merge(auth);
+        });
+
+        for (path, method, expected) in &[
+            ("/", http::Method::GET, "/"),
+            ("/api/users", http::Method::GET, "users"),
+            ("/api/users/1", http::Method::GET, "user"),
+            ("/api/login", http::Method::POST, "login"),
+            ("/api/unknown", http::Method::GET, "auth fallback"),
+        ] {
+            let res = block_on(simulate_request(&router, path, method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+        assert_eq!(router.names["login"], "/api/login");
+
+        let found = route_middleware_count(&router, "/api/login", &http::Method::POST);
+        assert_eq!(found, Some(2));
+    }
+
+    #[test]
+    fn merge_shared_resource() {
+        let mut other: Router<()> = Router::new();
+        other.middleware(passthrough_middleware);
+        other.at("/a").post(async || "other");
+
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "router");
+        router.merge(other);
+
+        for (method, expected) in &[(http::Method::GET, "router"), (http::Method::POST, "other")] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+        assert_eq!(route_middleware_count(&router, "/a", &http::Method::GET), Some(0));
+        assert_eq!(route_middleware_count(&router, "/a", &http::Method::POST), Some(1));
+
+        let res = block_on(simulate_request(&router, "/a", &http::Method::PUT)).unwrap();
+        assert_eq!(res.status(), 405);
+        assert_eq!(res.headers()["Allow"], "GET, HEAD, POST");
+    }
+
+    #[test]
+    #[should_panic(expected = "Both merged routers have a GET endpoint for path `/a`")]
+    fn merge_conflict() {
+        let mut other: Router<()> = Router::new();
+        other.at("/a").get(async || "other");
+
+        let mut router: Router<()> = Router::new();
+        router.at("/a").get(async || "router");
+        router.merge(other);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot merge a router matching paths case-insensitively")]
+    fn merge_case_insensitive_into_sensitive() {
+        let mut other: Router<()> = Router::new();
+        other.set_case_insensitive(true);
+        other.at("/a").get(async || "other");
+
+        let mut router: Router<()> = Router::new();
+        router.merge(other);
+    }
+
+    #[test]
+    fn chained_methods() {
+        use crate::ExtractConfiguration;
+        async fn endpoint(
+            ExtractConfiguration(x): ExtractConfiguration<&'static str>,
+        ) -> &'static str {
+            x.unwrap_or("none")
+        }
+
+        let mut router: Router<()> = Router::new();
+        router
+            .at("/a")
+            .get(endpoint)
+            .config("get")
+            .post(endpoint)
+            .any(endpoint);
+
+        for (method, expected) in &[
+            (http::Method::GET, "get"),
+            (http::Method::POST, "none"),
+            (http::Method::PUT, "none"),
+        ] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            assert_eq!(res.status(), 200);
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn route_meta() {
+        fn describe(ctx: RequestContext<()>) -> FutureObj<Response> {
+            let meta = ctx.route_meta();
+            let description = format!("{:?} {:?}", meta.get("auth"), meta.tags());
+            FutureObj::new(Box::new(future::ready(http::Response::new(description.into()))))
+        }
+
+        let mut router: Router<()> = Router::new();
+        router.middleware(describe);
+        router
+            .at("/a")
+            .get(async || "")
+            .tag("public")
+            .tag("cached")
+            .tag("public")
+            .post(async || "")
+            .meta("auth", "required");
+
+        for (method, expected) in &[
+            (http::Method::GET, r#"None ["public", "cached"]"#),
+            (http::Method::POST, r#"Some("required") []"#),
+        ] {
+            let res = block_on(simulate_request(&router, "/a", method)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+    }
+
     #[test]
     fn simple_middleware() {
         let mut router: Router<()> = Router::new();
@@ -551,6 +1995,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scoped_data() {
+        async fn endpoint(data: AppData<usize>) -> String {
+            data.0.to_string()
+        }
+
+        let mut router: Router<usize> = Router::new();
+        router.at("/").get(endpoint);
+        router.at("/a").nest(|router| {
+            router.data(1);
+            router.at("/").get(endpoint);
+            router.at("/b").nest(|router| {
+                router.data(2);
+                router.at("/").get(endpoint);
+            });
+            router.at("/c").nest(|router| {
+                router.at("/").get(endpoint);
+            });
+        });
+        router.apply_default_config();
+
+        for (path, expected) in &[("/", "0"), ("/a", "1"), ("/a/b", "2"), ("/a/c", "1")] {
+            let res = block_on(simulate_request(&router, path, &http::Method::GET)).unwrap();
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes(), "path {}", path);
+        }
+    }
+
     #[test]
     fn configuration() {
         use crate::ExtractConfiguration;
@@ -0,0 +1,54 @@
+//! Sampling a fraction of a sequence of events, such as requests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Picks a fraction of the events it is asked about, spread evenly over their sequence.
+///
+/// With a rate of `0.25`, every fourth event is picked, rather than a random quarter: the
+/// outcome is deterministic and the share is exact over any run of events.
+#[derive(Debug, Default)]
+pub(crate) struct Sampler {
+    seen: AtomicUsize,
+}
+
+impl Sampler {
+    /// Decide whether the next event is picked, for the fraction `rate` of events.
+    ///
+    /// Rates are clamped to `0.0..=1.0`, NaN counting as `0.0`.
+    pub(crate) fn sample(&self, rate: f64) -> bool {
+        let rate = clamp_rate(rate);
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * rate).floor() > (n * rate).floor()
+    }
+}
+
+/// Clamp a sampling `rate` to `0.0..=1.0`, NaN counting as `0.0`.
+pub(crate) fn clamp_rate(rate: f64) -> f64 {
+    rate.max(0.0).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread() {
+        let sampler = Sampler::default();
+        let picked: Vec<bool> = (0..8).map(|_| sampler.sample(0.25)).collect();
+        assert_eq!(
+            picked,
+            [false, false, false, true, false, false, false, true]
+        );
+
+        let sampler = Sampler::default();
+        assert_eq!((0..1000).filter(|_| sampler.sample(0.01)).count(), 10);
+    }
+
+    #[test]
+    fn clamped() {
+        let sampler = Sampler::default();
+        assert!((0..10).all(|_| sampler.sample(2.0)));
+        assert!(!(0..10).any(|_| sampler.sample(-1.0)));
+        assert!(!(0..10).any(|_| sampler.sample(std::f64::NAN)));
+    }
+}
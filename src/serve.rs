@@ -1,4 +1,1573 @@
-// Use hyper to serve the given HttpService at the given address
-pub(crate) fn serve<S: http_service::HttpService>(s: S, addr: std::net::SocketAddr) {
-    http_service_hyper::serve(s, addr);
+//! Serving an `HttpService` with hyper.
+
+use futures::{
+    compat::{Compat, Compat01As03},
+    future::{self, FutureObj},
+    prelude::*,
+};
+use futures01::{
+    future::Shared, sync::oneshot, task::AtomicTask, Async, Future as Future01, Poll,
+    Stream as Stream01,
+};
+use http_service::{Body, HttpService};
+use std::io::{self, Read, Write};
+use std::net::{self as std_net, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::reactor::Handle;
+use tokio::runtime;
+use tokio::timer::Delay;
+
+use crate::{
+    configuration::{Configuration, HttpVersions},
+    request::ConnectionInfo,
+    Error,
+};
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use crate::tls::{Acceptor, TlsConfig};
+
+// Wrapper type to allow us to provide a blanket `MakeService` impl
+struct WrapHttpService<H> {
+    service: Arc<H>,
+    requests: Option<Arc<Limit>>,
+    drain: Arc<Drain>,
+    head_limits: HeadLimits,
+}
+
+impl<H> WrapHttpService<H> {
+    fn new(service: H, configuration: &Configuration) -> WrapHttpService<H> {
+        WrapHttpService {
+            service: Arc::new(service),
+            requests: configuration.max_requests.map(Limit::new),
+            drain: Arc::default(),
+            head_limits: HeadLimits {
+                headers: configuration.max_headers,
+                uri_length: configuration.max_uri_length,
+            },
+        }
+    }
+}
+
+/// Limits on the request line and headers, beyond those enforced by hyper.
+#[derive(Clone, Copy)]
+struct HeadLimits {
+    headers: usize,
+    uri_length: Option<usize>,
+}
+
+impl HeadLimits {
+    /// The status to reject `req` with, if it exceeds a limit.
+    fn check<B>(&self, req: &http::Request<B>) -> Option<http::StatusCode> {
+        let uri_length = req.uri().path_and_query().map_or(0, |p| p.as_str().len());
+        if self.uri_length.map_or(false, |max| uri_length > max) {
+            Some(http::StatusCode::URI_TOO_LONG)
+        } else if req.headers().len() > self.headers {
+            Some(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for HeadLimits {
+    fn default() -> HeadLimits {
+        HeadLimits {
+            headers: usize::max_value(),
+            uri_length: None,
+        }
+    }
+}
+
+// Wrapper type to allow us to provide a blanket `Service` impl
+struct WrapConnection<H: HttpService> {
+    service: Arc<H>,
+    connection: H::Connection,
+    info: Arc<Mutex<ConnectionInfo>>,
+    activity: Arc<Activity>,
+    requests: Option<Arc<Limit>>,
+    drain: Arc<Drain>,
+    head_limits: HeadLimits,
+}
+
+/// The progress of a graceful shutdown, shared by the connections of a server.
+struct Drain {
+    started: AtomicBool,
+    // The requests whose response has not been sent completely.
+    in_flight: AtomicUsize,
+    // Fired to abort the requests still in flight once the shutdown timeout has elapsed.
+    abort: Mutex<Option<oneshot::Sender<()>>>,
+    aborted: Shared<oneshot::Receiver<()>>,
+}
+
+impl Default for Drain {
+    fn default() -> Drain {
+        let (abort, aborted) = oneshot::channel();
+        Drain {
+            started: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            abort: Mutex::new(Some(abort)),
+            aborted: aborted.shared(),
+        }
+    }
+}
+
+impl Drain {
+    /// Abort the requests still in flight, making their connections fail.
+    fn abort(&self) {
+        if let Some(abort) = self.abort.lock().unwrap().take() {
+            let _ = abort.send(());
+        }
+    }
+}
+
+/// The future of a response, as handed to hyper.
+type HyperResponse =
+    Abortable<Compat<FutureObj<'static, io::Result<http::Response<hyper_server::Body>>>>>;
+
+/// Answer a request with `res` right away.
+fn respond_now(res: http::Response<hyper_server::Body>, drain: &Drain) -> HyperResponse {
+    let res: FutureObj<'static, io::Result<_>> = FutureObj::new(Box::new(future::ready(Ok(res))));
+    Abortable::new(res.compat(), drain)
+}
+
+/// A response, or its body, failing once the server aborts the requests still in flight.
+struct Abortable<T> {
+    inner: T,
+    aborted: Shared<oneshot::Receiver<()>>,
+}
+
+impl<T> Abortable<T> {
+    fn new(inner: T, drain: &Drain) -> Abortable<T> {
+        Abortable {
+            inner,
+            aborted: drain.aborted.clone(),
+        }
+    }
+
+    fn check(&mut self) -> Result<(), io::Error> {
+        match self.aborted.poll() {
+            Ok(Async::NotReady) => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "aborted by the server shutdown",
+            )),
+        }
+    }
+}
+
+impl<F: Future01<Error = io::Error>> Future01 for Abortable<F> {
+    type Item = F::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<F::Item, io::Error> {
+        self.check()?;
+        self.inner.poll()
+    }
+}
+
+impl<S: Stream01<Error = io::Error>> Stream01 for Abortable<S> {
+    type Item = S::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, io::Error> {
+        self.check()?;
+        self.inner.poll()
+    }
+}
+
+/// A request counted as in flight by a `Drain`, until dropped.
+struct InFlight(Arc<Drain>);
+
+impl InFlight {
+    fn new(drain: &Arc<Drain>) -> InFlight {
+        drain.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(drain.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A cap on how many connections or requests the server handles at once.
+struct Limit {
+    max: usize,
+    current: AtomicUsize,
+    // The tasks waiting for the count to drop below `max`, one slot for each task sharing it.
+    waiting: Vec<AtomicTask>,
+}
+
+impl Limit {
+    fn new(max: usize) -> Arc<Limit> {
+        Limit::shared(max, 1)
+    }
+
+    /// A limit shared by `waiters` tasks, each waiting in a slot of its own.
+    fn shared(max: usize, waiters: usize) -> Arc<Limit> {
+        Arc::new(Limit {
+            max,
+            current: AtomicUsize::new(0),
+            waiting: (0..waiters.max(1)).map(|_| AtomicTask::new()).collect(),
+        })
+    }
+
+    /// Wake the current task, waiting in `slot`, once the count drops.
+    fn register(&self, slot: usize) {
+        self.waiting[slot].register();
+    }
+
+    fn is_full(&self) -> bool {
+        self.current.load(Ordering::SeqCst) >= self.max
+    }
+
+    /// Count one more, unless the limit is reached.
+    fn try_acquire(limit: &Arc<Limit>) -> Option<Permit> {
+        let mut current = limit.current.load(Ordering::SeqCst);
+        while current < limit.max {
+            match limit.current.compare_exchange(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Some(Permit(limit.clone())),
+                Err(actual) => current = actual,
+            }
+        }
+        None
+    }
+}
+
+/// One connection or request counted towards a `Limit`, until dropped.
+struct Permit(Arc<Limit>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::SeqCst);
+        for waiting in &self.0.waiting {
+            waiting.notify();
+        }
+    }
+}
+
+/// What a connection is waiting for, shared between it and the service handling its requests,
+/// to tell which of its timeouts applies.
+#[derive(Default)]
+struct Activity {
+    // The requests started on the connection so far.
+    started: AtomicUsize,
+    // The requests whose response has not been sent completely.
+    responding: AtomicUsize,
+    // Whether an endpoint is waiting for more of a request body.
+    awaiting_body: AtomicBool,
+}
+
+/// A request counted as responding by an `Activity`, until dropped.
+struct Responding(Arc<Activity>);
+
+impl Responding {
+    fn new(activity: &Arc<Activity>) -> Responding {
+        activity.started.fetch_add(1, Ordering::SeqCst);
+        activity.responding.fetch_add(1, Ordering::SeqCst);
+        Responding(activity.clone())
+    }
+}
+
+impl Drop for Responding {
+    fn drop(&mut self) {
+        self.0.responding.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A request body, recording on its connection whether the endpoint is waiting for more of it.
+struct RequestBody {
+    body: hyper_server::Body,
+    activity: Arc<Activity>,
+}
+
+impl Stream01 for RequestBody {
+    type Item = hyper_server::Chunk;
+    type Error = hyper_server::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let polled = self.body.poll();
+        let awaiting = match polled {
+            Ok(Async::NotReady) => true,
+            _ => false,
+        };
+        self.activity.awaiting_body.store(awaiting, Ordering::SeqCst);
+        polled
+    }
+}
+
+impl Drop for RequestBody {
+    fn drop(&mut self) {
+        self.activity.awaiting_body.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A connection accepted by the server, carrying its `ConnectionInfo` and `Activity`.
+trait Connected {
+    fn info(&self) -> &Arc<Mutex<ConnectionInfo>>;
+    fn activity(&self) -> &Arc<Activity>;
+}
+
+impl<'a, H, C> hyper_server::service::MakeService<&'a C> for WrapHttpService<H>
+where
+    H: HttpService,
+    C: Connected,
+{
+    type ReqBody = hyper_server::Body;
+    type ResBody = hyper_server::Body;
+    type Error = io::Error;
+    type Service = WrapConnection<H>;
+    type Future = Compat<FutureObj<'static, Result<Self::Service, Self::Error>>>;
+    type MakeError = io::Error;
+
+    fn make_service(&mut self, conn: &'a C) -> Self::Future {
+        let service = self.service.clone();
+        let requests = self.requests.clone();
+        let drain = self.drain.clone();
+        let head_limits = self.head_limits;
+        let info = conn.info().clone();
+        let activity = conn.activity().clone();
+        let error = io::Error::from(io::ErrorKind::Other);
+        FutureObj::new(Box::new(
+            async move {
+                let connection = await!(service.connect().into_future()).map_err(|_| error)?;
+                Ok(WrapConnection {
+                    service,
+                    connection,
+                    info,
+                    activity,
+                    requests,
+                    drain,
+                    head_limits,
+                })
+            },
+        ))
+        .compat()
+    }
+}
+
+impl<H> hyper_server::service::Service for WrapConnection<H>
+where
+    H: HttpService,
+{
+    type ReqBody = hyper_server::Body;
+    type ResBody = hyper_server::Body;
+    type Error = io::Error;
+    type Future = HyperResponse;
+
+    fn call(&mut self, req: http::Request<hyper_server::Body>) -> Self::Future {
+        let version = req.version();
+        // Requests arriving on open connections once the shutdown started are turned away.
+        if self.drain.started.load(Ordering::SeqCst) {
+            let mut res = busy();
+            close_after(&mut res, version);
+            return respond_now(res, &self.drain);
+        }
+        if let Some(status) = self.head_limits.check(&req) {
+            return respond_now(rejected(status), &self.drain);
+        }
+        let permit = match &self.requests {
+            Some(requests) => match Limit::try_acquire(requests) {
+                Some(permit) => Some(permit),
+                None => return respond_now(busy(), &self.drain),
+            },
+            None => None,
+        };
+        let in_flight = InFlight::new(&self.drain);
+        let responding = Responding::new(&self.activity);
+        let activity = self.activity.clone();
+        let error = io::Error::from(io::ErrorKind::Other);
+        let mut req = req.map(|body| {
+            let body = RequestBody { body, activity };
+            let stream = Compat01As03::new(body).map(|c| match c {
+                Ok(chunk) => Ok(chunk.into_bytes()),
+                Err(e) => Err(io::Error::new(io::ErrorKind::Other, e)),
+            });
+            Body::from_stream(stream)
+        });
+        // By the time hyper has parsed a request, any PROXY protocol header has been consumed, so
+        // the connection info is final.
+        let info = self.info.lock().unwrap().clone();
+        req.extensions_mut().insert(info);
+        let fut = self.service.respond(&mut self.connection, req);
+        let drain = self.drain.clone();
+
+        let res = FutureObj::new(Box::new(
+            async move {
+                let mut res: http::Response<_> = await!(fut.into_future()).map_err(|_| error)?;
+                if in_flight.0.started.load(Ordering::SeqCst) {
+                    close_after(&mut res, version);
+                }
+                Ok(res.map(move |body| {
+                    // The request counts towards the limit, and as in flight, until its response
+                    // body is sent.
+                    let body = body.compat().map(move |chunk| {
+                        let _ = (&permit, &in_flight, &responding);
+                        chunk
+                    });
+                    hyper_server::Body::wrap_stream(Abortable::new(body, &drain))
+                }))
+            },
+        ));
+        Abortable::new(res.compat(), &self.drain)
+    }
+}
+
+/// An `HttpService` as a hyper `Service`, handling the requests of one connection.
+///
+/// Created by `Server::into_hyper_service`, to embed an app in a hyper server set up by hand,
+/// or call it like hyper would without a socket. As hyper creates a service per connection,
+/// give it a fresh clone of the server each time:
+///
+/// ```rust, no_run
+/// # #![feature(async_await, futures_api)]
+/// # extern crate hyper_server as hyper;
+/// use futures01::Future;
+///
+/// let mut app = tide::App::new(());
+/// app.at("/").get(async || "Hello, world!");
+/// let server = app.into_http_service();
+///
+/// let addr = ([127, 0, 0, 1], 8181).into();
+/// let hyper = hyper::Server::bind(&addr)
+///     .serve(move || Ok::<_, std::io::Error>(server.clone().into_hyper_service()));
+/// tokio::run(hyper.map_err(|e| eprintln!("{}", e)));
+/// ```
+pub struct HyperService<H: HttpService> {
+    inner: WrapConnection<H>,
+}
+
+impl<H: HttpService<Connection = ()>> HyperService<H> {
+    pub(crate) fn new(service: H) -> HyperService<H> {
+        HyperService {
+            inner: WrapConnection {
+                service: Arc::new(service),
+                connection: (),
+                info: Arc::new(Mutex::new(ConnectionInfo::new(None, None))),
+                activity: Arc::default(),
+                requests: None,
+                drain: Arc::default(),
+                head_limits: HeadLimits::default(),
+            },
+        }
+    }
+
+    /// Report `info` as the connection the requests arrived on, e.g. with the remote address of
+    /// the hyper `AddrStream`. By default, no addresses are known.
+    pub fn connection_info(self, info: ConnectionInfo) -> Self {
+        *self.inner.info.lock().unwrap() = info;
+        self
+    }
+}
+
+impl<H: HttpService> hyper_server::service::Service for HyperService<H> {
+    type ReqBody = hyper_server::Body;
+    type ResBody = hyper_server::Body;
+    type Error = io::Error;
+    type Future = <WrapConnection<H> as hyper_server::service::Service>::Future;
+
+    fn call(&mut self, req: http::Request<hyper_server::Body>) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+/// Ask an HTTP/1 client to close the connection after `res`. HTTP/2 connections are closed by
+/// hyper, with a `GOAWAY` frame.
+fn close_after<B>(res: &mut http::Response<B>, version: http::Version) {
+    if version < http::Version::HTTP_2 {
+        res.headers_mut().insert(
+            http::header::CONNECTION,
+            http::header::HeaderValue::from_static("close"),
+        );
+    }
+}
+
+/// The response to requests beyond the limit of the `Configuration`.
+fn busy() -> http::Response<hyper_server::Body> {
+    let mut res = rejected(http::StatusCode::SERVICE_UNAVAILABLE);
+    res.headers_mut().insert(
+        http::header::RETRY_AFTER,
+        http::header::HeaderValue::from_static("1"),
+    );
+    res
+}
+
+/// The response to requests rejected before reaching the app, with the generic body of `status`.
+fn rejected(status: http::StatusCode) -> http::Response<hyper_server::Body> {
+    let reason = status.canonical_reason().unwrap_or("");
+    http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(hyper_server::Body::from(reason))
+        .unwrap()
+}
+
+/// An accepted TCP connection, optionally prefixed by a PROXY protocol header.
+struct Connection {
+    stream: TcpStream,
+    info: Arc<Mutex<ConnectionInfo>>,
+    // The PROXY protocol header read so far, or `None` once it has been consumed (or if the
+    // protocol is disabled).
+    proxy_header: Option<Vec<u8>>,
+    activity: Arc<Activity>,
+    // The number of requests started when the client began sending the one read now, if any.
+    reading_head: Option<usize>,
+    read_timeout: Timeout,
+    idle_timeout: Timeout,
+    write_timeout: Timeout,
+    _permit: Option<Permit>,
+}
+
+impl Connection {
+    fn new(accepted: Accepted, configuration: &Configuration) -> Connection {
+        let Accepted {
+            stream,
+            peer_addr,
+            permit,
+        } = accepted;
+        let info = ConnectionInfo::new(Some(peer_addr), stream.local_addr().ok());
+        Connection {
+            stream,
+            info: Arc::new(Mutex::new(info)),
+            proxy_header: if configuration.proxy_protocol {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            activity: Arc::default(),
+            reading_head: None,
+            read_timeout: Timeout::new(configuration.read_timeout),
+            idle_timeout: Timeout::new(configuration.idle_timeout),
+            write_timeout: Timeout::new(configuration.write_timeout),
+            _permit: permit,
+        }
+    }
+
+    /// Read the PROXY protocol header without consuming any bytes after it.
+    fn read_proxy_header(&mut self) -> io::Result<()> {
+        while let Some(header) = self.proxy_header.as_mut() {
+            match parse_proxy_header(header)? {
+                ProxyHeader::Incomplete(needed) => {
+                    let start = header.len();
+                    header.resize(start + needed, 0);
+                    let read = match self.stream.read(&mut header[start..]) {
+                        Ok(read) => read,
+                        Err(e) => {
+                            header.truncate(start);
+                            return Err(e);
+                        }
+                    };
+                    header.truncate(start + read);
+                    if read == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                ProxyHeader::Complete(addrs) => {
+                    if let Some((peer_addr, local_addr)) = addrs {
+                        *self.info.lock().unwrap() =
+                            ConnectionInfo::new(Some(peer_addr), Some(local_addr));
+                    }
+                    self.proxy_header = None;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Connected for Connection {
+    fn info(&self) -> &Arc<Mutex<ConnectionInfo>> {
+        &self.info
+    }
+
+    fn activity(&self) -> &Arc<Activity> {
+        &self.activity
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self
+            .read_proxy_header()
+            .and_then(|()| self.stream.read(buf));
+
+        // The read timeout applies while a request is being received, the idle timeout while
+        // waiting for the next one, and neither while a response is being produced.
+        let started = self.activity.started.load(Ordering::SeqCst);
+        let responding = self.activity.responding.load(Ordering::SeqCst) > 0;
+        if self.reading_head.map_or(false, |since| since != started) {
+            self.reading_head = None;
+        }
+        if let Ok(read) = &result {
+            if *read > 0 && !responding {
+                self.reading_head.get_or_insert(started);
+            }
+        }
+        let (applies, other) = if self.activity.awaiting_body.load(Ordering::SeqCst) {
+            (Some(&mut self.read_timeout), &mut self.idle_timeout)
+        } else if responding {
+            self.read_timeout.reset();
+            (None, &mut self.idle_timeout)
+        } else if self.reading_head.is_some() {
+            (Some(&mut self.read_timeout), &mut self.idle_timeout)
+        } else {
+            (Some(&mut self.idle_timeout), &mut self.read_timeout)
+        };
+        other.reset();
+        match applies {
+            Some(timeout) => timeout.check(result),
+            None => result,
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.stream.write(buf);
+        self.write_timeout.check(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl AsyncRead for Connection {}
+
+impl AsyncWrite for Connection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        AsyncWrite::shutdown(&mut self.stream)
+    }
+}
+
+/// A limit on how long I/O in one direction of a connection may be blocked.
+struct Timeout {
+    duration: Option<Duration>,
+    // Armed when the I/O first blocks, and disarmed once it makes progress again.
+    deadline: Option<Delay>,
+}
+
+impl Timeout {
+    fn new(duration: Option<Duration>) -> Timeout {
+        Timeout {
+            duration,
+            deadline: None,
+        }
+    }
+
+    /// Disarm the timeout, as the I/O is not waited for anymore.
+    fn reset(&mut self) {
+        self.deadline = None;
+    }
+
+    /// Pass on the `result` of an I/O operation, unless it has been blocked for too long.
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        let blocked = match &result {
+            Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+            Ok(_) => false,
+        };
+        if !blocked {
+            self.deadline = None;
+            return result;
+        }
+        if let Some(duration) = self.duration {
+            let deadline = self
+                .deadline
+                .get_or_insert_with(|| Delay::new(Instant::now() + duration));
+            if let Ok(Async::Ready(())) = deadline.poll() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+        }
+        result
+    }
+}
+
+/// The state of parsing a PROXY protocol header.
+#[derive(Debug, PartialEq)]
+enum ProxyHeader {
+    /// At least this many more bytes are needed.
+    Incomplete(usize),
+    /// The header is complete, carrying the original client and server addresses if the proxy
+    /// sent any.
+    Complete(Option<(SocketAddr, SocketAddr)>),
+}
+
+const PROXY_V1_PREFIX: &[u8] = b"PROXY ";
+const PROXY_V1_MAX_LEN: usize = 107;
+const PROXY_V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+fn invalid_header(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parse a PROXY protocol (v1 or v2) header from the start of a connection.
+fn parse_proxy_header(buf: &[u8]) -> io::Result<ProxyHeader> {
+    if buf.len() < PROXY_V1_PREFIX.len() {
+        Ok(ProxyHeader::Incomplete(PROXY_V1_PREFIX.len() - buf.len()))
+    } else if buf.starts_with(PROXY_V1_PREFIX) {
+        parse_proxy_v1(buf)
+    } else if PROXY_V2_SIGNATURE.starts_with(&buf[..PROXY_V1_PREFIX.len()]) {
+        parse_proxy_v2(buf)
+    } else {
+        Err(invalid_header("missing PROXY protocol header"))
+    }
+}
+
+fn parse_proxy_v1(buf: &[u8]) -> io::Result<ProxyHeader> {
+    if !buf.ends_with(b"\r\n") {
+        return if buf.len() < PROXY_V1_MAX_LEN {
+            Ok(ProxyHeader::Incomplete(1))
+        } else {
+            Err(invalid_header("PROXY protocol header too long"))
+        };
+    }
+
+    let line = std::str::from_utf8(&buf[PROXY_V1_PREFIX.len()..buf.len() - 2])
+        .map_err(|_| invalid_header("invalid PROXY protocol header"))?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields[0] {
+        "UNKNOWN" => Ok(ProxyHeader::Complete(None)),
+        "TCP4" | "TCP6" if fields.len() == 5 => {
+            let addr = |ip: &str, port: &str| -> Option<SocketAddr> {
+                Some(SocketAddr::new(ip.parse::<IpAddr>().ok()?, port.parse().ok()?))
+            };
+            match (addr(fields[1], fields[3]), addr(fields[2], fields[4])) {
+                (Some(src), Some(dst)) => Ok(ProxyHeader::Complete(Some((src, dst)))),
+                _ => Err(invalid_header("invalid PROXY protocol address")),
+            }
+        }
+        _ => Err(invalid_header("invalid PROXY protocol header")),
+    }
+}
+
+fn parse_proxy_v2(buf: &[u8]) -> io::Result<ProxyHeader> {
+    const HEADER_LEN: usize = 16;
+    if buf.len() < HEADER_LEN {
+        return Ok(ProxyHeader::Incomplete(HEADER_LEN - buf.len()));
+    }
+    if !buf.starts_with(PROXY_V2_SIGNATURE) || buf[12] >> 4 != 2 {
+        return Err(invalid_header("invalid PROXY protocol header"));
+    }
+    let len = HEADER_LEN + ((buf[14] as usize) << 8 | buf[15] as usize);
+    if buf.len() < len {
+        return Ok(ProxyHeader::Incomplete(len - buf.len()));
+    }
+
+    let addrs = &buf[HEADER_LEN..len];
+    let port = |bytes: &[u8]| (bytes[0] as u16) << 8 | bytes[1] as u16;
+    match (buf[12] & 0x0f, buf[13] >> 4) {
+        // LOCAL: a connection established by the proxy itself, e.g. for health checks.
+        (0x0, _) => Ok(ProxyHeader::Complete(None)),
+        // PROXY over IPv4.
+        (0x1, 0x1) if addrs.len() >= 12 => {
+            let src = Ipv4Addr::new(addrs[0], addrs[1], addrs[2], addrs[3]);
+            let dst = Ipv4Addr::new(addrs[4], addrs[5], addrs[6], addrs[7]);
+            Ok(ProxyHeader::Complete(Some((
+                SocketAddr::new(src.into(), port(&addrs[8..])),
+                SocketAddr::new(dst.into(), port(&addrs[10..])),
+            ))))
+        }
+        // PROXY over IPv6.
+        (0x1, 0x2) if addrs.len() >= 36 => {
+            let ip = |bytes: &[u8]| {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&bytes[..16]);
+                Ipv6Addr::from(octets)
+            };
+            Ok(ProxyHeader::Complete(Some((
+                SocketAddr::new(ip(&addrs[0..]).into(), port(&addrs[32..])),
+                SocketAddr::new(ip(&addrs[16..]).into(), port(&addrs[34..])),
+            ))))
+        }
+        // PROXY for an unspecified or non-IP (e.g. UNIX socket) family; keep the socket addresses.
+        (0x1, 0x0) | (0x1, 0x3) => Ok(ProxyHeader::Complete(None)),
+        _ => Err(invalid_header("invalid PROXY protocol header")),
+    }
+}
+
+/// The length of the queue of connections waiting to be accepted, as used by tokio.
+const LISTEN_BACKLOG: i32 = 1024;
+
+/// Listen at `addr`, as a socket not yet registered with any runtime.
+pub(crate) fn bind(
+    addr: SocketAddr,
+    configuration: &Configuration,
+) -> Result<std_net::TcpListener, Error> {
+    let bind = || -> io::Result<std_net::TcpListener> {
+        let builder = match addr {
+            SocketAddr::V4(_) => net2::TcpBuilder::new_v4()?,
+            SocketAddr::V6(_) => net2::TcpBuilder::new_v6()?,
+        };
+        if configuration.reuse_port {
+            reuse_port(&builder)?;
+        }
+        // Like the standard library, allow binding while connections of a previous server are
+        // still closing.
+        #[cfg(unix)]
+        builder.reuse_address(true)?;
+        builder.bind(addr)?.listen(LISTEN_BACKLOG)
+    };
+    bind().map_err(|e| Error::Bind(Box::new(e)))
+}
+
+/// Listen at `addr` with as many sockets as `configuration` asks for: one per worker thread
+/// with both `reuse_port` and `workers` set, so that each can run an accept loop of its own, and
+/// a single one otherwise.
+pub(crate) fn bind_all(
+    addr: SocketAddr,
+    configuration: &Configuration,
+) -> Result<Vec<std_net::TcpListener>, Error> {
+    let first = bind(addr, configuration)?;
+    let count = match configuration.workers {
+        Some(workers) if configuration.reuse_port => workers.max(1),
+        _ => 1,
+    };
+    // The port picked by the system for the first socket, when binding to port 0.
+    let addr = first.local_addr().map_err(|e| Error::Bind(Box::new(e)))?;
+    let mut listeners = vec![first];
+    for _ in 1..count {
+        listeners.push(bind(addr, configuration)?);
+    }
+    Ok(listeners)
+}
+
+#[cfg(unix)]
+fn reuse_port(builder: &net2::TcpBuilder) -> io::Result<()> {
+    use net2::unix::UnixTcpBuilderExt;
+    builder.reuse_port(true).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn reuse_port(_: &net2::TcpBuilder) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "SO_REUSEPORT is not supported on this platform",
+    ))
+}
+
+/// How long to stop accepting connections after an error such as running out of file
+/// descriptors, which accepting again right away would only repeat.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A connection accepted by a listener.
+struct Accepted {
+    stream: TcpStream,
+    peer_addr: SocketAddr,
+    permit: Option<Permit>,
+}
+
+/// The connections accepted by a listener, riding out accept errors rather than ending.
+///
+/// With a limit, no connections are accepted while it is reached, leaving new clients in the
+/// listen backlog of the system. The limit may be shared with the accept loops of other
+/// listeners, each waiting for it in its own `slot`.
+struct Incoming {
+    listener: TcpListener,
+    backoff: Option<Delay>,
+    connections: Option<Arc<Limit>>,
+    slot: usize,
+    // A connection accepted while the limit was taken by another accept loop.
+    pending: Option<(TcpStream, SocketAddr)>,
+}
+
+/// The limits of a server, shared by all its accept loops.
+#[derive(Clone)]
+struct Limits {
+    connections: Option<Arc<Limit>>,
+    requests: Option<Arc<Limit>>,
+    // The slot the accept loop given these limits waits for `connections` in.
+    slot: usize,
+}
+
+impl Limits {
+    /// The limits of `configuration`, for `loops` accept loops.
+    fn new(configuration: &Configuration, loops: usize) -> Limits {
+        Limits {
+            connections: configuration
+                .max_connections
+                .map(|max| Limit::shared(max, loops)),
+            requests: configuration.max_requests.map(Limit::new),
+            slot: 0,
+        }
+    }
+}
+
+impl Stream01 for Incoming {
+    type Item = Accepted;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        if let Some(backoff) = self.backoff.as_mut() {
+            if let Ok(Async::NotReady) = backoff.poll() {
+                return Ok(Async::NotReady);
+            }
+            self.backoff = None;
+        }
+        while self.pending.is_none() {
+            if let Some(connections) = &self.connections {
+                if connections.is_full() {
+                    connections.register(self.slot);
+                    // A connection may have closed before the task was registered.
+                    if connections.is_full() {
+                        return Ok(Async::NotReady);
+                    }
+                }
+            }
+            match self.listener.poll_accept() {
+                Ok(Async::Ready(accepted)) => self.pending = Some(accepted),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                // The client gave up on a connection before it was accepted.
+                Err(ref e) if is_connection_error(e) => continue,
+                Err(_) => {
+                    let mut backoff = Delay::new(Instant::now() + ACCEPT_ERROR_BACKOFF);
+                    if let Ok(Async::NotReady) = backoff.poll() {
+                        self.backoff = Some(backoff);
+                        return Ok(Async::NotReady);
+                    }
+                }
+            }
+        }
+        // Another accept loop may have taken the last permit since the limit was checked, in
+        // which case the connection waits for one.
+        let permit = match &self.connections {
+            Some(connections) => match Limit::try_acquire(connections) {
+                Some(permit) => Some(permit),
+                None => {
+                    connections.register(self.slot);
+                    match Limit::try_acquire(connections) {
+                        Some(permit) => Some(permit),
+                        None => return Ok(Async::NotReady),
+                    }
+                }
+            },
+            None => None,
+        };
+        let (stream, peer_addr) = self.pending.take().unwrap();
+        Ok(Async::Ready(Some(Accepted {
+            stream,
+            peer_addr,
+            permit,
+        })))
+    }
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::ConnectionReset => true,
+        _ => false,
+    }
+}
+
+/// A future serving an `HttpService`, to be run on a tokio runtime.
+pub(crate) type Serving = Box<dyn Future01<Item = (), Error = Error> + Send>;
+
+/// How the connections accepted by the server are secured.
+#[derive(Clone)]
+pub(crate) enum Security {
+    /// Plain HTTP.
+    None,
+    /// HTTPS, with TLS terminated by the server.
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    Tls(TlsConfig),
+}
+
+/// Use hyper to serve the given `HttpService` on the given listener, on the tokio runtime the
+/// returned future is run on.
+///
+/// With the `proxy_protocol` of `configuration`, every connection must start with a PROXY protocol
+/// header, whose addresses then replace the socket addresses in `ConnectionInfo`.
+///
+/// Once `shutdown` resolves, the server stops accepting connections and waits for in-flight
+/// requests to complete, for at most the `shutdown_timeout` of `configuration`, before the future
+/// resolves. Meanwhile, responses ask HTTP/1 clients to close their connection, and further
+/// requests on open connections are answered with `503 Service Unavailable`. The requests still
+/// in flight by then are aborted, closing their connections, and their number is logged to
+/// `logger`.
+pub(crate) fn run<S: HttpService>(
+    s: S,
+    listener: std_net::TcpListener,
+    configuration: &Configuration,
+    logger: slog::Logger,
+    security: Security,
+    shutdown: Option<FutureObj<'static, ()>>,
+) -> Serving {
+    let limits = Limits::new(configuration, 1);
+    run_with(s, listener, configuration, logger, security, shutdown, limits)
+}
+
+/// Like `run`, with `limits` possibly shared with other accept loops.
+fn run_with<S: HttpService>(
+    s: S,
+    listener: std_net::TcpListener,
+    configuration: &Configuration,
+    logger: slog::Logger,
+    security: Security,
+    shutdown: Option<FutureObj<'static, ()>>,
+    limits: Limits,
+) -> Serving {
+    let Limits {
+        connections,
+        requests,
+        slot,
+    } = limits;
+    let mut service = WrapHttpService::new(s, configuration);
+    service.requests = requests;
+    let configuration = configuration.clone();
+    // Register the listener on first poll, so that it uses the reactor of the runtime.
+    let serving = futures01::future::lazy(move || -> Result<Serving, Error> {
+        let listener = TcpListener::from_std(listener, &Handle::default())
+            .map_err(|e| Error::Bind(Box::new(e)))?;
+        let incoming = Incoming {
+            listener,
+            backoff: None,
+            connections,
+            slot,
+            pending: None,
+        };
+        let settings = configuration.clone();
+        let incoming = incoming.map(move |accepted| Connection::new(accepted, &settings));
+        Ok(match security {
+            Security::None => serve_incoming(incoming, service, &configuration, logger, shutdown),
+            #[cfg(any(feature = "tls", feature = "native-tls"))]
+            Security::Tls(TlsConfig { acceptor }) => match acceptor {
+                #[cfg(feature = "tls")]
+                Acceptor::Rustls(config) => {
+                    let config = with_alpn(config, configuration.http_versions);
+                    let acceptor = tokio_rustls::TlsAcceptor::from(config);
+                    let accept = move |conn| acceptor.accept(conn);
+                    let incoming = Handshakes::new(incoming, accept, &configuration);
+                    serve_incoming(incoming, service, &configuration, logger, shutdown)
+                }
+                #[cfg(feature = "native-tls")]
+                Acceptor::Native(acceptor) => {
+                    let accept = move |conn| acceptor.accept(conn);
+                    let incoming = Handshakes::new(incoming, accept, &configuration);
+                    serve_incoming(incoming, service, &configuration, logger, shutdown)
+                }
+            },
+        })
+    });
+    Box::new(serving.flatten())
+}
+
+/// Serve the given `HttpService` on each of `listeners`, like `run`, with an accept loop of its
+/// own spawned on the tokio runtime the returned future is run on, so that the worker threads of
+/// the runtime accept connections in parallel.
+///
+/// The `max_connections` and `max_requests` of `configuration` apply to all listeners together,
+/// and `shutdown` stops all of them. The future resolves once every listener is done
+/// serving, or as soon as one of them fails.
+pub(crate) fn run_all<S: HttpService + Clone>(
+    s: S,
+    listeners: Vec<std_net::TcpListener>,
+    configuration: &Configuration,
+    logger: slog::Logger,
+    security: Security,
+    shutdown: Option<FutureObj<'static, ()>>,
+) -> Serving {
+    if listeners.len() == 1 {
+        let listener = listeners.into_iter().next().unwrap();
+        return run(s, listener, configuration, logger, security, shutdown);
+    }
+    let limits = Limits::new(configuration, listeners.len());
+    let configuration = configuration.clone();
+    let shutdown = shutdown.map(|shutdown| Future01::shared(shutdown.map(Ok::<(), ()>).compat()));
+    // Spawn the accept loops on first poll, so that they run on the runtime of the server.
+    let serving = futures01::future::lazy(move || {
+        let done = listeners.into_iter().enumerate().map(move |(slot, listener)| {
+            let shutdown = shutdown.clone().map(|shutdown| {
+                let shutdown = Compat01As03::new(shutdown).map(|_| ());
+                FutureObj::new(Box::new(shutdown))
+            });
+            let serving = run_with(
+                s.clone(),
+                listener,
+                &configuration,
+                logger.clone(),
+                security.clone(),
+                shutdown,
+                Limits {
+                    slot,
+                    ..limits.clone()
+                },
+            );
+            let (done, result) = oneshot::channel();
+            tokio::spawn(serving.then(move |served| {
+                let _ = done.send(served);
+                Ok(())
+            }));
+            // A loop dropped without a result was dropped with the runtime.
+            result.then(|served| served.unwrap_or(Ok(())))
+        });
+        futures01::future::join_all(done.collect::<Vec<_>>()).map(|_| ())
+    });
+    Box::new(serving)
+}
+
+/// A connection of a transport provided by the application, whose addresses are unknown.
+struct Transport<IO> {
+    io: IO,
+    info: Arc<Mutex<ConnectionInfo>>,
+    activity: Arc<Activity>,
+}
+
+impl<IO> Connected for Transport<IO> {
+    fn info(&self) -> &Arc<Mutex<ConnectionInfo>> {
+        &self.info
+    }
+
+    fn activity(&self) -> &Arc<Activity> {
+        &self.activity
+    }
+}
+
+impl<IO: Read> Read for Transport<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<IO: Write> Write for Transport<IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<IO: AsyncRead> AsyncRead for Transport<IO> {}
+
+impl<IO: AsyncWrite> AsyncWrite for Transport<IO> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Use hyper to serve the given `HttpService` on the connections of `incoming`, on the tokio
+/// runtime the returned future is run on.
+///
+/// The settings of `configuration` about accepting connections and their sockets do not apply.
+pub(crate) fn run_incoming<S, I, IO>(
+    s: S,
+    incoming: I,
+    configuration: &Configuration,
+    logger: slog::Logger,
+) -> Serving
+where
+    S: HttpService,
+    I: Stream01<Item = IO, Error = io::Error> + Send + 'static,
+    IO: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let service = WrapHttpService::new(s, configuration);
+    let incoming = incoming.map(|io| Transport {
+        io,
+        info: Arc::new(Mutex::new(ConnectionInfo::new(None, None))),
+        activity: Arc::default(),
+    });
+    serve_incoming(incoming, service, configuration, logger, None)
+}
+
+fn serve_incoming<I, H>(
+    incoming: I,
+    service: WrapHttpService<H>,
+    configuration: &Configuration,
+    logger: slog::Logger,
+    shutdown: Option<FutureObj<'static, ()>>,
+) -> Serving
+where
+    I: Stream01<Error = io::Error> + Send + 'static,
+    I::Item: AsyncRead + AsyncWrite + Connected + Send + 'static,
+    H: HttpService,
+{
+    let shutdown_timeout = configuration.shutdown_timeout;
+    let drain = service.drain.clone();
+    let service_drain = service.drain.clone();
+    // hyper refuses buffers smaller than 8 KiB.
+    let max_buf_size = configuration
+        .max_head_size
+        .map_or(HYPER_MAX_BUF_SIZE, |size| size.max(8192));
+    let server = hyper_server::Server::builder(incoming)
+        .http1_max_buf_size(max_buf_size)
+        .http1_keepalive(configuration.keep_alive)
+        .http1_only(configuration.http_versions == HttpVersions::Http1Only)
+        .http2_only(configuration.http_versions == HttpVersions::Http2Only)
+        .serve(service);
+    let shutdown = match shutdown {
+        Some(shutdown) => shutdown,
+        None => {
+            let server = server.map_err(|e| Error::Server(Box::new(e))).then(move |result| {
+                service_drain.abort();
+                result
+            });
+            return Box::new(server);
+        }
+    };
+
+    let (draining, drain_started) = oneshot::channel();
+    let started = drain.clone();
+    let signal = shutdown.map(Ok::<(), ()>).compat().then(move |_| {
+        started.started.store(true, Ordering::SeqCst);
+        let _ = draining.send(());
+        Ok::<(), ()>(())
+    });
+    let deadline = drain_started
+        .or_else(|_| futures01::future::empty::<(), Error>())
+        .and_then(move |()| {
+            Delay::new(Instant::now() + shutdown_timeout).then(move |_| {
+                let aborted = drain.in_flight.load(Ordering::SeqCst);
+                if aborted > 0 {
+                    slog::warn!(
+                        logger,
+                        "Shutdown timeout elapsed, aborting {} requests in flight",
+                        aborted
+                    );
+                }
+                Ok::<(), Error>(())
+            })
+        });
+    let server = server
+        .with_graceful_shutdown(signal)
+        .map_err(|e| Error::Server(Box::new(e)))
+        .select(deadline)
+        .then(move |result| {
+            // Whatever is left once the server stops is aborted, rather than left to the
+            // runtime, which may be the application's and keep running.
+            service_drain.abort();
+            result.map(|_| ()).map_err(|(e, _)| e)
+        });
+    Box::new(server)
+}
+
+/// The default limit of hyper on the buffer holding the request line and headers.
+const HYPER_MAX_BUF_SIZE: usize = 8192 + 4096 * 100;
+
+/// Advertise only the HTTP versions the server speaks during the TLS handshake, unless the
+/// configuration was set up with other protocols by hand.
+#[cfg(feature = "tls")]
+fn with_alpn(
+    config: Arc<rustls::ServerConfig>,
+    versions: HttpVersions,
+) -> Arc<rustls::ServerConfig> {
+    let protocols: &[&[u8]] = match versions {
+        HttpVersions::Any => return config,
+        HttpVersions::Http1Only => &[b"http/1.1"],
+        HttpVersions::Http2Only => &[b"h2"],
+    };
+    let advertised = |protocol: &Vec<u8>| &protocol[..] == b"h2" || &protocol[..] == b"http/1.1";
+    if !config.alpn_protocols.iter().all(advertised) {
+        return config;
+    }
+    let mut config = (*config).clone();
+    config.alpn_protocols = protocols.iter().map(|p| p.to_vec()).collect();
+    Arc::new(config)
+}
+
+#[cfg(feature = "tls")]
+impl Connected for tokio_rustls::server::TlsStream<Connection> {
+    fn info(&self) -> &Arc<Mutex<ConnectionInfo>> {
+        self.get_ref().0.info()
+    }
+
+    fn activity(&self) -> &Arc<Activity> {
+        self.get_ref().0.activity()
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl Connected for tokio_tls::TlsStream<Connection> {
+    fn info(&self) -> &Arc<Mutex<ConnectionInfo>> {
+        self.get_ref().get_ref().info()
+    }
+
+    fn activity(&self) -> &Arc<Activity> {
+        self.get_ref().get_ref().activity()
+    }
+}
+
+/// The connections of `incoming` that completed the TLS handshake with `accept`.
+///
+/// Every handshake runs as a task of its own, so that accepting never waits for one. While the
+/// `max_handshakes` of the configuration are running, no further connections are taken from
+/// `incoming`. Connections failing the handshake, or not completing it within the
+/// `handshake_timeout`, are dropped.
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+struct Handshakes<I, A, T> {
+    incoming: I,
+    accept: A,
+    running: Arc<Limit>,
+    timeout: Duration,
+    // Whether `incoming` has ended.
+    done: bool,
+    sender: futures01::sync::mpsc::UnboundedSender<T>,
+    completed: futures01::sync::mpsc::UnboundedReceiver<T>,
+}
+
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+impl<I, A, T> Handshakes<I, A, T> {
+    fn new(incoming: I, accept: A, configuration: &Configuration) -> Handshakes<I, A, T> {
+        let (sender, completed) = futures01::sync::mpsc::unbounded();
+        Handshakes {
+            incoming,
+            accept,
+            running: Limit::new(configuration.max_handshakes.max(1)),
+            timeout: configuration.handshake_timeout,
+            done: false,
+            sender,
+            completed,
+        }
+    }
+}
+
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+impl<I, A, F> Stream01 for Handshakes<I, A, F::Item>
+where
+    I: Stream01<Item = Connection, Error = io::Error>,
+    A: FnMut(Connection) -> F,
+    F: Future01 + Send + 'static,
+    F::Item: Send + 'static,
+{
+    type Item = F::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<F::Item>, io::Error> {
+        while !self.done {
+            if self.running.is_full() {
+                self.running.register(0);
+                // A handshake may have completed before the task was registered.
+                if self.running.is_full() {
+                    break;
+                }
+            }
+            match self.incoming.poll()? {
+                Async::Ready(Some(conn)) => {
+                    // Only this task acquires permits, so the limit cannot have been reached
+                    // since it was checked.
+                    let permit = Limit::try_acquire(&self.running);
+                    let sender = self.sender.clone();
+                    let handshake = (self.accept)(conn);
+                    let handshake = tokio::timer::Timeout::new(handshake, self.timeout)
+                        .then(move |stream| {
+                            if let Ok(stream) = stream {
+                                let _ = sender.unbounded_send(stream);
+                            }
+                            drop(permit);
+                            Ok(())
+                        });
+                    tokio::spawn(handshake);
+                }
+                Async::Ready(None) => self.done = true,
+                Async::NotReady => break,
+            }
+        }
+        if self.done {
+            self.running.register(0);
+        }
+        // Checked before receiving, as connections are sent before their permit is released.
+        let idle = self.done && self.running.current.load(Ordering::SeqCst) == 0;
+        match self.completed.poll() {
+            Ok(Async::Ready(Some(stream))) => Ok(Async::Ready(Some(stream))),
+            _ if idle => Ok(Async::Ready(None)),
+            _ => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Run `serving` on a runtime of its own, with the worker threads of `configuration`.
+///
+/// Blocks until `serving` resolves. The connections still open then are dropped.
+pub(crate) fn block_on(serving: Serving, configuration: &Configuration) -> Result<(), Error> {
+    let mut builder = runtime::Builder::new();
+    builder.name_prefix(configuration.thread_name.clone());
+    if let Some(workers) = configuration.workers {
+        builder.core_threads(workers.max(1));
+    }
+    let mut runtime = builder.build().map_err(Error::Runtime)?;
+    let result = runtime.block_on(serving);
+    let _ = runtime.shutdown_now().wait();
+    result
+}
+
+/// A future resolving when the process receives SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+///
+/// Pass it to `App::serve_with_shutdown` to shut down gracefully when the process is asked to
+/// stop, e.g. by a container orchestrator. A signal whose handler cannot be installed is left
+/// out, so that the others still work; if none can be installed, the future never resolves.
+#[cfg(feature = "signals")]
+pub fn shutdown_signal() -> FutureObj<'static, ()> {
+    type Interrupt = Box<dyn Future01<Item = (), Error = ()> + Send>;
+
+    // The first signal delivered to a stream of signals, once installed, or never if it cannot
+    // be installed.
+    fn first<S>(stream: impl Future01<Item = S, Error = io::Error> + Send + 'static) -> Interrupt
+    where
+        S: Stream01<Error = io::Error> + Send + 'static,
+    {
+        let signal = stream
+            .flatten_stream()
+            .into_future()
+            .map(|_| ())
+            .or_else(|_| futures01::future::empty::<(), ()>());
+        Box::new(signal)
+    }
+
+    // Install the handlers on first poll, so that they use the reactor of the server.
+    let signal = futures01::future::lazy(|| {
+        let signal = first(tokio_signal::ctrl_c());
+        #[cfg(unix)]
+        let signal = {
+            use tokio_signal::unix::{Signal, SIGTERM};
+            let term = first(Signal::new(SIGTERM));
+            Box::new(signal.select(term).map(|_| ()).map_err(|_| ())) as Interrupt
+        };
+        signal
+    });
+    FutureObj::new(Box::new(Compat01As03::new(signal).map(|_| ())))
+}
+
+/// The listening socket passed by systemd socket activation, if any.
+///
+/// systemd hands over sockets as file descriptors starting at 3, announced by the `LISTEN_PID`
+/// and `LISTEN_FDS` environment variables; this takes the first one, for
+/// `App::serve_listener`. The variables are removed, so that child processes do not take the
+/// socket as theirs.
+#[cfg(unix)]
+pub fn systemd_listener() -> Option<std_net::TcpListener> {
+    use std::env;
+    use std::os::unix::io::FromRawFd;
+
+    const FIRST_FD: i32 = 3;
+
+    let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = env::var("LISTEN_FDS").ok()?.parse::<u32>().ok()?;
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    if pid != std::process::id() || fds == 0 {
+        return None;
+    }
+    // This is safe because systemd passes the descriptor to this process alone, and the
+    // environment variables announcing it are gone, so it is not taken twice.
+    Some(unsafe { std_net::TcpListener::from_raw_fd(FIRST_FD) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(src: &str, dst: &str) -> ProxyHeader {
+        ProxyHeader::Complete(Some((src.parse().unwrap(), dst.parse().unwrap())))
+    }
+
+    #[test]
+    fn proxy_v1() {
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n";
+        assert_eq!(
+            parse_proxy_header(header).unwrap(),
+            complete("192.0.2.1:56324", "198.51.100.1:443")
+        );
+
+        let header = b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n";
+        assert_eq!(
+            parse_proxy_header(header).unwrap(),
+            complete("[2001:db8::1]:56324", "[2001:db8::2]:443")
+        );
+
+        let header = b"PROXY UNKNOWN\r\n";
+        assert_eq!(
+            parse_proxy_header(header).unwrap(),
+            ProxyHeader::Complete(None)
+        );
+    }
+
+    #[test]
+    fn proxy_v1_incomplete() {
+        assert_eq!(
+            parse_proxy_header(b"PRO").unwrap(),
+            ProxyHeader::Incomplete(3)
+        );
+        assert_eq!(
+            parse_proxy_header(b"PROXY TCP4 192.0.2.1").unwrap(),
+            ProxyHeader::Incomplete(1)
+        );
+    }
+
+    #[test]
+    fn proxy_v2() {
+        let mut header = PROXY_V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        assert_eq!(
+            parse_proxy_header(&header).unwrap(),
+            ProxyHeader::Incomplete(12)
+        );
+
+        header.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb]);
+        assert_eq!(
+            parse_proxy_header(&header).unwrap(),
+            complete("192.0.2.1:56324", "198.51.100.1:443")
+        );
+
+        let mut local = PROXY_V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(
+            parse_proxy_header(&local).unwrap(),
+            ProxyHeader::Complete(None)
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let limit = Limit::new(2);
+        let first = Limit::try_acquire(&limit).unwrap();
+        let _second = Limit::try_acquire(&limit).unwrap();
+        assert!(limit.is_full());
+        assert!(Limit::try_acquire(&limit).is_none());
+
+        drop(first);
+        assert!(!limit.is_full());
+        assert!(Limit::try_acquire(&limit).is_some());
+    }
+
+    #[test]
+    fn head_limits() {
+        let limits = HeadLimits {
+            headers: 1,
+            uri_length: Some(8),
+        };
+        let req = |uri: &str, headers: usize| {
+            let mut req = http::Request::get(uri);
+            for i in 0..headers {
+                req.header(format!("X-Header-{}", i).as_str(), "");
+            }
+            req.body(()).unwrap()
+        };
+
+        assert_eq!(limits.check(&req("/?q=1234", 1)), None);
+        assert_eq!(
+            limits.check(&req("/?q=12345", 1)),
+            Some(http::StatusCode::URI_TOO_LONG)
+        );
+        assert_eq!(
+            limits.check(&req("/", 2)),
+            Some(http::StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn proxy_missing_header() {
+        assert!(parse_proxy_header(b"GET / HTTP/1.1\r\n").is_err());
+        assert!(parse_proxy_header(b"PROXY TCP4 nonsense\r\n").is_err());
+    }
 }
@@ -0,0 +1,153 @@
+//! Splitting the traffic of a route between two endpoints, for A/B tests and canary releases.
+//!
+//! ```rust, no_run
+//! # #![feature(async_await)]
+//! use tide::split::Split;
+//!
+//! let mut app = tide::App::new(());
+//! app.at("/checkout").get(
+//!     Split::new(async || "current checkout", async || "new checkout")
+//!         .weight(0.1)
+//!         .sticky_cookie("session"),
+//! );
+//! ```
+//!
+//! The branch serving a request is recorded as a `Branch` in the response's `extensions`, where
+//! middleware can pick it up for logging or metrics.
+
+use futures::future::FutureObj;
+
+use crate::{
+    configuration::Store,
+    endpoint::{BoxedEndpoint, Endpoint},
+    hash,
+    sampler::{self, Sampler},
+    Request, Response, RouteMatch,
+};
+
+/// The branch of a `Split` that served a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Branch {
+    /// The first endpoint, receiving the traffic not sent to `B`.
+    A,
+    /// The second endpoint, receiving the configured share of traffic.
+    B,
+}
+
+/// Where a `Split` takes the key keeping a client on the same branch from.
+enum Sticky {
+    Cookie(String),
+    Header(http::header::HeaderName),
+}
+
+/// An endpoint dispatching each request to one of two endpoints.
+///
+/// By default, requests are spread evenly according to the weight. With a sticky cookie or
+/// header, requests carrying the same value always go to the same branch, which keeps a user on
+/// one variant across requests; requests without it fall back to the even spread.
+pub struct Split<Data> {
+    a: BoxedEndpoint<Data>,
+    b: BoxedEndpoint<Data>,
+    weight: f64,
+    sticky: Option<Sticky>,
+    sampler: Sampler,
+}
+
+impl<Data> Split<Data> {
+    /// Split requests between `a` and `b`, sending half of them to each.
+    pub fn new<A, KA, B, KB>(a: A, b: B) -> Split<Data>
+    where
+        A: Endpoint<Data, KA>,
+        B: Endpoint<Data, KB>,
+    {
+        Split {
+            a: BoxedEndpoint::new(a),
+            b: BoxedEndpoint::new(b),
+            weight: 0.5,
+            sticky: None,
+            sampler: Sampler::default(),
+        }
+    }
+
+    /// Send the given fraction (between `0.0` and `1.0`) of requests to `b`.
+    ///
+    /// Weights outside that range are clamped to it.
+    pub fn weight(mut self, weight: f64) -> Self {
+        self.weight = sampler::clamp_rate(weight);
+        self
+    }
+
+    /// Keep requests with the same value of the cookie `name` on the same branch.
+    pub fn sticky_cookie(mut self, name: impl Into<String>) -> Self {
+        self.sticky = Some(Sticky::Cookie(name.into()));
+        self
+    }
+
+    /// Keep requests with the same value of the header `name` on the same branch.
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn sticky_header(mut self, name: &str) -> Self {
+        let name = http::header::HeaderName::from_bytes(name.as_bytes())
+            .unwrap_or_else(|_| panic!("Invalid header name `{}`", name));
+        self.sticky = Some(Sticky::Header(name));
+        self
+    }
+
+    fn sticky_key(&self, req: &Request) -> Option<String> {
+        match self.sticky.as_ref()? {
+            Sticky::Header(name) => Some(req.headers().get(name)?.to_str().ok()?.to_owned()),
+            Sticky::Cookie(name) => req
+                .headers()
+                .get_all(http::header::COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .filter_map(|cookie| cookie::Cookie::parse(cookie.trim()).ok())
+                .find(|cookie| cookie.name() == name)
+                .map(|cookie| cookie.value().to_owned()),
+        }
+    }
+
+    fn branch(&self, req: &Request) -> Branch {
+        let in_b = match self.sticky_key(req) {
+            // The hash is fixed, so a key keeps its branch across restarts and instances.
+            Some(key) => (hash::fnv(key.as_bytes()) % 10_000) as f64 < self.weight * 10_000.0,
+            None => self.sampler.sample(self.weight),
+        };
+        if in_b {
+            Branch::B
+        } else {
+            Branch::A
+        }
+    }
+}
+
+/// A marker type used for the (phantom) `Kind` parameter of `Split`.
+#[doc(hidden)]
+pub struct SplitKind;
+
+impl<Data: Send + Sync + 'static> Endpoint<Data, SplitKind> for Split<Data> {
+    type Fut = FutureObj<'static, Response>;
+
+    fn call(
+        &self,
+        data: Data,
+        req: Request,
+        params: Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let branch = self.branch(&req);
+        let endpoint = match branch {
+            Branch::A => &self.a,
+            Branch::B => &self.b,
+        };
+        let fut = endpoint.call(data, req, params, store);
+        FutureObj::new(Box::new(
+            async move {
+                let mut res = await!(fut);
+                res.extensions_mut().insert(branch);
+                res
+            },
+        ))
+    }
+}
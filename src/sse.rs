@@ -0,0 +1,275 @@
+//! Types for serving server-sent events.
+//!
+//! An endpoint can return an `EventStream` wrapping any stream of `Event`s, which is sent to the
+//! client as `text/event-stream`. Clients reconnecting after a dropped connection report the ID
+//! of the last event they saw, which endpoints can read with the `LastEventId` extractor in order
+//! to replay missed events.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # #![feature(async_await, futures_api)]
+//! use futures::stream;
+//! use tide::sse::{Event, EventStream, LastEventId};
+//!
+//! type Events = EventStream<stream::Iter<std::vec::IntoIter<Event>>>;
+//!
+//! async fn events(LastEventId(last): LastEventId) -> Events {
+//!     let start = last.and_then(|id| id.parse().ok()).map_or(0, |id: usize| id + 1);
+//!     let events: Vec<_> = (start..start + 3)
+//!         .map(|i| Event::new(format!("message {}", i)).id(i.to_string()))
+//!         .collect();
+//!     EventStream(stream::iter(events))
+//! }
+//!
+//! fn main() {
+//!     let mut app = tide::App::new(());
+//!     app.at("/events").get(events);
+//!     app.serve().unwrap();
+//! }
+//! ```
+//!
+//! Proxies tend to drop idle connections, so long-lived streams should send heartbeats, with
+//! `EventStream::heartbeat`:
+//!
+//! ```rust, no_run
+//! # #![feature(async_await, futures_api)]
+//! use futures::stream;
+//! use std::time::Duration;
+//! use tide::sse::{Event, EventStream};
+//!
+//! let mut app = tide::App::new(());
+//! app.at("/ticks").get(async || {
+//!     EventStream(stream::iter(vec![Event::new("tick")])).heartbeat(Duration::from_secs(15))
+//! });
+//! ```
+
+use futures::{future, prelude::*, stream};
+use http_service::Body;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{configuration::Store, Extract, IntoResponse, Request, Response, RouteMatch};
+
+/// A single server-sent event.
+#[derive(Clone, Debug, Default)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<u64>,
+    comment: Option<String>,
+}
+
+impl Event {
+    /// Create an event carrying the given `data`.
+    pub fn new<D: Into<String>>(data: D) -> Event {
+        Event {
+            data: Some(data.into()),
+            ..Event::default()
+        }
+    }
+
+    /// Create a comment, which clients ignore. Useful as a heartbeat to keep idle connections
+    /// open.
+    pub fn comment<C: Into<String>>(comment: C) -> Event {
+        Event {
+            comment: Some(comment.into()),
+            ..Event::default()
+        }
+    }
+
+    /// Set the ID of this event, reported back by reconnecting clients in `Last-Event-ID`.
+    ///
+    /// Panics if `id` contains a line break or a NUL character, which would let it inject other
+    /// fields or events.
+    pub fn id<I: Into<String>>(mut self, id: I) -> Self {
+        let id = id.into();
+        assert!(
+            !id.contains(|c| c == '\n' || c == '\r' || c == '\0'),
+            "Invalid event ID {:?}",
+            id
+        );
+        self.id = Some(id);
+        self
+    }
+
+    /// Set the event type, used by clients to dispatch the event.
+    ///
+    /// Panics if `event` contains a line break, which would let it inject other fields or events.
+    pub fn event<E: Into<String>>(mut self, event: E) -> Self {
+        let event = event.into();
+        assert!(
+            !event.contains(|c| c == '\n' || c == '\r'),
+            "Invalid event type {:?}",
+            event
+        );
+        self.event = Some(event);
+        self
+    }
+
+    /// Set the reconnection delay, in milliseconds, the client should use.
+    pub fn retry(mut self, millis: u64) -> Self {
+        self.retry = Some(millis);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(comment) = &self.comment {
+            for line in lines(comment) {
+                writeln!(fmt, ": {}", line)?;
+            }
+        }
+        if let Some(id) = &self.id {
+            writeln!(fmt, "id: {}", id)?;
+        }
+        if let Some(event) = &self.event {
+            writeln!(fmt, "event: {}", event)?;
+        }
+        if let Some(retry) = self.retry {
+            writeln!(fmt, "retry: {}", retry)?;
+        }
+        if let Some(data) = &self.data {
+            for line in lines(data) {
+                writeln!(fmt, "data: {}", line)?;
+            }
+        }
+        writeln!(fmt)
+    }
+}
+
+/// The lines of `text`, split at every line break clients recognize: `\r\n`, `\n` and `\r`.
+///
+/// A trailing line break yields a last, empty line, so that clients joining the lines with
+/// `\n` get `text` back.
+fn lines(text: &str) -> Vec<String> {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    text.split('\n').map(ToOwned::to_owned).collect()
+}
+
+/// A response streaming server-sent events.
+pub struct EventStream<S>(pub S);
+
+impl<S> EventStream<S> {
+    /// Send a comment every `interval` along with the events, so that proxies do not close the
+    /// connection while no events occur.
+    pub fn heartbeat(self, interval: Duration) -> Heartbeat<S> {
+        Heartbeat {
+            events: self.0,
+            interval,
+        }
+    }
+}
+
+impl<S> IntoResponse for EventStream<S>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    fn into_response(self) -> Response {
+        event_stream(self.0)
+    }
+}
+
+/// A response streaming server-sent events with heartbeats, created by
+/// `EventStream::heartbeat`.
+pub struct Heartbeat<S> {
+    events: S,
+    interval: Duration,
+}
+
+impl<S> IntoResponse for Heartbeat<S>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    fn into_response(self) -> Response {
+        let heartbeats = futures_timer::Interval::new(self.interval)
+            .map(|()| Some(Event::comment("heartbeat")));
+        // The heartbeats end with the events, marked by `None`.
+        let events = self
+            .events
+            .map(Some)
+            .chain(stream::once(future::ready(None)));
+        let events = events
+            .select(heartbeats)
+            .take_while(|event| future::ready(event.is_some()))
+            .filter_map(future::ready);
+        event_stream(events)
+    }
+}
+
+fn event_stream<S>(events: S) -> Response
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    let stream = events.map(|event| Ok(bytes::Bytes::from(event.to_string())));
+    http::Response::builder()
+        .status(http::status::StatusCode::OK)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(Body::from_stream(stream))
+        .unwrap()
+}
+
+/// An extractor for the `Last-Event-ID` header sent by reconnecting clients.
+///
+/// The extracted value is `None` on the first connection.
+pub struct LastEventId(pub Option<String>);
+
+impl<S: 'static> Extract<S> for LastEventId {
+    type Fut = future::Ready<Result<Self, Response>>;
+
+    fn extract(
+        data: &mut S,
+        req: &mut Request,
+        params: &Option<RouteMatch<'_>>,
+        store: &Store,
+    ) -> Self::Fut {
+        let id = req
+            .headers()
+            .get("Last-Event-ID")
+            .and_then(|id| id.to_str().ok())
+            .map(ToOwned::to_owned);
+        future::ok(LastEventId(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_format() {
+        let event = Event::new("foo\nbar").id("1").event("update").retry(1000);
+        assert_eq!(
+            event.to_string(),
+            "id: 1\nevent: update\nretry: 1000\ndata: foo\ndata: bar\n\n"
+        );
+        assert_eq!(Event::comment("keep-alive").to_string(), ": keep-alive\n\n");
+    }
+
+    #[test]
+    fn line_breaks() {
+        let event = Event::new("a\rb\r\nc");
+        assert_eq!(event.to_string(), "data: a\ndata: b\ndata: c\n\n");
+    }
+
+    #[test]
+    fn trailing_line_break() {
+        assert_eq!(Event::new("a\n").to_string(), "data: a\ndata: \n\n");
+        assert_eq!(Event::new("a\r\n\r\n").to_string(), "data: a\ndata: \ndata: \n\n");
+    }
+
+    #[test]
+    #[should_panic]
+    fn id_with_line_break() {
+        Event::new("").id("1\ndata: injected");
+    }
+
+    #[test]
+    #[should_panic]
+    fn event_with_line_break() {
+        Event::new("").event("update\r\r");
+    }
+}
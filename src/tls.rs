@@ -0,0 +1,386 @@
+//! Terminating TLS, for serving the app over HTTPS.
+//!
+//! Two backends are available, selected with cargo features: `tls` uses rustls, and
+//! `native-tls` uses the TLS stack of the system (OpenSSL, Secure Transport or SChannel), e.g.
+//! to pick up its certificate authorities or meet FIPS requirements. Either way, the app is
+//! served with `App::serve_tls` given a `TlsConfig`.
+//!
+//! Only rustls negotiates the protocol with ALPN, offering HTTP/2 to clients. The `native-tls`
+//! backend does not, so browsers and most other clients talk HTTP/1.1 to it.
+//!
+//! Certificates are not provisioned automatically: there is no ACME client, so accounts, orders
+//! and challenges are left to an external client such as certbot. Tide only serves its HTTP-01
+//! challenges, with `wellknown::acme_challenge_dir`, and picks up the certificates it renews,
+//! with `TlsConfig::reloading_pem_files`.
+
+#[cfg(feature = "native-tls")]
+use native_tls_crate as native_tls;
+#[cfg(feature = "tls")]
+use rustls::internal::pemfile;
+#[cfg(feature = "tls")]
+use rustls::sign::{self, CertifiedKey};
+#[cfg(feature = "tls")]
+use rustls::{Certificate, NoClientAuth, PrivateKey, ResolvesServerCert, ServerConfig};
+#[cfg(feature = "tls")]
+use std::collections::HashMap;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use std::fs;
+use std::io;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use std::path::Path;
+#[cfg(feature = "tls")]
+use std::path::PathBuf;
+#[cfg(feature = "tls")]
+use std::sync::{Arc, RwLock, Weak};
+#[cfg(feature = "tls")]
+use std::thread;
+#[cfg(feature = "tls")]
+use std::time::{Duration, SystemTime};
+
+/// How often certificates loaded with `TlsConfig::reloading_pem_files` are checked for changes.
+#[cfg(feature = "tls")]
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The certificates and settings a server terminates TLS with.
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub(crate) acceptor: Acceptor,
+}
+
+/// The backend terminating TLS.
+#[derive(Clone)]
+pub(crate) enum Acceptor {
+    #[cfg(feature = "tls")]
+    Rustls(Arc<ServerConfig>),
+    #[cfg(feature = "native-tls")]
+    Native(tokio_tls::TlsAcceptor),
+}
+
+#[cfg(feature = "tls")]
+impl TlsConfig {
+    /// Use the certificate chain and private key in the given PEM files, with rustls.
+    ///
+    /// The chain starts with the certificate of the server, followed by the intermediate
+    /// certificates. The key can be in PKCS#8 or PKCS#1 (RSA) format.
+    pub fn from_pem_files(
+        cert_chain: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> io::Result<TlsConfig> {
+        TlsConfig::from_pem(&fs::read(cert_chain)?, &fs::read(key)?)
+    }
+
+    /// Use the given PEM-encoded certificate chain and private key, with rustls.
+    pub fn from_pem(cert_chain: &[u8], key: &[u8]) -> io::Result<TlsConfig> {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config
+            .set_single_cert(certs(cert_chain)?, private_key(key)?)
+            .map_err(|e| invalid(&format!("invalid certificate or key: {:?}", e)))?;
+        config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+        Ok(TlsConfig::from_rustls(Arc::new(config)))
+    }
+
+    /// Use the certificate chain and private key in the given PEM files, with rustls, picking up
+    /// new versions of the files without restarting the server.
+    ///
+    /// This is meant for certificates renewed by an external ACME client such as certbot, with
+    /// its HTTP-01 challenges answered by `wellknown::acme_challenge_dir`; Tide does not talk to
+    /// ACME servers itself. A background thread checks the files for changes once a minute, so
+    /// handshakes never wait on the file system. Connections keep the certificate they were
+    /// established with, and files that fail to load leave the previous certificate in use.
+    pub fn reloading_pem_files(
+        cert_chain: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> io::Result<TlsConfig> {
+        let resolver = ReloadingResolver::new(cert_chain.into(), key.into())?;
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.cert_resolver = Arc::new(resolver);
+        config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+        Ok(TlsConfig::from_rustls(Arc::new(config)))
+    }
+
+    /// Use a rustls configuration set up by hand, e.g. to require client certificates.
+    pub fn from_rustls(config: Arc<ServerConfig>) -> TlsConfig {
+        TlsConfig {
+            acceptor: Acceptor::Rustls(config),
+        }
+    }
+
+    /// Start setting up a configuration with a certificate per server name, chosen by the name
+    /// the client asks for (SNI), with rustls.
+    ///
+    /// This lets one server terminate TLS for several domains:
+    ///
+    /// ```rust, ignore
+    /// use tide::tls::TlsConfig;
+    ///
+    /// let tls = TlsConfig::sni()
+    ///     .pem_files("example.com", "example.com.crt", "example.com.key")?
+    ///     .pem_files("*.example.org", "example.org.crt", "example.org.key")?
+    ///     .fallback_pem_files("default.crt", "default.key")?
+    ///     .build();
+    /// ```
+    pub fn sni() -> SniBuilder {
+        SniBuilder::default()
+    }
+}
+
+/// A builder for a `TlsConfig` picking certificates by server name.
+///
+/// Created by `TlsConfig::sni`. Names are matched case-insensitively; a name starting with `*.`
+/// covers the names one level below it, and exact names take precedence. Clients asking for
+/// another name, or for none, get the fallback certificate if there is one, and fail the
+/// handshake otherwise.
+#[cfg(feature = "tls")]
+#[derive(Default)]
+pub struct SniBuilder {
+    resolver: SniResolver,
+}
+
+#[cfg(feature = "tls")]
+impl SniBuilder {
+    /// Use the PEM-encoded certificate chain and private key for `name`.
+    pub fn pem(mut self, name: &str, cert_chain: &[u8], key: &[u8]) -> io::Result<Self> {
+        let key = certified_key(cert_chain, key)?;
+        self.resolver.keys.insert(name.to_ascii_lowercase(), key);
+        Ok(self)
+    }
+
+    /// Use the certificate chain and private key in the given PEM files for `name`.
+    pub fn pem_files(
+        self,
+        name: &str,
+        cert_chain: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        self.pem(name, &fs::read(cert_chain)?, &fs::read(key)?)
+    }
+
+    /// Use the PEM-encoded certificate chain and private key for unknown server names.
+    pub fn fallback_pem(mut self, cert_chain: &[u8], key: &[u8]) -> io::Result<Self> {
+        self.resolver.fallback = Some(certified_key(cert_chain, key)?);
+        Ok(self)
+    }
+
+    /// Use the certificate chain and private key in the given PEM files for unknown server
+    /// names.
+    pub fn fallback_pem_files(
+        self,
+        cert_chain: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        self.fallback_pem(&fs::read(cert_chain)?, &fs::read(key)?)
+    }
+
+    /// Finish the configuration.
+    pub fn build(self) -> TlsConfig {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.cert_resolver = Arc::new(self.resolver);
+        config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+        TlsConfig::from_rustls(Arc::new(config))
+    }
+}
+
+/// Picks the certificate for the server name requested by the client.
+#[cfg(feature = "tls")]
+#[derive(Default)]
+struct SniResolver {
+    keys: HashMap<String, CertifiedKey>,
+    fallback: Option<CertifiedKey>,
+}
+
+#[cfg(feature = "tls")]
+impl ResolvesServerCert for SniResolver {
+    fn resolve(
+        &self,
+        server_name: Option<webpki::DNSNameRef>,
+        _sigschemes: &[rustls::SignatureScheme],
+    ) -> Option<CertifiedKey> {
+        server_name
+            .and_then(|name| lookup(&self.keys, name.into()))
+            .or_else(|| self.fallback.as_ref())
+            .cloned()
+    }
+}
+
+/// Serves the certificate in a pair of PEM files, reloaded by a background thread when the
+/// files change.
+#[cfg(feature = "tls")]
+struct ReloadingResolver {
+    current: Arc<RwLock<CertifiedKey>>,
+}
+
+#[cfg(feature = "tls")]
+impl ReloadingResolver {
+    fn new(cert_chain: PathBuf, key: PathBuf) -> io::Result<ReloadingResolver> {
+        let modified = last_modified(&cert_chain, &key);
+        let current = Arc::new(RwLock::new(load(&cert_chain, &key)?));
+        let watched = Arc::downgrade(&current);
+        thread::Builder::new()
+            .name("tide-tls-reload".to_string())
+            .spawn(move || watch(&cert_chain, &key, modified, watched))?;
+        Ok(ReloadingResolver { current })
+    }
+}
+
+#[cfg(feature = "tls")]
+impl ResolvesServerCert for ReloadingResolver {
+    fn resolve(
+        &self,
+        _server_name: Option<webpki::DNSNameRef>,
+        _sigschemes: &[rustls::SignatureScheme],
+    ) -> Option<CertifiedKey> {
+        let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+        Some(current.clone())
+    }
+}
+
+/// Reload the certificate when its files change, until the resolver serving it is dropped.
+#[cfg(feature = "tls")]
+fn watch(
+    cert_chain: &Path,
+    key: &Path,
+    mut modified: Option<SystemTime>,
+    current: Weak<RwLock<CertifiedKey>>,
+) {
+    loop {
+        thread::sleep(RELOAD_INTERVAL);
+        let current = match current.upgrade() {
+            Some(current) => current,
+            None => return,
+        };
+        let latest = last_modified(cert_chain, key);
+        if latest == modified {
+            continue;
+        }
+        if let Ok(loaded) = load(cert_chain, key) {
+            *current.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+            modified = latest;
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+fn load(cert_chain: &Path, key: &Path) -> io::Result<CertifiedKey> {
+    certified_key(&fs::read(cert_chain)?, &fs::read(key)?)
+}
+
+/// The latest modification time of the certificate chain and key files.
+#[cfg(feature = "tls")]
+fn last_modified(cert_chain: &Path, key: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    modified(cert_chain).max(modified(key))
+}
+
+/// Find the entry for the server `name`, trying an exact match before a wildcard one.
+#[cfg(feature = "tls")]
+fn lookup<'a, T>(entries: &'a HashMap<String, T>, name: &str) -> Option<&'a T> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    entries.get(&name).or_else(|| {
+        let parent = &name[name.find('.')?..];
+        entries.get(&format!("*{}", parent))
+    })
+}
+
+#[cfg(feature = "tls")]
+fn certified_key(cert_chain: &[u8], key: &[u8]) -> io::Result<CertifiedKey> {
+    let key = sign::any_supported_type(&private_key(key)?)
+        .map_err(|()| invalid("unsupported private key type"))?;
+    Ok(CertifiedKey::new(certs(cert_chain)?, Arc::new(key)))
+}
+
+/// Configurations using the TLS stack of the system.
+///
+/// It does not negotiate ALPN, so connections are not upgraded to HTTP/2 the way browsers do
+/// with rustls: use the `tls` feature to serve HTTP/2 over TLS.
+#[cfg(feature = "native-tls")]
+impl TlsConfig {
+    /// Use the certificate chain and private key in the given PEM files, with the system TLS
+    /// stack.
+    ///
+    /// See `TlsConfig::from_pem_native_tls` for the formats.
+    pub fn from_pem_files_native_tls(
+        cert_chain: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> io::Result<TlsConfig> {
+        TlsConfig::from_pem_native_tls(&fs::read(cert_chain)?, &fs::read(key)?)
+    }
+
+    /// Use the given PEM-encoded certificate chain and private key, with the system TLS stack.
+    ///
+    /// The chain starts with the certificate of the server, followed by the intermediate
+    /// certificates. The key must be in PKCS#8 format; a PKCS#1 (RSA) key can be converted with
+    /// `openssl pkcs8 -topk8 -nocrypt -in key.pem -out key.pkcs8.pem`.
+    pub fn from_pem_native_tls(cert_chain: &[u8], key: &[u8]) -> io::Result<TlsConfig> {
+        let identity = native_tls::Identity::from_pkcs8(cert_chain, key)
+            .map_err(|e| invalid(&format!("invalid certificate or key: {}", e)))?;
+        let acceptor = native_tls::TlsAcceptor::new(identity)
+            .map_err(|e| invalid(&format!("unusable identity: {}", e)))?;
+        Ok(TlsConfig::from_native_tls(acceptor))
+    }
+
+    /// Use the certificate chain and private key in the given PKCS#12 archive, with the system
+    /// TLS stack.
+    ///
+    /// A PEM certificate and key can be converted with
+    /// `openssl pkcs12 -export -in cert.pem -inkey key.pem -out identity.p12`.
+    pub fn from_pkcs12(archive: &[u8], password: &str) -> io::Result<TlsConfig> {
+        let identity = native_tls::Identity::from_pkcs12(archive, password)
+            .map_err(|e| invalid(&format!("invalid PKCS#12 archive: {}", e)))?;
+        let acceptor = native_tls::TlsAcceptor::new(identity)
+            .map_err(|e| invalid(&format!("unusable identity: {}", e)))?;
+        Ok(TlsConfig::from_native_tls(acceptor))
+    }
+
+    /// Use an acceptor of the system TLS stack set up by hand.
+    pub fn from_native_tls(acceptor: native_tls::TlsAcceptor) -> TlsConfig {
+        TlsConfig {
+            acceptor: Acceptor::Native(acceptor.into()),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parse a PEM-encoded certificate chain.
+#[cfg(feature = "tls")]
+fn certs(pem: &[u8]) -> io::Result<Vec<Certificate>> {
+    match pemfile::certs(&mut &*pem) {
+        Ok(ref certs) if certs.is_empty() => Err(invalid("no certificate found")),
+        Ok(certs) => Ok(certs),
+        Err(()) => Err(invalid("invalid PEM certificate")),
+    }
+}
+
+/// Parse the first PEM-encoded PKCS#8 or RSA private key.
+#[cfg(feature = "tls")]
+fn private_key(pem: &[u8]) -> io::Result<PrivateKey> {
+    let pkcs8 = pemfile::pkcs8_private_keys(&mut &*pem).unwrap_or_default();
+    let rsa = pemfile::rsa_private_keys(&mut &*pem).unwrap_or_default();
+    pkcs8
+        .into_iter()
+        .chain(rsa)
+        .next()
+        .ok_or_else(|| invalid("no PEM private key found"))
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sni_lookup() {
+        let mut entries = HashMap::new();
+        entries.insert("example.com".to_owned(), 1);
+        entries.insert("*.example.com".to_owned(), 2);
+        entries.insert("api.example.com".to_owned(), 3);
+
+        assert_eq!(lookup(&entries, "example.com"), Some(&1));
+        assert_eq!(lookup(&entries, "Example.COM."), Some(&1));
+        assert_eq!(lookup(&entries, "www.example.com"), Some(&2));
+        assert_eq!(lookup(&entries, "api.example.com"), Some(&3));
+        assert_eq!(lookup(&entries, "a.b.example.com"), None);
+        assert_eq!(lookup(&entries, "example.org"), None);
+    }
+}
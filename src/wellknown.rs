@@ -0,0 +1,124 @@
+//! Endpoints for the files every site is expected to serve at fixed locations.
+//!
+//! ```rust, no_run
+//! use tide::wellknown;
+//!
+//! let mut app = tide::App::new(());
+//! app.at("/robots.txt")
+//!     .get(wellknown::robots_txt("User-agent: *\nDisallow: /admin/\n"));
+//! app.at("/favicon.ico")
+//!     .get(wellknown::favicon(std::fs::read("static/favicon.ico").unwrap()));
+//! app.at("/.well-known/security.txt")
+//!     .get(wellknown::security_txt("Contact: mailto:security@example.com\n"));
+//! app.at("/.well-known/acme-challenge/{}")
+//!     .get(wellknown::acme_challenge_dir("/var/lib/acme/challenges"));
+//! ```
+
+use bytes::Bytes;
+use futures::future;
+use http::StatusCode;
+use http_service::Body;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::{head::Path, IntoResponse, Response};
+
+fn fixed(
+    content_type: &'static str,
+    cache_control: &'static str,
+    body: Bytes,
+) -> impl Fn() -> future::Ready<Response> + Clone + Send + Sync + 'static {
+    move || {
+        let res = http::Response::builder()
+            .header("Content-Type", content_type)
+            .header("Cache-Control", cache_control)
+            .body(Body::from(body.clone()))
+            .unwrap();
+        future::ready(res)
+    }
+}
+
+/// An endpoint serving `content` as `/robots.txt`.
+pub fn robots_txt(
+    content: impl Into<String>,
+) -> impl Fn() -> future::Ready<Response> + Clone + Send + Sync + 'static {
+    let content = Bytes::from(content.into());
+    fixed("text/plain; charset=utf-8", "public, max-age=3600", content)
+}
+
+/// An endpoint serving an ICO image as `/favicon.ico`.
+pub fn favicon(
+    icon: impl Into<Bytes>,
+) -> impl Fn() -> future::Ready<Response> + Clone + Send + Sync + 'static {
+    fixed("image/x-icon", "public, max-age=86400", icon.into())
+}
+
+/// An endpoint serving `content` as `/.well-known/security.txt` (RFC 9116).
+pub fn security_txt(
+    content: impl Into<String>,
+) -> impl Fn() -> future::Ready<Response> + Clone + Send + Sync + 'static {
+    let content = Bytes::from(content.into());
+    fixed("text/plain; charset=utf-8", "public, max-age=3600", content)
+}
+
+/// Whether `token` is a valid ACME challenge token, i.e. base64url without padding.
+///
+/// This also keeps tokens from escaping the challenge directory.
+fn valid_token(token: &str) -> bool {
+    !token.is_empty()
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn key_authorization(key_authorization: Option<Vec<u8>>) -> Response {
+    match key_authorization {
+        Some(key_authorization) => http::Response::builder()
+            .header("Content-Type", "application/octet-stream")
+            .header("Cache-Control", "no-store")
+            .body(Body::from(key_authorization))
+            .unwrap(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// An endpoint answering ACME HTTP-01 challenges with the key authorizations returned by
+/// `callback`, for the route `/.well-known/acme-challenge/{}`.
+///
+/// `callback` is given the challenge token; returning `None` answers `404 Not Found`.
+pub fn acme_challenge<F>(
+    callback: F,
+) -> impl Fn(Path<String>) -> future::Ready<Response> + Clone + Send + Sync + 'static
+where
+    F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+{
+    let callback = Arc::new(callback);
+    move |Path(token): Path<String>| {
+        let found = if valid_token(&token) {
+            callback(&token).map(String::into_bytes)
+        } else {
+            None
+        };
+        future::ready(key_authorization(found))
+    }
+}
+
+/// An endpoint answering ACME HTTP-01 challenges from files in `dir`, named after their
+/// tokens, for the route `/.well-known/acme-challenge/{}`.
+///
+/// This is how ACME clients such as certbot's `webroot` plugin publish their challenges, with
+/// `dir` being `<webroot>/.well-known/acme-challenge`. The files are small and rarely requested,
+/// so they are read synchronously.
+pub fn acme_challenge_dir(
+    dir: impl Into<PathBuf>,
+) -> impl Fn(Path<String>) -> future::Ready<Response> + Clone + Send + Sync + 'static {
+    let dir = Arc::new(dir.into());
+    move |Path(token): Path<String>| {
+        let found = if valid_token(&token) {
+            std::fs::read(dir.join(&token)).ok()
+        } else {
+            None
+        };
+        future::ready(key_authorization(found))
+    }
+}
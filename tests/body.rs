@@ -0,0 +1,146 @@
+#![feature(futures_api, async_await, await_macro)]
+
+use futures::{executor::block_on, future::FutureObj};
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{
+    body,
+    configuration::Configuration,
+    middleware::{BufferBody, DefaultHeaders, Recorder, RequestContext},
+    IntoResponse, Response,
+};
+
+async fn echo(msg: body::Str) -> String {
+    msg.0
+}
+
+async fn echo_twice(first: body::Str, second: body::Bytes) -> String {
+    format!("{}{:?}", *first, *second)
+}
+
+#[test]
+fn single_body_extractor() {
+    let mut app = tide::App::new(());
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo").body(Body::from("foo")).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"foo");
+}
+
+#[test]
+fn multiple_body_extractors_fail() {
+    let mut app = tide::App::new(());
+    app.at("/echo").post(echo_twice);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo").body(Body::from("foo")).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 500);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert!(String::from_utf8(body).unwrap().contains("already been consumed"));
+}
+
+#[test]
+fn buffered_body_replays() {
+    let mut app = tide::App::new(());
+    app.middleware(BufferBody::new());
+    app.at("/echo").post(echo_twice);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo").body(Body::from("foo")).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"foo[102, 111, 111]");
+}
+
+#[test]
+fn max_body_size() {
+    let mut app = tide::App::new(());
+    app.config(Configuration::build().max_body_size(3).finalize());
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo").body(Body::from("foo")).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 200);
+
+    let req = http::Request::post("/echo")
+        .header("Content-Length", "4")
+        .body(Body::from("food"))
+        .unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 413);
+
+    let req = http::Request::post("/echo").body(Body::from("food")).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 413);
+}
+
+/// Read the body as a stream, the way a proxy or an upload endpoint does, and answer with its
+/// length.
+fn streamed_length(mut ctx: RequestContext<()>) -> FutureObj<Response> {
+    let body = std::mem::replace(ctx.req.body_mut(), Body::empty());
+    FutureObj::new(Box::new(
+        async move {
+            match await!(body.into_vec()) {
+                Ok(bytes) => bytes.len().to_string().into_response(),
+                Err(_) => http::StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            }
+        },
+    ))
+}
+
+#[test]
+fn default_max_body_size() {
+    let large = vec![b'a'; 2 * 1024 * 1024 + 1];
+
+    let mut app = tide::App::new(());
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+    let req = http::Request::post("/echo").body(Body::from(large.clone())).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 413);
+
+    // Only bodies read into memory are limited by default.
+    let mut app = tide::App::new(());
+    app.middleware(streamed_length);
+    app.at("/upload").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+    let req = http::Request::post("/upload")
+        .header("Content-Length", large.len().to_string())
+        .body(Body::from(large.clone()))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(body, large.len().to_string().as_bytes());
+
+    let mut app = tide::App::new(());
+    app.config(Configuration::build().unlimited_body_size().finalize());
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+    let req = http::Request::post("/echo").body(Body::from(large)).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 200);
+}
+
+#[test]
+fn max_body_size_through_middleware() {
+    let mut app = tide::App::new(());
+    app.config(Configuration::build().max_body_size(3).finalize());
+    app.middleware(DefaultHeaders::new().header("X-Seen", "1"));
+    app.middleware(Recorder::new());
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo").body(Body::from("food")).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 413);
+
+    let req = http::Request::post("/echo")
+        .header("Content-Length", "4")
+        .body(Body::from("food"))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 413);
+    assert_eq!(res.headers()["X-Seen"], "1");
+}
@@ -0,0 +1,64 @@
+#![feature(futures_api, async_await)]
+
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::middleware::CacheControl;
+
+#[test]
+fn cache_rules() {
+    let mut app = tide::App::new(());
+    app.middleware(
+        CacheControl::new()
+            .rule("/assets/*", "public, max-age=31536000, immutable")
+            .rule("/index.html", "no-cache"),
+    );
+    app.at("/assets/*").get(async || "asset");
+    app.at("/index.html").get(async || "index");
+    app.at("/other").get(async || "other");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/assets/app.1234.js")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(
+        res.headers()["Cache-Control"],
+        "public, max-age=31536000, immutable"
+    );
+
+    let req = http::Request::get("/index.html").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["Cache-Control"], "no-cache");
+
+    let req = http::Request::get("/other").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert!(!res.headers().contains_key("Cache-Control"));
+
+    let req = http::Request::get("/assets").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers()["Cache-Control"],
+        "public, max-age=31536000, immutable"
+    );
+}
+
+#[test]
+fn constrained_rules() {
+    let mut app = tide::App::new(());
+    app.middleware(
+        CacheControl::new()
+            .rule("/users/{id:u32}", "private, max-age=60")
+            .rule("/users/{name}", "no-store"),
+    );
+    app.at("/users/{}").get(async || "user");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/users/42").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["Cache-Control"], "private, max-age=60");
+
+    let req = http::Request::get("/users/me").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["Cache-Control"], "no-store");
+}
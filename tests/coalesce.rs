@@ -0,0 +1,83 @@
+#![feature(futures_api, async_await, await_macro)]
+
+use futures::{executor::block_on, future, prelude::*};
+use http_service::{Body, HttpService};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use tide::{middleware::Coalesce, AppData};
+
+type Counter = Arc<AtomicUsize>;
+
+fn yield_once() -> impl Future<Output = ()> {
+    let mut yielded = false;
+    future::poll_fn(move |waker| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            waker.wake();
+            Poll::Pending
+        }
+    })
+}
+
+async fn expensive(counter: AppData<Counter>) -> String {
+    let n = counter.fetch_add(1, Ordering::SeqCst);
+    await!(yield_once());
+    n.to_string()
+}
+
+fn body(res: Result<http_service::Response, std::io::Error>) -> Vec<u8> {
+    block_on(res.unwrap().into_body().into_vec()).unwrap()
+}
+
+#[test]
+fn hosts_are_not_coalesced() {
+    let counter = Counter::default();
+    let mut app = tide::App::new(counter.clone());
+    app.middleware(Coalesce::new());
+    app.host("a.example.com").at("/").get(expensive);
+    app.host("b.example.com").at("/").get(expensive);
+    let server = app.into_http_service();
+
+    let request = |host: &str| {
+        http::Request::get("/")
+            .header("Host", host)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let a = server.respond(&mut (), request("a.example.com")).into_future();
+    let b = server.respond(&mut (), request("b.example.com")).into_future();
+    let (a, b) = block_on(future::join(a, b));
+
+    let mut bodies = vec![body(a), body(b)];
+    bodies.sort();
+    assert_eq!(bodies, [b"0", b"1"]);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn concurrent_requests_share_a_response() {
+    let counter = Counter::default();
+    let mut app = tide::App::new(counter.clone());
+    app.middleware(Coalesce::new());
+    app.at("/report").get(expensive);
+    let server = app.into_http_service();
+
+    let request = |user: &str| {
+        http::Request::get("/report")
+            .header("Authorization", user)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let first = server.respond(&mut (), request("alice")).into_future();
+    let second = server.respond(&mut (), request("alice")).into_future();
+    let other = server.respond(&mut (), request("bob")).into_future();
+    let (first, second, other) = block_on(future::join3(first, second, other));
+
+    assert_eq!(body(first), b"0");
+    assert_eq!(body(second), b"0");
+    assert_eq!(body(other), b"1");
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
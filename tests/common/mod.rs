@@ -0,0 +1,37 @@
+//! Helpers shared by the integration tests, included with `mod common;`.
+
+#![allow(dead_code)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::{make_server, TestBackend};
+
+/// A mock server running `app`.
+pub fn server<Data>(app: tide::App<Data>) -> TestBackend<tide::Server<Data>>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    make_server(app.into_http_service()).unwrap()
+}
+
+/// A `GET` request for `path`, without a body.
+pub fn get(path: &str) -> http::Request<Body> {
+    http::Request::get(path).body(Body::empty()).unwrap()
+}
+
+/// The response of `server` to `req`, with its body read.
+pub fn simulate<Data>(
+    server: &mut TestBackend<tide::Server<Data>>,
+    req: http::Request<Body>,
+) -> http::Response<Vec<u8>>
+where
+    Data: Clone + Send + Sync + 'static,
+{
+    let (parts, body) = server.simulate(req).unwrap().into_parts();
+    http::Response::from_parts(parts, block_on(body.into_vec()).unwrap())
+}
+
+/// The body of `res` as text.
+pub fn text(res: &http::Response<Vec<u8>>) -> String {
+    String::from_utf8(res.body().clone()).unwrap()
+}
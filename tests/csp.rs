@@ -0,0 +1,55 @@
+#![feature(futures_api, async_await)]
+
+use tide::middleware::{ContentSecurityPolicy, CspNonce};
+
+mod common;
+
+async fn page(nonce: CspNonce) -> String {
+    nonce.to_string()
+}
+
+fn server(csp: ContentSecurityPolicy) -> impl FnMut() -> (http::HeaderMap, String) {
+    let mut app = tide::App::new(());
+    app.middleware(csp);
+    app.at("/").get(page);
+    let mut server = common::server(app);
+    move || {
+        let res = common::simulate(&mut server, common::get("/"));
+        (res.headers().clone(), common::text(&res))
+    }
+}
+
+#[test]
+fn fresh_nonce_per_request() {
+    let mut get = server(ContentSecurityPolicy::new("script-src 'nonce-{nonce}'"));
+
+    let (headers, first) = get();
+    assert_eq!(first.len(), 32);
+    assert_eq!(
+        headers["Content-Security-Policy"],
+        format!("script-src 'nonce-{}'", first)
+    );
+
+    let (_, second) = get();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn report_only() {
+    let csp = ContentSecurityPolicy::new("script-src 'nonce-{nonce}'").report_only(true);
+    let mut get = server(csp);
+    let (headers, nonce) = get();
+    assert!(!headers.contains_key("Content-Security-Policy"));
+    assert_eq!(
+        headers["Content-Security-Policy-Report-Only"],
+        format!("script-src 'nonce-{}'", nonce)
+    );
+}
+
+#[test]
+fn nonce_requires_middleware() {
+    let mut app = tide::App::new(());
+    app.at("/").get(page);
+    let res = common::simulate(&mut common::server(app), common::get("/"));
+    assert_eq!(res.status(), 500);
+}
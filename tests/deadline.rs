@@ -0,0 +1,90 @@
+#![feature(futures_api, async_await, await_macro)]
+
+use http_service::Body;
+use std::time::Duration;
+use tide::{configuration::RequestTimeout, head::Deadline, middleware::DefaultHeaders};
+
+mod common;
+
+async fn budget(deadline: Deadline) -> String {
+    match deadline.remaining() {
+        Some(remaining) => (remaining.as_secs() + 1).to_string(),
+        None => "none".to_string(),
+    }
+}
+
+fn simulate(app: tide::App<()>, header: Option<(&str, &str)>) -> String {
+    let mut req = http::Request::get("/");
+    if let Some((name, value)) = header {
+        req.header(name, value);
+    }
+    let req = req.body(Body::empty()).unwrap();
+    common::text(&common::simulate(&mut common::server(app), req))
+}
+
+#[test]
+fn no_deadline() {
+    let mut app = tide::App::new(());
+    app.at("/").get(budget);
+    assert_eq!(simulate(app, None), "none");
+}
+
+#[test]
+fn configured_deadline() {
+    let mut app = tide::App::new(());
+    app.config(RequestTimeout(Duration::from_secs(30)));
+    app.at("/").get(budget);
+    assert_eq!(simulate(app, None), "30");
+}
+
+#[test]
+fn requested_deadline_is_earlier() {
+    let mut app = tide::App::new(());
+    app.config(RequestTimeout(Duration::from_secs(30)));
+    app.at("/").get(budget);
+    assert_eq!(simulate(app, Some(("X-Request-Timeout", "5"))), "5");
+
+    let mut app = tide::App::new(());
+    app.at("/").get(budget);
+    assert_eq!(simulate(app, Some(("grpc-timeout", "2S"))), "2");
+}
+
+#[test]
+fn unrepresentable_requests_are_ignored() {
+    for &(name, value) in &[
+        ("X-Request-Timeout", "1e30"),
+        ("X-Request-Timeout", "inf"),
+        ("X-Request-Timeout", "NaN"),
+        ("X-Request-Timeout", "-1"),
+        ("grpc-timeout", "99999999999999H"),
+        ("grpc-timeout", "123456789S"),
+        ("grpc-timeout", "+5S"),
+    ] {
+        let mut app = tide::App::new(());
+        app.at("/").get(budget);
+        assert_eq!(simulate(app, Some((name, value))), "none", "{}: {}", name, value);
+    }
+
+    let mut app = tide::App::new(());
+    app.at("/").get(budget);
+    assert_ne!(simulate(app, Some(("grpc-timeout", "99999999H"))), "none");
+}
+
+#[test]
+fn exhausted_budget_cancels_the_endpoint() {
+    let mut app = tide::App::new(());
+    app.config(RequestTimeout(Duration::from_millis(10)));
+    app.middleware(DefaultHeaders::new().header("X-Seen", "1"));
+    app.at("/slow").get(async || {
+        let _ = await!(futures_timer::Delay::new(Duration::from_secs(10)));
+        "late"
+    });
+    app.at("/fast").get(async || "fast");
+    let mut server = common::server(app);
+
+    let res = common::simulate(&mut server, common::get("/slow"));
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.headers()["X-Seen"], "1");
+    let res = common::simulate(&mut server, common::get("/fast"));
+    assert_eq!(res.status(), 200);
+}
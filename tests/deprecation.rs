@@ -0,0 +1,57 @@
+#![feature(futures_api, async_await)]
+
+use chrono::{TimeZone, Utc};
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::middleware::DeprecationHeaders;
+
+#[test]
+fn deprecated_resource() {
+    let mut app = tide::App::new(());
+    app.middleware(DeprecationHeaders::new());
+    app.at("/v1")
+        .deprecated(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0), "https://example.com/v2")
+        .get(async || "v1");
+    app.at("/v2").get(async || "v2");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/v1").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["Deprecation"], "true");
+    assert_eq!(res.headers()["Sunset"], "Wed, 01 Jan 2020 00:00:00 GMT");
+    assert_eq!(
+        res.headers()["Link"],
+        "<https://example.com/v2>; rel=\"deprecation\""
+    );
+
+    let req = http::Request::get("/v2").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert!(!res.headers().contains_key("Deprecation"));
+    assert!(!res.headers().contains_key("Sunset"));
+}
+
+#[test]
+fn keeps_other_links() {
+    let mut app = tide::App::new(());
+    app.middleware(DeprecationHeaders::new());
+    app.at("/v1")
+        .deprecated(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0), "https://example.com/v2")
+        .get(async || {
+            http::Response::builder()
+                .header("Link", "</style.css>; rel=preload; as=style")
+                .body(Body::empty())
+                .unwrap()
+        });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/v1").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    let links: Vec<_> = res.headers().get_all("Link").iter().collect();
+    assert_eq!(
+        links,
+        [
+            "</style.css>; rel=preload; as=style",
+            "<https://example.com/v2>; rel=\"deprecation\""
+        ]
+    );
+}
@@ -0,0 +1,63 @@
+#![feature(futures_api, async_await, await_macro)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use tide::{head::Path, Dispatcher, Response};
+
+mod common;
+
+async fn item(Path(id): Path<u32>) -> String {
+    format!("item {}", id)
+}
+
+async fn forward(dispatcher: Dispatcher<()>) -> Response {
+    let req = http::Request::get("/items/1").body(Body::empty()).unwrap();
+    await!(dispatcher.dispatch(req))
+}
+
+async fn batch(dispatcher: Dispatcher<()>) -> String {
+    let mut bodies = Vec::new();
+    for id in 1..3 {
+        let req = http::Request::get(&*format!("/items/{}", id))
+            .body(Body::empty())
+            .unwrap();
+        let res = await!(dispatcher.dispatch(req));
+        bodies.push(String::from_utf8(await!(res.into_body().into_vec()).unwrap()).unwrap());
+    }
+    bodies.join(", ")
+}
+
+fn get(path: &str) -> (http::StatusCode, String) {
+    let mut app = tide::App::new(());
+    app.middleware(tide::middleware::DefaultHeaders::new().header("X-App", "yes"));
+    app.at("/items/{}").get(item);
+    app.at("/forward").get(forward);
+    app.at("/batch").get(batch);
+
+    let res = common::simulate(&mut common::server(app), common::get(path));
+    assert_eq!(res.headers()["X-App"], "yes");
+    (res.status(), common::text(&res))
+}
+
+#[test]
+fn forward_to_another_route() {
+    assert_eq!(get("/forward"), (http::StatusCode::OK, "item 1".to_owned()));
+}
+
+#[test]
+fn compose_routes() {
+    assert_eq!(get("/batch"), (http::StatusCode::OK, "item 1, item 2".to_owned()));
+}
+
+#[test]
+fn dispatch_from_server() {
+    let mut app = tide::App::new(());
+    app.at("/items/{}").get(item);
+    let server = app.into_http_service();
+
+    let res = block_on(server.dispatch(common::get("/items/7")));
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"item 7");
+
+    assert_eq!(block_on(server.dispatch(common::get("/missing"))).status(), 404);
+}
@@ -0,0 +1,79 @@
+#![feature(futures_api, async_await)]
+
+use futures::{executor::block_on, future::FutureObj};
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{middleware::RequestContext, ConnectionInfo, FromExtension, Response};
+
+#[derive(Clone)]
+struct User(&'static str);
+
+fn authenticate(mut ctx: RequestContext<()>) -> FutureObj<Response> {
+    ctx.req.extensions_mut().insert(User("alice"));
+    ctx.next()
+}
+
+async fn whoami(FromExtension(user): FromExtension<User>) -> &'static str {
+    user.0
+}
+
+#[test]
+fn value_from_middleware() {
+    let mut app = tide::App::new(());
+    app.middleware(authenticate);
+    app.at("/whoami").get(whoami);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/whoami").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"alice");
+}
+
+#[test]
+fn missing_value_error() {
+    let mut app = tide::App::new(());
+    app.at("/whoami").get(whoami);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/whoami").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 500);
+}
+
+async fn describe(body: tide::body::Str, parts: tide::head::Parts) -> String {
+    let user = parts.extensions().get::<User>().map_or("nobody", |user| user.0);
+    format!("{} {} {} {}", parts.method(), parts.path(), user, body.0)
+}
+
+#[test]
+fn extensions_in_parts() {
+    let mut app = tide::App::new(());
+    app.middleware(authenticate);
+    app.at("/describe").post(describe);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/describe").body(Body::from("hi")).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"POST /describe alice hi");
+}
+
+async fn peer(info: ConnectionInfo) -> String {
+    format!("{:?}", info.peer_addr())
+}
+
+#[test]
+fn connection_info_without_connection() {
+    let mut app = tide::App::new(());
+    app.at("/peer").get(peer);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/peer").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"None");
+}
@@ -0,0 +1,98 @@
+#![feature(futures_api, async_await)]
+
+use http_service::Body;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tide::{middleware::idempotency::Idempotency, AppData};
+
+mod common;
+
+type Counter = Arc<AtomicUsize>;
+
+async fn charge(counter: AppData<Counter>) -> String {
+    counter.fetch_add(1, Ordering::SeqCst).to_string()
+}
+
+fn app(counter: &Counter) -> tide::App<Counter> {
+    let mut app = tide::App::new(counter.clone());
+    app.middleware(Idempotency::new());
+    app.at("/charge").post(charge);
+    app.at("/charge").get(charge);
+    app
+}
+
+fn request(method: http::Method, key: Option<&str>) -> http::Request<Body> {
+    let mut req = http::Request::builder();
+    req.method(method).uri("/charge");
+    if let Some(key) = key {
+        req.header("Idempotency-Key", key);
+    }
+    req.body(Body::empty()).unwrap()
+}
+
+fn read(res: &http::Response<Vec<u8>>) -> (String, bool) {
+    (common::text(res), res.headers().contains_key("Idempotent-Replayed"))
+}
+
+#[test]
+fn replays_retries() {
+    let counter = Counter::default();
+    let mut server = common::server(app(&counter));
+
+    let res = common::simulate(&mut server, request(http::Method::POST, Some("a")));
+    assert_eq!(read(&res), ("0".to_string(), false));
+    let res = common::simulate(&mut server, request(http::Method::POST, Some("a")));
+    assert_eq!(read(&res), ("0".to_string(), true));
+    let res = common::simulate(&mut server, request(http::Method::POST, Some("b")));
+    assert_eq!(read(&res), ("1".to_string(), false));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn ignores_other_requests() {
+    let counter = Counter::default();
+    let mut server = common::server(app(&counter));
+
+    for expected in 0..2 {
+        let res = common::simulate(&mut server, request(http::Method::POST, None));
+        assert_eq!(read(&res), (expected.to_string(), false));
+    }
+    for expected in 2..4 {
+        let res = common::simulate(&mut server, request(http::Method::GET, Some("a")));
+        assert_eq!(read(&res), (expected.to_string(), false));
+    }
+}
+
+#[test]
+fn scopes_keys_to_the_caller() {
+    let counter = Counter::default();
+    let mut server = common::server(app(&counter));
+    let as_user = |user: &str| {
+        http::Request::post("/charge")
+            .header("Idempotency-Key", "a")
+            .header("Authorization", user)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    assert_eq!(read(&common::simulate(&mut server, as_user("alice"))), ("0".to_string(), false));
+    assert_eq!(read(&common::simulate(&mut server, as_user("mallory"))), ("1".to_string(), false));
+    assert_eq!(read(&common::simulate(&mut server, as_user("alice"))), ("0".to_string(), true));
+}
+
+#[test]
+fn rejects_reused_keys_with_another_body() {
+    let counter = Counter::default();
+    let mut server = common::server(app(&counter));
+    let with_body = |body: &'static str| {
+        http::Request::post("/charge")
+            .header("Idempotency-Key", "a")
+            .body(Body::from(body))
+            .unwrap()
+    };
+
+    assert_eq!(common::simulate(&mut server, with_body("10")).status(), 200);
+    assert_eq!(common::simulate(&mut server, with_body("10")).status(), 200);
+    assert_eq!(common::simulate(&mut server, with_body("20")).status(), 422);
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,40 @@
+#![feature(futures_api, async_await)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::info::{self, BuildInfo};
+
+#[test]
+fn serves_build_info() {
+    let mut app = tide::App::new(());
+    app.config(BuildInfo {
+        version: "1.2.0".to_owned(),
+        git_sha: Some("4cff7a9".to_owned()),
+        build_timestamp: None,
+    });
+    app.at("/info").get(info::endpoint());
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/info").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Content-Type"], "application/json");
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["version"], "1.2.0");
+    assert_eq!(json["git_sha"], "4cff7a9");
+    assert!(json["build_timestamp"].is_null());
+    assert!(json["uptime"].is_u64());
+}
+
+#[test]
+fn missing_build_info() {
+    let mut app = tide::App::new(());
+    app.at("/info").get(info::endpoint());
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/info").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 500);
+}
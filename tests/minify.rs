@@ -0,0 +1,63 @@
+#![feature(futures_api, async_await)]
+
+use http_service::Body;
+use tide::{middleware::Minify, Response};
+
+mod common;
+
+async fn page() -> Response {
+    http::Response::builder()
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Content-Length", "40")
+        .body(Body::from("<ul>\n    <li>a</li>\n    <li>b</li>\n</ul>"))
+        .unwrap()
+}
+
+async fn data() -> Response {
+    http::Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from("{\n  \"a b\": [1, 2]\n}"))
+        .unwrap()
+}
+
+async fn text() -> &'static str {
+    "a    b"
+}
+
+fn get(minify: Minify, path: &str) -> (http::HeaderMap, String) {
+    let mut app = tide::App::new(());
+    app.middleware(minify);
+    app.at("/page").get(page);
+    app.at("/data").get(data);
+    app.at("/text").get(text);
+    let mut server = common::server(app);
+
+    let res = common::simulate(&mut server, common::get(path));
+    (res.headers().clone(), common::text(&res))
+}
+
+#[test]
+fn minify_html() {
+    let (headers, body) = get(Minify::new(), "/page");
+    assert_eq!(body, "<ul>\n<li>a</li>\n<li>b</li>\n</ul>");
+    assert!(!headers.contains_key("Content-Length"));
+}
+
+#[test]
+fn minify_json() {
+    let (_, body) = get(Minify::new(), "/data");
+    assert_eq!(body, r#"{"a b":[1,2]}"#);
+}
+
+#[test]
+fn other_content_types_untouched() {
+    let (_, body) = get(Minify::new(), "/text");
+    assert_eq!(body, "a    b");
+}
+
+#[test]
+fn debug_disables_minification() {
+    let (headers, body) = get(Minify::new().debug(true), "/page");
+    assert_eq!(body, "<ul>\n    <li>a</li>\n    <li>b</li>\n</ul>");
+    assert_eq!(headers["Content-Length"], "40");
+}
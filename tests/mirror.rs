@@ -0,0 +1,80 @@
+#![feature(futures_api, async_await)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::make_server;
+use std::sync::{Arc, Mutex};
+use tide::{body, middleware::Mirror};
+
+async fn echo(body: body::Str) -> String {
+    body.0
+}
+
+#[test]
+fn mirrored_request() {
+    let mirrored = Arc::new(Mutex::new(Vec::new()));
+    let sink = mirrored.clone();
+
+    let mut app = tide::App::new(());
+    app.middleware(Mirror::new(move |req| sink.lock().unwrap().push(req)));
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/echo?x=1")
+        .header("X-Test", "yes")
+        .header("Content-Length", "5")
+        .body(Body::from("hello"))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"hello");
+
+    let mirrored = mirrored.lock().unwrap();
+    assert_eq!(mirrored.len(), 1);
+    assert_eq!(mirrored[0].method(), http::Method::POST);
+    assert_eq!(mirrored[0].uri(), "/echo?x=1");
+    assert_eq!(mirrored[0].headers()["X-Test"], "yes");
+    assert_eq!(&mirrored[0].body()[..], &b"hello"[..]);
+}
+
+#[test]
+fn large_bodies_not_mirrored() {
+    let mirrored = Arc::new(Mutex::new(0));
+    let counter = mirrored.clone();
+
+    let mut app = tide::App::new(());
+    app.middleware(Mirror::new(move |_| *counter.lock().unwrap() += 1).max_body_size(4));
+    app.at("/echo").post(echo);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for length in &[Some("5"), None] {
+        let mut req = http::Request::post("/echo");
+        match length {
+            Some(length) => req.header("Content-Length", *length),
+            None => req.header("Transfer-Encoding", "chunked"),
+        };
+        let res = server.simulate(req.body(Body::from("hello")).unwrap()).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, &*b"hello");
+    }
+    assert_eq!(*mirrored.lock().unwrap(), 0);
+}
+
+#[test]
+fn sampled_mirroring() {
+    let mirrored = Arc::new(Mutex::new(0));
+    let counter = mirrored.clone();
+
+    let mut app = tide::App::new(());
+    app.middleware(Mirror::new(move |_| *counter.lock().unwrap() += 1).sample(0.5));
+    app.at("/").get(async || "");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for _ in 0..4 {
+        let req = http::Request::get("/").body(Body::empty()).unwrap();
+        assert_eq!(server.simulate(req).unwrap().status(), 200);
+    }
+    assert_eq!(*mirrored.lock().unwrap(), 2);
+}
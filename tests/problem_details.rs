@@ -0,0 +1,79 @@
+#![feature(futures_api, async_await)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{middleware::ProblemDetails, IntoResponse};
+
+fn make_app() -> tide::App<()> {
+    let mut app = tide::App::new(());
+    app.middleware(ProblemDetails::new());
+    app.at("/teapot").get(async || "short and stout".with_status(http::StatusCode::IM_A_TEAPOT));
+    app.at("/unavailable").get(async || {
+        http::Response::builder()
+            .status(http::StatusCode::SERVICE_UNAVAILABLE)
+            .header("Content-Length", "0")
+            .header("Retry-After", "120")
+            .body(Body::empty())
+            .unwrap()
+    });
+    app
+}
+
+#[test]
+fn not_found_as_problem() {
+    let mut server = make_server(make_app().into_http_service()).unwrap();
+
+    let req = http::Request::get("/missing")
+        .header("Accept", "application/problem+json")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+    assert_eq!(res.headers()["Content-Type"], "application/problem+json");
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], 404);
+    assert_eq!(json["title"], "Not Found");
+}
+
+#[test]
+fn replaced_body_headers_dropped() {
+    let mut server = make_server(make_app().into_http_service()).unwrap();
+
+    let req = http::Request::get("/unavailable")
+        .header("Accept", "application/problem+json")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 503);
+    assert_eq!(res.headers()["Retry-After"], "120");
+    assert!(!res.headers().contains_key("Content-Length"));
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["status"], 503);
+}
+
+#[test]
+fn not_accepted() {
+    let mut server = make_server(make_app().into_http_service()).unwrap();
+
+    let req = http::Request::get("/missing").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+    assert!(!res.headers().contains_key("Content-Type"));
+}
+
+#[test]
+fn error_with_body_untouched() {
+    let mut server = make_server(make_app().into_http_service()).unwrap();
+
+    let req = http::Request::get("/teapot")
+        .header("Accept", "application/problem+json")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 418);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*b"short and stout");
+}
@@ -0,0 +1,109 @@
+#![feature(futures_api, async_await)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{body, middleware::Recorder};
+
+async fn echo(body: body::Str) -> String {
+    body.0
+}
+
+#[test]
+fn har_export() {
+    let recorder = Recorder::new().max_entries(2).max_body_size(4);
+    let mut app = tide::App::new(());
+    app.middleware(recorder.clone());
+    app.at("/echo").post(echo);
+    app.at("/har").get(recorder.endpoint());
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for body in &["first", "second", "third"] {
+        let req = http::Request::post("/echo?lang=en")
+            .body(Body::from(*body))
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let echoed = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*echoed, body.as_bytes());
+    }
+
+    let req = http::Request::get("/har").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let har = block_on(res.into_body().into_vec()).unwrap();
+    let har: serde_json::Value = serde_json::from_slice(&har).unwrap();
+
+    let entries = har["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    let entry = &entries[1];
+    assert_eq!(entry["request"]["method"], "POST");
+    assert_eq!(entry["request"]["queryString"][0]["name"], "lang");
+    assert_eq!(entry["request"]["postData"]["text"], "thir");
+    assert_eq!(entry["request"]["bodySize"], 5);
+    assert_eq!(entry["response"]["status"], 200);
+    assert_eq!(entry["response"]["content"]["text"], "thir");
+    assert_eq!(entry["response"]["content"]["size"], 5);
+}
+
+#[test]
+fn records_only_what_the_app_reads() {
+    let recorder = Recorder::new();
+    let mut app = tide::App::new(());
+    app.middleware(recorder.clone());
+    app.at("/ignore").post(async || "ignored");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/ignore")
+        .body(Body::from("unread"))
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    block_on(res.into_body().into_vec()).unwrap();
+
+    let har: serde_json::Value = serde_json::from_str(&recorder.to_har()).unwrap();
+    let entry = &har["log"]["entries"][0];
+    assert_eq!(entry["request"]["bodySize"], 0);
+    assert!(entry["request"].get("postData").is_none());
+}
+
+#[test]
+fn sensitive_headers_redacted() {
+    let recorder = Recorder::new().redact("X-Api-Key");
+    let mut app = tide::App::new(());
+    app.middleware(recorder.clone());
+    app.at("/login").get(async || {
+        http::Response::builder()
+            .header("Set-Cookie", "session=secret")
+            .body(Body::empty())
+            .unwrap()
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/login")
+        .header("Authorization", "Bearer secret")
+        .header("Cookie", "session=secret")
+        .header("X-Api-Key", "secret")
+        .header("Accept", "text/html")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    block_on(res.into_body().into_vec()).unwrap();
+
+    let har = recorder.to_har();
+    assert!(!har.contains("secret"), "{}", har);
+    let har: serde_json::Value = serde_json::from_str(&har).unwrap();
+    let entry = &har["log"]["entries"][0];
+    let value = |headers: &serde_json::Value, name: &str| {
+        headers
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|header| header["name"] == name)
+            .map(|header| header["value"].clone())
+    };
+    let request = &entry["request"]["headers"];
+    assert_eq!(value(request, "authorization").unwrap(), "[redacted]");
+    assert_eq!(value(request, "x-api-key").unwrap(), "[redacted]");
+    assert_eq!(value(request, "accept").unwrap(), "text/html");
+    let response = &entry["response"]["headers"];
+    assert_eq!(value(response, "set-cookie").unwrap(), "[redacted]");
+}
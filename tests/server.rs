@@ -0,0 +1,590 @@
+#![feature(futures_api, async_await, await_macro)]
+
+use futures::future::FutureObj;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{
+    configuration::{Configuration, TrailingSlash},
+    head::{self, RoutePattern},
+    middleware::{DefaultHeaders, RequestContext},
+    IntoResponse, Response,
+};
+
+#[test]
+fn bind_port_zero() {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "/");
+    let bound = app
+        .into_http_service()
+        .bind(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    assert_ne!(bound.local_addr().port(), 0);
+    assert!(std::net::TcpStream::connect(bound.local_addr()).is_ok());
+}
+
+#[test]
+fn bind_listener() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = tide::App::new(());
+    let bound = app.into_http_service().bind_listener(listener).unwrap();
+
+    assert_eq!(bound.local_addr(), addr);
+}
+
+/// Serve `app` on a runtime of its own, at a port picked by the system.
+fn serve(app: tide::App<()>) -> (tokio::runtime::Runtime, std::net::SocketAddr) {
+    use futures::TryFutureExt;
+    use futures01::Future;
+
+    let bound = app
+        .into_http_service()
+        .bind(([127, 0, 0, 1], 0).into())
+        .unwrap();
+    let addr = bound.local_addr();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(bound.run().compat().map_err(|e| panic!("{}", e)));
+    (runtime, addr)
+}
+
+#[test]
+fn slow_responses_outlive_the_read_timeout() {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    let mut app = tide::App::new(());
+    app.config(
+        Configuration::build()
+            .read_timeout(Duration::from_millis(50))
+            .finalize(),
+    );
+    app.at("/").get(async || {
+        let _ = await!(futures_timer::Delay::new(Duration::from_millis(300)));
+        "slow"
+    });
+    let (_runtime, addr) = serve(app);
+
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut res = String::new();
+    stream.read_to_string(&mut res).unwrap();
+    assert!(res.starts_with("HTTP/1.1 200"), "{}", res);
+    assert!(res.ends_with("slow"), "{}", res);
+}
+
+#[test]
+fn idle_connections_are_closed() {
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    let mut app = tide::App::new(());
+    app.config(
+        Configuration::build()
+            .idle_timeout(Duration::from_millis(100))
+            .finalize(),
+    );
+    let (_runtime, addr) = serve(app);
+
+    let start = Instant::now();
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn listener_per_worker() {
+    use std::io::{Read, Write};
+
+    let mut app = tide::App::new(());
+    app.config(Configuration::build().reuse_port(true).workers(4).finalize());
+    app.at("/").get(async || "hello");
+    let (_runtime, addr) = serve(app);
+
+    for _ in 0..8 {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.ends_with("hello"), "{}", res);
+    }
+}
+
+#[test]
+fn lifecycle_hooks() {
+    use futures::TryFutureExt;
+    use std::sync::{Arc, Mutex};
+
+    type Events = Arc<Mutex<Vec<&'static str>>>;
+
+    let events = Events::default();
+    let mut app = tide::App::new(events.clone());
+    app.on_start(async move |events: Events| events.lock().unwrap().push("start"));
+    app.on_stop(async move |events: Events| events.lock().unwrap().push("stop"));
+    let bound = app
+        .into_http_service()
+        .bind(([127, 0, 0, 1], 0).into())
+        .unwrap();
+
+    let serving = bound.run_with_shutdown(futures::future::ready(()));
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(serving.compat()).unwrap();
+    assert_eq!(*events.lock().unwrap(), ["start", "stop"]);
+}
+
+#[test]
+fn server_accessors() {
+    let mut app = tide::App::new(42usize);
+    app.at("/").get(async || "/");
+    let server = app.into_http_service();
+
+    assert_eq!(*server.data(), 42);
+    assert_eq!(server.router().get_item::<Configuration>().unwrap().port, 8181);
+}
+
+#[test]
+fn shared_data() {
+    use tide::SharedData;
+
+    let mut app = tide::App::new("site".to_string());
+    app.at("/").get(async move |name: SharedData<String>| name.to_string());
+    app.at("/address")
+        .get(async move |name: SharedData<String>| format!("{:p}", &*name));
+    app.at("/admin").nest(|router| {
+        router.data("admin".to_string());
+        router
+            .at("/")
+            .get(async move |name: SharedData<String>| name.to_string());
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+    let mut get = |path: &str| {
+        let req = http::Request::get(path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        futures::executor::block_on(res.into_body().into_vec()).unwrap()
+    };
+
+    assert_eq!(get("/"), b"site");
+    assert_eq!(get("/admin"), b"admin");
+    assert_eq!(get("/address"), get("/address"));
+}
+
+#[test]
+fn response_hooks() {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "/");
+    app.map_response(|mut res| {
+        res.headers_mut()
+            .insert("X-Hooked", http::HeaderValue::from_static("1"));
+        res
+    });
+    app.map_response(|mut res| {
+        let hooked = res.headers_mut().remove("X-Hooked").unwrap();
+        res.headers_mut().insert("X-Hooked-Twice", hooked);
+        res
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for path in &["/", "/missing"] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert!(!res.headers().contains_key("X-Hooked"));
+        assert_eq!(res.headers()["X-Hooked-Twice"], "1");
+    }
+}
+
+#[test]
+fn head_requests() {
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "hello");
+    app.at("/sized").get(async || {
+        http::Response::builder()
+            .header("Content-Length", "5")
+            .body(Body::from("hello"))
+            .unwrap()
+    });
+    app.at("/endless").get(async || {
+        let tick = || Ok::<_, std::io::Error>(bytes::Bytes::from_static(b"data: tick\n\n"));
+        let chunks = futures::stream::iter(std::iter::repeat(()).map(move |()| tick()));
+        http::Response::new(Body::from_stream(chunks))
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::head("/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["Content-Length"], "5");
+    assert_eq!(res.headers()["Content-Type"], "text/plain; charset=utf-8");
+    let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+    assert!(body.is_empty());
+
+    let req = http::Request::head("/sized").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["Content-Length"], "5");
+
+    let req = http::Request::head("/endless").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    assert!(!res.headers().contains_key("Content-Length"));
+}
+
+#[test]
+fn trailing_slash_redirect() {
+    let mut app = tide::App::new(());
+    app.at("/foo").get(async || "foo");
+    app.at("/api").nest(|router| {
+        router.config(TrailingSlash::Redirect);
+        router.at("/items").get(async || "items");
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/foo/").body(Body::empty()).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 200);
+
+    let req = http::Request::get("/api/items/?page=2")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers()["Location"], "/api/items?page=2");
+
+    let req = http::Request::get("/api/items").body(Body::empty()).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 200);
+}
+
+#[test]
+fn trailing_slash_redirect_stays_on_host() {
+    let mut app = tide::App::new(());
+    app.config(TrailingSlash::Redirect);
+    app.middleware(DefaultHeaders::new().header("X-Seen", "1"));
+    app.at("/{host}").get(async || "host");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("//evil.com/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers()["Location"], "/evil.com");
+    assert_eq!(res.headers()["X-Seen"], "1");
+}
+
+#[test]
+fn trailing_slash_redirect_preserves_method() {
+    let mut app = tide::App::new(());
+    app.config(TrailingSlash::Redirect);
+    app.at("/foo").post(async || "foo");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::post("/foo/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 308);
+    assert_eq!(res.headers()["Location"], "/foo");
+}
+
+#[test]
+fn host_routing() {
+    let mut app = tide::App::new(());
+    app.host("api.example.com").at("/").get(async || "api");
+    app.host("*.example.com").at("/").get(async || "tenant");
+    app.host("*.eu.example.com").at("/").get(async || "eu tenant");
+    app.at("/").get(async || "main");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (host, expected) in &[
+        ("api.example.com", "api"),
+        ("API.example.com:8080", "api"),
+        ("acme.example.com", "tenant"),
+        ("acme.eu.example.com", "eu tenant"),
+        ("example.com", "main"),
+        ("other.org", "main"),
+    ] {
+        let req = http::Request::get("/")
+            .header("Host", *host)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes(), "host {}", host);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Route name `home` is already used for path `/`")]
+fn route_names_clash_across_hosts() {
+    let mut app = tide::App::new(());
+    app.at("/").name("home").get(async || "main");
+    app.host("api.example.com")
+        .at("/")
+        .name("home")
+        .get(async || "api");
+    app.into_http_service();
+}
+
+#[test]
+fn case_insensitive_paths() {
+    async fn user(name: head::Path<String>) -> String {
+        name.0
+    }
+
+    let mut app = tide::App::new(());
+    app.case_insensitive(true);
+    app.at("/Users/{}").get(user);
+    app.at("/api").nest(|router| {
+        router.at("/Items/{}*").get(user);
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/users/Alice", "Alice"),
+        ("/USERS/Bob", "Bob"),
+        ("/API/items/A/B", "A/B"),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200, "path {}", path);
+        let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes(), "path {}", path);
+    }
+}
+
+#[test]
+fn reload() {
+    let mut app = tide::App::new(());
+    app.at("/a").get(async || "old");
+    let server = app.into_http_service();
+    let mut client = make_server(server.clone()).unwrap();
+
+    let mut app = tide::App::new(());
+    app.at("/a").get(async || "new");
+    app.at("/b").get(async || "b");
+    server.reload(app);
+
+    for (path, expected) in &[("/a", "new"), ("/b", "b")] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = client.simulate(req).unwrap();
+        let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+}
+
+fn pattern_header(ctx: RequestContext<()>) -> FutureObj<Response> {
+    let pattern = ctx.route_pattern().unwrap_or("none").to_owned();
+    FutureObj::new(Box::new(
+        async move {
+            let mut res = await!(ctx.next());
+            res.headers_mut()
+                .insert("X-Route", http::HeaderValue::from_str(&pattern).unwrap());
+            res
+        },
+    ))
+}
+
+#[test]
+fn route_pattern() {
+    let mut app = tide::App::new(());
+    app.middleware(pattern_header);
+    app.at("/message/{id}")
+        .get(async move |RoutePattern(pattern): RoutePattern| pattern.unwrap());
+    app.at("/api").nest(|router| {
+        router
+            .at("/files/{}*")
+            .get(async move |RoutePattern(pattern): RoutePattern| pattern.unwrap());
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/message/42", "/message/{id}"),
+        ("/api/files/a/b", "/api/files/{}*"),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.headers()["X-Route"], *expected);
+        let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    let req = http::Request::get("/missing").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["X-Route"], "none");
+}
+
+#[test]
+fn spa_fallback() {
+    let mut app = tide::App::new(());
+    app.at("/index.html").get(async || "app");
+    app.at("/api").nest(|router| {
+        router.default_handler(async || http::StatusCode::NOT_FOUND);
+        router.at("/users").get(async || "[]");
+    });
+    app.spa_fallback("/index.html");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let request = |method: http::Method, path: &str, accept: &str| {
+        http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header("Accept", accept)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = server
+        .simulate(request(http::Method::GET, "/users/42", "text/html,*/*"))
+        .unwrap();
+    assert_eq!(res.status(), 200);
+    let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, b"app");
+
+    for (method, path, accept) in &[
+        (http::Method::GET, "/users/42", "application/json"),
+        (http::Method::POST, "/users/42", "text/html"),
+        (http::Method::GET, "/api/missing", "text/html"),
+    ] {
+        let res = server
+            .simulate(request(method.clone(), path, accept))
+            .unwrap();
+        assert_eq!(res.status(), 404, "{} {} {}", method, path, accept);
+    }
+}
+
+fn deny(_ctx: RequestContext<bool>) -> FutureObj<Response> {
+    FutureObj::new(Box::new(futures::future::ready(
+        http::StatusCode::UNAUTHORIZED.into_response(),
+    )))
+}
+
+#[test]
+fn spa_fallback_case_insensitive() {
+    let mut app = tide::App::new(());
+    app.case_insensitive(true);
+    app.at("/index.html").get(async || "app");
+    app.spa_fallback("/Index.html");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/users/42")
+        .header("Accept", "text/html")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, b"app");
+}
+
+#[test]
+fn health_endpoints() {
+    use std::time::Duration;
+
+    let mut app = tide::App::new(false);
+    app.middleware(deny);
+    app.host("api.example.com").at("/").get(async || "api");
+    app.enable_health("/healthz", "/readyz")
+        .readiness_timeout(Duration::from_millis(50))
+        .readiness_check("cache", async move |_| Ok(()))
+        .readiness_check("db", async move |connected: bool| {
+            if connected {
+                Ok(())
+            } else {
+                Err("not connected".to_owned())
+            }
+        })
+        .readiness_check("queue", async move |_| {
+            let _ = await!(futures_timer::Delay::new(Duration::from_secs(5)));
+            Ok(())
+        });
+    let mut server = make_server(app.into_http_service()).unwrap();
+    let get = |path: &str, host: &str| {
+        http::Request::get(path)
+            .header(http::header::HOST, host)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    for host in &["localhost", "api.example.com"] {
+        let res = server.simulate(get("/healthz", host)).unwrap();
+        assert_eq!(res.status(), 200, "{}", host);
+    }
+    let res = server.simulate(get("/", "localhost")).unwrap();
+    assert_eq!(res.status(), 401);
+
+    let res = server.simulate(get("/readyz", "api.example.com")).unwrap();
+    assert_eq!(res.status(), 503);
+    let body = futures::executor::block_on(res.into_body().into_vec()).unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["status"], "unavailable");
+    assert_eq!(report["checks"]["cache"], "ok");
+    assert_eq!(report["checks"]["db"], "not connected");
+    assert_eq!(report["checks"]["queue"], "timed out after 50ms");
+}
+
+#[test]
+fn readiness_checks_run_concurrently() {
+    use futures::channel::oneshot;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // The first check times out unless the second one starts while it waits.
+    let (started, wait) = oneshot::channel::<()>();
+    let wait = Arc::new(Mutex::new(Some(wait)));
+    let started = Arc::new(Mutex::new(Some(started)));
+    let mut app = tide::App::new(());
+    app.enable_health("/healthz", "/readyz")
+        .readiness_timeout(Duration::from_millis(500))
+        .readiness_check("first", move |_| {
+            let wait = wait.lock().unwrap().take();
+            async move {
+                match wait {
+                    Some(wait) => await!(wait).map_err(|_| "cancelled".to_owned()),
+                    None => Ok(()),
+                }
+            }
+        })
+        .readiness_check("second", move |_| {
+            if let Some(started) = started.lock().unwrap().take() {
+                let _ = started.send(());
+            }
+            futures::future::ready(Ok(()))
+        });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/readyz").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+}
+
+#[test]
+#[should_panic(expected = "Cannot accept 101 headers per request, the server parses at most 100")]
+fn too_many_headers_allowed() {
+    Configuration::build().max_headers(101);
+}
+
+#[test]
+#[should_panic(expected = "Duplicate readiness check `db`")]
+fn duplicate_readiness_checks() {
+    let mut app = tide::App::new(());
+    app.readiness_check("db", async move |_| Ok(()))
+        .readiness_check("db", async move |_| Ok(()));
+}
+
+#[test]
+fn hyper_service() {
+    use futures01::Future;
+    use hyper_server::service::Service;
+
+    let mut app = tide::App::new(());
+    app.at("/").get(async || "/");
+    let mut service = app.into_http_service().into_hyper_service();
+
+    let req = http::Request::get("/")
+        .body(hyper_server::Body::empty())
+        .unwrap();
+    assert_eq!(service.call(req).wait().unwrap().status(), 200);
+    let req = http::Request::get("/missing")
+        .body(hyper_server::Body::empty())
+        .unwrap();
+    assert_eq!(service.call(req).wait().unwrap().status(), 404);
+}
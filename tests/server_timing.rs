@@ -0,0 +1,34 @@
+#![feature(futures_api, async_await)]
+
+use futures::future::FutureObj;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::{
+    middleware::{RequestContext, ServerTiming, Timed},
+    Response,
+};
+
+fn passthrough(ctx: RequestContext<()>) -> FutureObj<Response> {
+    ctx.next()
+}
+
+#[test]
+fn server_timing_header() {
+    let mut app = tide::App::new(());
+    app.middleware(ServerTiming::new());
+    app.middleware(Timed::new("passthrough", passthrough));
+    app.at("/").get(async || "/");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    let header = res.headers()["Server-Timing"].to_str().unwrap();
+    for phase in &["route", "extract", "endpoint", "passthrough", "total"] {
+        assert!(
+            header.contains(&format!("{};dur=", phase)),
+            "missing {} in {}",
+            phase,
+            header
+        );
+    }
+}
@@ -0,0 +1,58 @@
+#![feature(futures_api, async_await)]
+
+use http_service::Body;
+use tide::split::{Branch, Split};
+
+mod common;
+
+fn app() -> tide::App<()> {
+    let mut app = tide::App::new(());
+    app.at("/").get(Split::new(async || "a", async || "b").sticky_header("X-User"));
+    app
+}
+
+fn branch(res: &http::Response<Vec<u8>>) -> Branch {
+    *res.extensions().get::<Branch>().unwrap()
+}
+
+#[test]
+fn even_split() {
+    let mut server = common::server(app());
+
+    let mut served = Vec::new();
+    for _ in 0..4 {
+        let res = common::simulate(&mut server, common::get("/"));
+        let b = branch(&res);
+        assert_eq!(&**res.body(), if b == Branch::A { &b"a"[..] } else { &b"b"[..] });
+        served.push(b);
+    }
+    assert_eq!(served.iter().filter(|b| **b == Branch::B).count(), 2);
+}
+
+#[test]
+fn sticky_split() {
+    let mut server = common::server(app());
+
+    let mut served = Vec::new();
+    for _ in 0..4 {
+        let req = http::Request::get("/")
+            .header("X-User", "alice")
+            .body(Body::empty())
+            .unwrap();
+        served.push(branch(&common::simulate(&mut server, req)));
+    }
+    assert!(served.iter().all(|b| *b == served[0]));
+}
+
+#[test]
+fn sticky_branches_are_stable() {
+    let mut server = common::server(app());
+
+    for (user, expected) in &[("alice", Branch::A), ("bob", Branch::B)] {
+        let req = http::Request::get("/")
+            .header("X-User", *user)
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(branch(&common::simulate(&mut server, req)), *expected, "{}", user);
+    }
+}
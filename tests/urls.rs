@@ -0,0 +1,53 @@
+#![feature(futures_api, async_await)]
+
+use futures::executor::block_on;
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::Urls;
+
+async fn links(urls: Urls) -> String {
+    let links = vec![
+        urls.url_for("home", &[]),
+        urls.url_for("message", &["42"]),
+        urls.url_for("member", &["rust lang", "7"]),
+        urls.url_for("file", &["docs/a b.txt"]),
+        urls.url_for("message", &[]),
+        urls.url_for("unknown", &[]),
+    ];
+    links
+        .into_iter()
+        .map(|link| link.unwrap_or_else(|| "-".to_owned()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn url_for_named_routes() {
+    let mut app = tide::App::new(());
+    app.at("/").name("home").get(links);
+    app.at("/message/{id}").name("message").get(async || "");
+    app.at("/orgs").nest(|router| {
+        router
+            .at("{org}/members/{}")
+            .name("member")
+            .get(async || "");
+    });
+    app.at("/static/{path}*").name("file").get(async || "");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(
+        String::from_utf8(body).unwrap(),
+        "/ /message/42 /orgs/rust%20lang/members/7 /static/docs/a%20b.txt - -"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Route name `home` is already used for path `/`")]
+fn duplicate_route_names() {
+    let mut app = tide::App::new(());
+    app.at("/").name("home");
+    app.at("/index").name("home");
+}
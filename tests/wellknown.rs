@@ -0,0 +1,72 @@
+#![feature(futures_api, async_await)]
+
+use tide::wellknown;
+
+mod common;
+
+fn get(app: tide::App<()>, path: &str) -> (http::StatusCode, http::HeaderMap, Vec<u8>) {
+    let (parts, body) = common::simulate(&mut common::server(app), common::get(path)).into_parts();
+    (parts.status, parts.headers, body)
+}
+
+#[test]
+fn robots_and_favicon() {
+    let mut app = tide::App::new(());
+    app.at("/robots.txt").get(wellknown::robots_txt("User-agent: *\n"));
+    let (status, headers, body) = get(app, "/robots.txt");
+    assert_eq!(status, 200);
+    assert_eq!(headers["Content-Type"], "text/plain; charset=utf-8");
+    assert_eq!(&*body, &*b"User-agent: *\n");
+
+    let mut app = tide::App::new(());
+    app.at("/favicon.ico").get(wellknown::favicon(vec![0, 0, 1, 0]));
+    let (_, headers, body) = get(app, "/favicon.ico");
+    assert_eq!(headers["Content-Type"], "image/x-icon");
+    assert_eq!(body, vec![0, 0, 1, 0]);
+}
+
+#[test]
+fn acme_challenge_callback() {
+    let app = || {
+        let mut app = tide::App::new(());
+        app.at("/.well-known/acme-challenge/{}")
+            .get(wellknown::acme_challenge(|token| {
+                if token == "abc_123" {
+                    Some("abc_123.thumbprint".to_owned())
+                } else {
+                    None
+                }
+            }));
+        app
+    };
+
+    let (status, _, body) = get(app(), "/.well-known/acme-challenge/abc_123");
+    assert_eq!(status, 200);
+    assert_eq!(&*body, &*b"abc_123.thumbprint");
+
+    let (status, _, _) = get(app(), "/.well-known/acme-challenge/unknown");
+    assert_eq!(status, 404);
+}
+
+#[test]
+fn acme_challenge_directory() {
+    let dir = std::env::temp_dir().join(format!("tide-acme-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("token"), "token.thumbprint").unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+    let app = || {
+        let mut app = tide::App::new(());
+        app.at("/.well-known/acme-challenge/{}")
+            .get(wellknown::acme_challenge_dir(dir.clone()));
+        app
+    };
+
+    let (status, _, body) = get(app(), "/.well-known/acme-challenge/token");
+    assert_eq!(status, 200);
+    assert_eq!(&*body, &*b"token.thumbprint");
+
+    let (status, _, _) = get(app(), "/.well-known/acme-challenge/secret.txt");
+    assert_eq!(status, 404);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,25 @@
+#![feature(futures_api, async_await)]
+
+use http_service::Body;
+use http_service_mock::make_server;
+use tide::middleware::{DefaultHeaders, When};
+
+#[test]
+fn conditional_middleware() {
+    let mut app = tide::App::new(());
+    app.middleware(When(
+        |req: &tide::Request| req.uri().path() != "/healthz",
+        DefaultHeaders::new().header("X-Checked", "1"),
+    ));
+    app.at("/").get(async || "/");
+    app.at("/healthz").get(async || "ok");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.headers()["X-Checked"], "1");
+
+    let req = http::Request::get("/healthz").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert!(!res.headers().contains_key("X-Checked"));
+}
@@ -3,7 +3,8 @@
 use futures::executor::block_on;
 use http_service::Body;
 use http_service_mock::make_server;
-use tide::head::{Named, NamedSegment};
+use tide::configuration::{PathDecoding, PathErrorStatus};
+use tide::head::{Named, NamedSegment, NamedSegments, Params, Path, Segments};
 
 struct Number(i32);
 
@@ -58,6 +59,20 @@ fn invalid_segment_error() {
     assert_eq!(res.status(), 400);
 }
 
+#[test]
+fn configured_invalid_segment_error() {
+    let mut app = tide::App::new(());
+    app.config(PathErrorStatus(http::StatusCode::NOT_FOUND));
+    app.at("/add_one/{num}").get(add_one);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/add_one/a")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 404);
+}
+
 #[test]
 fn not_found_error() {
     let mut app = tide::App::new(());
@@ -68,3 +83,153 @@ fn not_found_error() {
     let res = server.simulate(req).unwrap();
     assert_eq!(res.status(), 404);
 }
+
+async fn echo_params(params: Params) -> String {
+    format!("{:?} {:?}", params.segments(), params.get("id"))
+}
+
+#[test]
+fn all_params() {
+    let mut app = tide::App::new(());
+    app.at("/orgs/{}/members/{id}").get(echo_params);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let req = http::Request::get("/orgs/rust/members/3")
+        .body(Body::empty())
+        .unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 200);
+    let body = block_on(res.into_body().into_vec()).unwrap();
+    assert_eq!(&*body, &*br#"["rust", "3"] Some("3")"#);
+}
+
+#[test]
+fn constrained_segments() {
+    let mut app = tide::App::new(());
+    app.at("/add_one/{num:i32}").get(add_one);
+    app.at("/orgs/{:alpha}").nest(|router| {
+        router.at("members/{id}").get(echo_params);
+    });
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    let mut status = |path: &str| {
+        let req = http::Request::get(path).body(Body::empty()).unwrap();
+        server.simulate(req).unwrap().status()
+    };
+    assert_eq!(status("/add_one/3"), 200);
+    assert_eq!(status("/add_one/a"), 404);
+    assert_eq!(status("/orgs/rust/members/3"), 200);
+    assert_eq!(status("/orgs/42/members/3"), 404);
+}
+
+#[test]
+#[should_panic(expected = "Unknown constraint `number` in path `/add_one/{num:number}`")]
+fn unknown_constraint() {
+    let mut app = tide::App::new(());
+    app.at("/add_one/{num:number}").get(add_one);
+}
+
+#[test]
+fn constraints_at_same_position() {
+    let mut app = tide::App::new(());
+    app.at("/users/{id:u32}").get(async || "id");
+    app.at("/users/{name:alpha}").get(async || "name");
+    app.at("/users/{name:alpha}").post(async || "created");
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (method, path, expected) in &[
+        ("GET", "/users/42", "id"),
+        ("GET", "/users/jane", "name"),
+        ("GET", "/users/j%C3%A9r%C3%B4me", "name"),
+        ("POST", "/users/jane", "created"),
+    ] {
+        let req = http::Request::builder()
+            .method(*method)
+            .uri(*path)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.simulate(req).unwrap();
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes(), "{} {}", method, path);
+    }
+
+    let req = http::Request::get("/users/jane-doe").body(Body::empty()).unwrap();
+    assert_eq!(server.simulate(req).unwrap().status(), 404);
+}
+
+#[test]
+#[should_panic(expected = "conflicts with existing wildcard segment `{num}`")]
+fn conflicting_wildcards() {
+    let mut app = tide::App::new(());
+    app.at("/add_one/{num}").get(add_one);
+    app.at("/add_one/{id}").post(add_one);
+}
+
+#[test]
+fn percent_decoding() {
+    async fn echo(Path(s): Path<String>) -> String {
+        s
+    }
+
+    let mut app = tide::App::new(());
+    app.at("/decoded/{}").get(echo);
+    app.at("/plus/{}").get(echo).config(PathDecoding::PercentAndPlus);
+    app.at("/raw/{}").get(echo).config(PathDecoding::Raw);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (path, expected) in &[
+        ("/decoded/caf%C3%A9%20au+lait", "café au+lait"),
+        ("/decoded/100%", "100%"),
+        ("/plus/caf%C3%A9%20au+lait", "café au lait"),
+        ("/raw/caf%C3%A9%20au+lait", "caf%C3%A9%20au+lait"),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), 200);
+        let body = block_on(res.into_body().into_vec()).unwrap();
+        assert_eq!(&*body, expected.as_bytes());
+    }
+
+    let req = http::Request::get("/decoded/%FF").body(Body::empty()).unwrap();
+    let res = server.simulate(req).unwrap();
+    assert_eq!(res.status(), 400);
+}
+
+#[test]
+fn multiple_segments() {
+    #[derive(serde_derive::Deserialize)]
+    struct Repo {
+        org: String,
+        id: u32,
+    }
+
+    async fn tuple(
+        Segments((org, id)): Segments<(String, u32)>,
+        Path(file): Path<String>,
+    ) -> String {
+        format!("{}/{}/{}", org, id, file)
+    }
+    async fn named(NamedSegments(repo): NamedSegments<Repo>) -> String {
+        format!("{}/{}", repo.org, repo.id)
+    }
+
+    let mut app = tide::App::new(());
+    app.at("/tuple/{}/{}/{}").get(tuple);
+    app.at("/named/{org}/{id}").get(named);
+    let mut server = make_server(app.into_http_service()).unwrap();
+
+    for (path, status, expected) in &[
+        ("/tuple/rust%20lang/7/main.rs", 200, "rust lang/7/main.rs"),
+        ("/named/rust%26lang/7", 200, "rust&lang/7"),
+        ("/tuple/rust/x/main.rs", 400, ""),
+        ("/named/rust/x", 400, ""),
+    ] {
+        let req = http::Request::get(*path).body(Body::empty()).unwrap();
+        let res = server.simulate(req).unwrap();
+        assert_eq!(res.status(), *status);
+        if *status == 200 {
+            let body = block_on(res.into_body().into_vec()).unwrap();
+            assert_eq!(&*body, expected.as_bytes());
+        }
+    }
+}